@@ -7,22 +7,459 @@
 //!
 //! The builder is best accessed via the preconstructed network objects in `nets.rs`.
 
-use std::marker::PhantomData;
+use std::{convert::TryFrom, marker::PhantomData};
+use thiserror::Error;
 
-use coins_core::{builder::TxBuilder, enc::AddressEncoder, types::tx::Transaction};
+use coins_core::{
+    builder::TxBuilder, enc::AddressEncoder, hashes::MarkedDigestOutput, ser::prefix_byte_len,
+    types::tx::Transaction,
+};
 
 use crate::{
+    descriptors::AddressKind,
     enc::encoder::{Address, BitcoinEncoderMarker},
     types::{
         legacy::LegacyTx,
-        script::{ScriptPubkey, ScriptSig, Witness},
-        tx::{BitcoinTransaction, BitcoinTx},
+        script::{ScriptPubkey, ScriptSig, StandardOutput, Witness},
+        tx::{BitcoinTransaction, BitcoinTx, TxError},
         txin::{BitcoinOutpoint, BitcoinTxIn},
         txout::TxOut,
+        utxo::{InputKind, Utxo},
         witness::{WitnessTransaction, WitnessTx},
     },
 };
 
+/// Errors produced by the builder.
+#[derive(Debug, Error)]
+pub enum BuilderError {
+    /// A fee was required, but no fee rate was set.
+    #[error("No fee rate set")]
+    MissingFeeRate,
+
+    /// The builder does not know the UTXO spent by this input. Use `spend_utxo`.
+    #[error("Unknown prevout for input spending {0:?}")]
+    MissingPrevout(BitcoinOutpoint),
+
+    /// The builder can't predict the signed weight of an input spending this UTXO.
+    #[error("Can't predict the weight of input spending {0:?}")]
+    UnknownInputWeight(BitcoinOutpoint),
+
+    /// The output index does not exist.
+    #[error("No output at index {0}")]
+    NoSuchOutput(usize),
+
+    /// The output selected to pay the fee is smaller than the fee.
+    #[error("Output {index} has value {value}, which can't cover the fee of {fee}")]
+    FeeExceedsOutput {
+        /// The index of the output
+        index: usize,
+        /// The value of the output
+        value: u64,
+        /// The fee
+        fee: u64,
+    },
+
+    /// The available UTXOs can't cover the target value and fee.
+    #[error("Insufficient funds: {available} available, {required} required")]
+    InsufficientFunds {
+        /// The total value of the UTXOs selected
+        available: u64,
+        /// The target value plus the fee
+        required: u64,
+    },
+
+    /// The fee rate or absolute fee exceeds the builder's fee guard.
+    #[error("Fee of {requested} exceeds the limit of {limit}")]
+    FeeTooHigh {
+        /// The requested fee rate in sat/vbyte, or the absolute fee in sats
+        requested: u64,
+        /// The configured limit
+        limit: u64,
+    },
+
+    /// The output's script pubkey is not a standard output type.
+    #[error("Output {0} has a nonstandard script pubkey")]
+    NonStandardOutput(usize),
+
+    /// After paying the fee, the output would be dust.
+    #[error("Output {index} would be dust with value {value}")]
+    DustOutput {
+        /// The index of the output
+        index: usize,
+        /// The value of the output after paying the fee
+        value: u64,
+    },
+
+    /// A locktime is set, but every input sequence is final, so consensus ignores the locktime.
+    #[error("Locktime {0} is ignored, as every input sequence is final")]
+    LocktimeIgnored(u32),
+
+    /// Both `sort_bip69` and `preserve_input_order` were requested.
+    #[error("Can't both sort inputs by BIP69 and preserve their order")]
+    ConflictingInputOrder,
+
+    /// Bubbled up from constructing the transaction
+    #[error(transparent)]
+    TxError(#[from] TxError),
+}
+
+/// Type alias for result with BuilderError
+pub type BuilderResult<T> = Result<T, BuilderError>;
+
+/// The maximum weight of a transaction accepted by default mempool policy.
+pub const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+
+/// The default maximum fee rate accepted by the builder, in sat/vbyte.
+pub const DEFAULT_MAX_FEE_RATE: u64 = 1_000;
+
+/// The default maximum absolute fee accepted by the builder, in sats. This is 0.01 BTC.
+pub const DEFAULT_MAX_ABSOLUTE_FEE: u64 = 1_000_000;
+
+/// What a build at a given fee rate would do with the excess input value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChangeDecision {
+    /// The excess is paid to a new change output at `index`.
+    Added {
+        /// The position of the change output
+        index: usize,
+        /// The value of the change output
+        value: u64,
+    },
+    /// The excess is below the dust threshold of a change output, so it is left to the fee.
+    DroppedAsDust {
+        /// The excess that a change output would have paid
+        value: u64,
+    },
+}
+
+/// Why the builder spends a UTXO.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SelectionReason {
+    /// The caller required it, via `spend_utxo`, `require_input` or `consolidate`.
+    Required,
+    /// A `CoinSelector` chose it, and it was added via `spend_selection`.
+    Selected,
+    /// `fund` added it to cover the target.
+    Funded {
+        /// The effective value of the UTXO at the fee rate passed to `fund`
+        effective_value: u64,
+    },
+}
+
+/// A report of the decisions a build at a given fee rate would make. Produced by
+/// `BitcoinTxBuilder::dry_run`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildReport {
+    /// The outpoints spent by the transaction, in input order, and why each is spent
+    pub inputs: Vec<(BitcoinOutpoint, SelectionReason)>,
+    /// The total value of the inputs
+    pub input_value: u64,
+    /// The total value of the outputs, excluding change
+    pub output_value: u64,
+    /// The fee the transaction would pay
+    pub fee: u64,
+    /// The estimated vsize of the signed transaction, including change
+    pub vsize: usize,
+    /// What happens to the excess input value
+    pub change: ChangeDecision,
+}
+
+/// Estimate the virtual size of a signed transaction with the given input and output kinds,
+/// without constructing it. This is intended for quick fee quotes, before inputs are selected.
+/// Input weights are predicted as in `InputKind::predicted_weight`.
+pub fn estimate_tx_vsize(inputs: &[InputKind], outputs: &[AddressKind]) -> usize {
+    // version, locktime, and the vin and vout length prefixes
+    let mut weight = 4
+        * (8 + prefix_byte_len(inputs.len() as u64) as usize
+            + prefix_byte_len(outputs.len() as u64) as usize);
+
+    weight += inputs.iter().map(|i| i.predicted_weight()).sum::<usize>();
+    if inputs.iter().any(|i| i.is_witness()) {
+        // segwit marker and flag, and an empty witness for each legacy input
+        weight += 2 + inputs.iter().filter(|i| !i.is_witness()).count();
+    }
+
+    weight += outputs
+        .iter()
+        .map(|o| {
+            let script_len = match o {
+                AddressKind::Pkh | AddressKind::PkhUncompressed => 25,
+                AddressKind::ShWpkh => 23,
+                AddressKind::Wpkh => 22,
+                AddressKind::Tr => 34,
+            };
+            TxOut::new(0, vec![0u8; script_len]).weight()
+        })
+        .sum::<usize>();
+
+    weight.div_ceil(4)
+}
+
+/// A placeholder for a change output, with the size and dust threshold of a P2WPKH output.
+fn placeholder_change(value: u64) -> TxOut {
+    let mut script_pubkey = vec![0x00, 0x14]; // OP_0 PUSH_20
+    script_pubkey.extend([0u8; 20]);
+    TxOut::new(value, script_pubkey)
+}
+
+/// The UTXOs chosen by a `CoinSelector`, and what becomes of their excess value. The selected
+/// value is always `target + fee + change`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selection {
+    /// The selected UTXOs
+    pub utxos: Vec<Utxo>,
+    /// The total value of the selected UTXOs
+    pub value: u64,
+    /// The fee for spending the selected UTXOs, and for the change output if there is one. If
+    /// the change would be dust, it is included here instead.
+    pub fee: u64,
+    /// The value of the change output. 0 if the excess was dropped to the fee.
+    pub change: u64,
+}
+
+impl Selection {
+    /// Calculate the fee and change of spending `utxos` to cover `target` at `fee_rate`
+    /// sat/vbyte. A P2WPKH change output is added if the excess covers its fee, plus
+    /// `dust_threshold`. Otherwise the excess is dropped to the fee.
+    ///
+    /// Errors with `InsufficientFunds` if the UTXOs can't cover the target and their fees.
+    pub fn new(
+        utxos: Vec<Utxo>,
+        target: u64,
+        fee_rate: u64,
+        dust_threshold: u64,
+    ) -> BuilderResult<Self> {
+        let value = utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+        let insufficient = |required| BuilderError::InsufficientFunds {
+            available: value,
+            required,
+        };
+
+        // A target or fee that overflows can't be covered by any set of UTXOs
+        let mut input_fee = 0u64;
+        for utxo in utxos.iter() {
+            let weight = utxo
+                .predicted_input_weight()
+                .ok_or(BuilderError::UnknownInputWeight(utxo.outpoint))?;
+            input_fee = fee_rate
+                .checked_mul(weight.div_ceil(4) as u64)
+                .and_then(|fee| input_fee.checked_add(fee))
+                .ok_or_else(|| insufficient(u64::MAX))?;
+        }
+        let required = target
+            .checked_add(input_fee)
+            .ok_or_else(|| insufficient(u64::MAX))?;
+
+        let excess = value
+            .checked_sub(required)
+            .ok_or_else(|| insufficient(required))?;
+        let change_fee = change_output_fee(fee_rate);
+        let (fee, change) = if excess >= change_fee.saturating_add(dust_threshold) {
+            (input_fee + change_fee, excess - change_fee)
+        } else {
+            (input_fee + excess, 0)
+        };
+        Ok(Self {
+            utxos,
+            value,
+            fee,
+            change,
+        })
+    }
+}
+
+/// The fee for adding a P2WPKH change output at `fee_rate` sat/vbyte.
+fn change_output_fee(fee_rate: u64) -> u64 {
+    fee_rate.saturating_mul(placeholder_change(0).weight().div_ceil(4) as u64)
+}
+
+/// The effective value and index of each UTXO at `fee_rate`, skipping UTXOs that cost more to
+/// spend than they are worth, sorted from highest to lowest value.
+fn by_effective_value(utxos: &[Utxo], fee_rate: u64) -> BuilderResult<Vec<(u64, usize)>> {
+    let mut pool = vec![];
+    for (index, utxo) in utxos.iter().enumerate() {
+        let effective_value = utxo
+            .effective_value(fee_rate)
+            .ok_or(BuilderError::UnknownInputWeight(utxo.outpoint))?;
+        if effective_value > 0 {
+            pool.push((effective_value as u64, index));
+        }
+    }
+    pool.sort_by_key(|(value, _)| std::cmp::Reverse(*value));
+    Ok(pool)
+}
+
+/// The UTXOs at `indices`, in the order they appear in `utxos`.
+fn in_caller_order(utxos: &[Utxo], mut indices: Vec<usize>) -> Vec<Utxo> {
+    indices.sort_unstable();
+    indices.into_iter().map(|i| utxos[i].clone()).collect()
+}
+
+/// A strategy for choosing which UTXOs fund a transaction.
+pub trait CoinSelector {
+    /// Select UTXOs from `utxos` covering `target` plus the marginal fee of each selected input
+    /// at `fee_rate` sat/vbyte. `target` is typically the value of the outputs plus the fee for
+    /// the rest of the transaction. UTXOs that cost more to spend than they are worth are
+    /// never selected. The selected UTXOs are listed in the order they appear in `utxos`, so
+    /// that `spend_selection` doesn't reorder the caller's inputs.
+    ///
+    /// Errors with `InsufficientFunds` if the UTXOs can't cover the target, and with
+    /// `UnknownInputWeight` if the weight of an input spending one of them can't be predicted.
+    fn select(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> BuilderResult<Selection>;
+}
+
+/// Select the UTXOs with the highest effective value first, until the target is covered. This
+/// spends few inputs, at the cost of usually producing change.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LargestFirst {
+    /// Change below this value is dropped to the fee. Defaults to the dust threshold of a P2WPKH
+    /// output.
+    pub dust_threshold: u64,
+}
+
+impl Default for LargestFirst {
+    fn default() -> Self {
+        Self {
+            dust_threshold: placeholder_change(0).dust_threshold(),
+        }
+    }
+}
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> BuilderResult<Selection> {
+        let mut selected = vec![];
+        let mut effective_value = 0;
+        for (value, index) in by_effective_value(utxos, fee_rate)? {
+            if effective_value >= target {
+                break;
+            }
+            effective_value += value;
+            selected.push(index);
+        }
+        Selection::new(
+            in_caller_order(utxos, selected),
+            target,
+            fee_rate,
+            self.dust_threshold,
+        )
+    }
+}
+
+/// Search for a selection that needs no change output, as in Bitcoin Core's branch and bound
+/// coin selection. A selection needs no change output if its excess is too small to pay for
+/// one, plus `dust_threshold`. This avoids the fee of the change output, and of later spending
+/// it, and does not reveal which output is change.
+///
+/// The search explores at most `max_tries` branches, and takes the first match found. If none
+/// is found, this falls back to `LargestFirst`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BranchAndBound {
+    /// Excess below this value, after the change output fee, is dropped to the fee. Defaults to
+    /// the dust threshold of a P2WPKH output.
+    pub dust_threshold: u64,
+    /// The maximum number of branches to explore. Defaults to 100,000, as in Bitcoin Core.
+    pub max_tries: usize,
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        Self {
+            dust_threshold: placeholder_change(0).dust_threshold(),
+            max_tries: 100_000,
+        }
+    }
+}
+
+impl BranchAndBound {
+    /// Depth-first search for a subset of `values[index..]` whose sum, added to `selected_sum`,
+    /// is in `[target, upper)`. `remaining` is the sum of `values[index..]`.
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        values: &[u64],
+        index: usize,
+        selected_sum: u64,
+        remaining: u64,
+        target: u64,
+        upper: u64,
+        tries: &mut usize,
+        selected: &mut Vec<usize>,
+    ) -> bool {
+        if selected_sum >= upper || *tries == 0 {
+            return false;
+        }
+        if selected_sum >= target {
+            return true;
+        }
+        if index == values.len() || selected_sum + remaining < target {
+            return false;
+        }
+        *tries -= 1;
+
+        let value = values[index];
+        selected.push(index);
+        if Self::search(
+            values,
+            index + 1,
+            selected_sum + value,
+            remaining - value,
+            target,
+            upper,
+            tries,
+            selected,
+        ) {
+            return true;
+        }
+        selected.pop();
+        Self::search(
+            values,
+            index + 1,
+            selected_sum,
+            remaining - value,
+            target,
+            upper,
+            tries,
+            selected,
+        )
+    }
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> BuilderResult<Selection> {
+        let pool = by_effective_value(utxos, fee_rate)?;
+        let values = pool.iter().map(|(value, _)| *value).collect::<Vec<_>>();
+        let upper = target
+            .saturating_add(change_output_fee(fee_rate))
+            .saturating_add(self.dust_threshold);
+
+        let mut tries = self.max_tries;
+        let mut selected = vec![];
+        let remaining = values.iter().sum();
+        if Self::search(
+            &values,
+            0,
+            0,
+            remaining,
+            target,
+            upper,
+            &mut tries,
+            &mut selected,
+        ) {
+            let selected = selected.iter().map(|i| pool[*i].1).collect();
+            return Selection::new(
+                in_caller_order(utxos, selected),
+                target,
+                fee_rate,
+                self.dust_threshold,
+            );
+        }
+
+        LargestFirst {
+            dust_threshold: self.dust_threshold,
+        }
+        .select(utxos, target, fee_rate)
+    }
+}
+
 /// This is a generic builder for Bitcoin transactions. It allows you to easily build legacy and
 /// witness transactions.
 ///
@@ -36,9 +473,19 @@ pub struct BitcoinTxBuilder<T: AddressEncoder> {
     version: u32,
     vin: Vec<BitcoinTxIn>,
     vout: Vec<TxOut>,
+    extra_outputs: Vec<usize>,
     locktime: u32,
     witnesses: Vec<Witness>,
     produce_witness: bool,
+    prevouts: Vec<(Utxo, SelectionReason)>,
+    fee_rate: Option<u64>,
+    subtract_fee_from: Option<usize>,
+    change_script: Option<ScriptPubkey>,
+    max_fee_rate: u64,
+    max_absolute_fee: u64,
+    allow_ignored_locktime: bool,
+    sort_bip69: bool,
+    preserve_input_order: bool,
     encoder: PhantomData<fn(T) -> T>,
 }
 
@@ -83,19 +530,52 @@ where
     }
 
     /// Consume self, produce a legacy tx. Discard any witness information in the builder
-    pub fn build_legacy(self) -> Result<LegacyTx, <LegacyTx as Transaction>::TxError> {
-        LegacyTx::new(self.version, self.vin, self.vout, self.locktime)
+    pub fn build_legacy(mut self) -> BuilderResult<LegacyTx> {
+        self.check_extra_outputs()?;
+        self.check_locktime()?;
+        self.apply_change()?;
+        self.apply_fee()?;
+        self.apply_ordering()?;
+        Ok(LegacyTx::new(
+            self.version,
+            self.vin,
+            self.vout,
+            self.locktime,
+        )?)
     }
 
     /// Consume self, produce a witness tx
-    pub fn build_witness(self) -> Result<WitnessTx, <WitnessTx as Transaction>::TxError> {
-        <WitnessTx as WitnessTransaction>::new(
+    pub fn build_witness(mut self) -> BuilderResult<WitnessTx> {
+        self.check_extra_outputs()?;
+        self.check_locktime()?;
+        self.apply_change()?;
+        self.apply_fee()?;
+        self.apply_ordering()?;
+        Ok(<WitnessTx as WitnessTransaction>::new(
             self.version,
             self.vin,
             self.vout,
             self.witnesses,
             self.locktime,
-        )
+        )?)
+    }
+
+    /// Append outputs not owned by the wallet, such as a coordinator's outputs. They are
+    /// ordinary outputs: they count toward `output_value`, and so toward the funding target and
+    /// the fee estimate.
+    ///
+    /// `fund` and the build error if any of the outputs is dust or has a nonstandard script
+    /// pubkey.
+    pub fn with_extra_outputs(mut self, outputs: Vec<TxOut>) -> Self {
+        let start = self.vout.len();
+        self.extra_outputs.extend(start..start + outputs.len());
+        self.vout.extend(outputs);
+        self
+    }
+
+    /// Sum the values of the outputs.
+    pub fn output_value(&self) -> u64 {
+        self.vout.iter().map(|o| o.value).sum()
     }
 
     /// Add an output paying `value` to `script_pubkey`
@@ -104,6 +584,542 @@ where
         self.vout.push(output);
         self
     }
+
+    /// Add an input spending `utxo`. Unlike `spend`, this records the UTXO's value and script
+    /// pubkey, so that the builder can estimate the transaction's size and fee. Spending a
+    /// witness UTXO causes the builder to produce a witness transaction.
+    pub fn spend_utxo(self, utxo: &Utxo, sequence: u32) -> Self {
+        self.spend_utxo_for(utxo, sequence, SelectionReason::Required)
+    }
+
+    /// Add an input spending `utxo`, recording why it is spent.
+    fn spend_utxo_for(mut self, utxo: &Utxo, sequence: u32, reason: SelectionReason) -> Self {
+        self.produce_witness |= utxo.is_witness();
+        self.prevouts.push((utxo.clone(), reason));
+        self.spend(utxo.outpoint, sequence)
+    }
+
+    /// Set the fee rate in satoshis per vbyte.
+    pub fn fee_rate(mut self, sat_per_vb: u64) -> Self {
+        self.fee_rate = Some(sat_per_vb);
+        self
+    }
+
+    /// Set the maximum fee rate in sat/vbyte. Fee estimation errors if the fee rate is higher.
+    /// Defaults to `DEFAULT_MAX_FEE_RATE`.
+    pub fn max_fee_rate(mut self, sat_per_vb: u64) -> Self {
+        self.max_fee_rate = sat_per_vb;
+        self
+    }
+
+    /// Set the maximum absolute fee in sats. Fee estimation errors if the fee is higher.
+    /// Defaults to `DEFAULT_MAX_ABSOLUTE_FEE`.
+    pub fn max_absolute_fee(mut self, sats: u64) -> Self {
+        self.max_absolute_fee = sats;
+        self
+    }
+
+    /// Allow building a transaction whose locktime is ignored because every input sequence is
+    /// final. Otherwise, building such a transaction errors with `LocktimeIgnored`. Builders
+    /// made from an existing transaction allow it by default, so that it can be rebuilt as-is.
+    pub fn allow_ignored_locktime(mut self, allow: bool) -> Self {
+        self.allow_ignored_locktime = allow;
+        self
+    }
+
+    /// Sort inputs and outputs at build time, as specified by BIP69. Inputs are sorted by the
+    /// big-endian txid and then the index of the outpoint they spend, and outputs by value and
+    /// then script pubkey. Witnesses are moved with their inputs.
+    ///
+    /// The build errors if `preserve_input_order` is also set.
+    pub fn sort_bip69(mut self, sort: bool) -> Self {
+        self.sort_bip69 = sort;
+        self
+    }
+
+    /// Keep inputs in the order they were added, e.g. when a protocol has assigned positions to
+    /// specific inputs. The builder never reorders inputs when this is set. Inputs added via
+    /// `spend_selection` keep the order of the UTXOs passed to the `CoinSelector`.
+    ///
+    /// The build errors if `sort_bip69` is also set.
+    pub fn preserve_input_order(mut self, preserve: bool) -> Self {
+        self.preserve_input_order = preserve;
+        self
+    }
+
+    /// Pay the excess input value to `address` at build time. The change is the input value,
+    /// less the output value and the fee at the builder's fee rate. The fee is estimated with the
+    /// change output included. If the change would be dust, no change output is added, and the
+    /// excess is left to the fee. Requires that all inputs were added via `spend_utxo`.
+    ///
+    /// If an output is marked by `subtract_fee_from_output`, it pays the fee instead, and the
+    /// change is the input value less the output value.
+    ///
+    /// The build errors with `MissingFeeRate` if no fee rate is set, and with
+    /// `InsufficientFunds` if the inputs can't cover the outputs and fee.
+    pub fn change_address(mut self, address: &Address) -> Self {
+        self.change_script = Some(T::decode_address(address));
+        self
+    }
+
+    /// Mark the output at `index` to pay the fee. At build time, its value will be reduced by
+    /// the fee computed from the fee rate and the estimated vsize. The build errors if the output
+    /// can't cover the fee, or would become dust.
+    pub fn subtract_fee_from_output(mut self, index: usize) -> Self {
+        self.subtract_fee_from = Some(index);
+        self
+    }
+
+    /// Consolidate UTXOs into a single output paying `destination`. This spends up to
+    /// `max_inputs` new UTXOs from `utxos`, in order, stopping early if the estimated weight would
+    /// exceed `MAX_STANDARD_TX_WEIGHT`. Inputs signal RBF.
+    ///
+    /// Inputs already added via `spend_utxo` are kept, and are not added again. The output pays
+    /// the value of all inputs, less the value of any other outputs, and less the fee at
+    /// `fee_rate` sat/vbyte, which is subtracted at build time.
+    ///
+    /// Errors if the builder can't predict the weight of an input spending one of the UTXOs, if
+    /// an existing input was not added via `spend_utxo`, or if the inputs can't cover the other
+    /// outputs.
+    pub fn consolidate(
+        self,
+        utxos: &[Utxo],
+        destination: &Address,
+        fee_rate: u64,
+        max_inputs: usize,
+    ) -> BuilderResult<Self> {
+        let mut builder = self.pay(0, destination).fee_rate(fee_rate);
+        let index = builder.vout.len() - 1;
+        let mut weight = builder.estimated_weight()?;
+
+        // skip existing inputs before capping, so that they don't count toward `max_inputs`
+        let candidates = utxos
+            .iter()
+            .filter(|utxo| builder.prevout(&utxo.outpoint).is_none())
+            .take(max_inputs)
+            .collect::<Vec<_>>();
+        for utxo in candidates {
+            let witness = builder.produce_witness || !builder.witnesses.is_empty();
+            let inputs = builder.vin.len() as u64;
+
+            let mut added = utxo
+                .predicted_input_weight()
+                .ok_or(BuilderError::UnknownInputWeight(utxo.outpoint))?;
+            added += 4 * (prefix_byte_len(inputs + 1) - prefix_byte_len(inputs)) as usize;
+            if witness && !utxo.is_witness() {
+                // an empty witness for the legacy input
+                added += 1;
+            } else if !witness && utxo.is_witness() {
+                // segwit marker and flag, and an empty witness for each existing legacy input
+                added += 2 + inputs as usize;
+            }
+
+            if weight + added > MAX_STANDARD_TX_WEIGHT {
+                break;
+            }
+            weight += added;
+            builder = builder.spend_utxo(utxo, 0xffff_fffd);
+        }
+
+        // the consolidation output is still 0, so this is the value of the other outputs
+        let available = builder.input_value()?;
+        let required = builder.output_value();
+        builder.vout[index].value =
+            available
+                .checked_sub(required)
+                .ok_or(BuilderError::InsufficientFunds {
+                    available,
+                    required,
+                })?;
+        Ok(builder.subtract_fee_from_output(index))
+    }
+
+    /// Add an input spending each UTXO chosen by a `CoinSelector`, in the order the selection
+    /// lists them. The inputs signal RBF. This
+    /// does not add a change output. If `selection.change` is non-zero, the caller should pay it
+    /// to a change address.
+    pub fn spend_selection(mut self, selection: &Selection) -> Self {
+        for utxo in selection.utxos.iter() {
+            self = self.spend_utxo_for(utxo, 0xffff_fffd, SelectionReason::Selected);
+        }
+        self
+    }
+
+    /// Require that the transaction spend `utxo`. It is added as an input immediately, so coin
+    /// selection via `fund` always includes it, and its value counts toward the target. The
+    /// input signals RBF.
+    pub fn require_input(self, utxo: &Utxo) -> Self {
+        self.spend_utxo(utxo, 0xffff_fffd)
+    }
+
+    /// Add inputs from `utxos`, in order, until they cover `target` plus the fee at `fee_rate`
+    /// sat/vbyte. The fee is re-estimated as each input is added. UTXOs that cost more to spend
+    /// than they are worth at the fee rate are skipped. Inputs signal RBF.
+    ///
+    /// Inputs already added via `spend_utxo` or `require_input` count toward the target, and are
+    /// not added again.
+    ///
+    /// This does not add outputs. `target` is typically the sum of the builder's outputs, and
+    /// any excess should be sent to change.
+    ///
+    /// This differs from `LargestFirst`, which sorts UTXOs by effective value and only counts
+    /// the fee of the inputs it selects. `fund` keeps the caller's order, which may express a
+    /// preference such as spending older UTXOs first, and counts the fee of the whole
+    /// transaction, including its outputs and existing inputs. To choose with a
+    /// `CoinSelector`, pass its `Selection` to `spend_selection`.
+    pub fn fund(mut self, utxos: &[Utxo], target: u64, fee_rate: u64) -> BuilderResult<Self> {
+        self.check_extra_outputs()?;
+        self = self.fee_rate(fee_rate);
+        let mut available = self
+            .prevouts
+            .iter()
+            .map(|(utxo, _)| utxo.value)
+            .sum::<u64>();
+        let required = |builder: &Self| -> BuilderResult<u64> {
+            // a target that overflows can't be covered by any set of UTXOs
+            Ok(target.saturating_add(builder.estimated_fee()?))
+        };
+        for utxo in utxos.iter() {
+            let required = required(&self)?;
+            if available >= required {
+                return Ok(self);
+            }
+            if self.prevout(&utxo.outpoint).is_some() {
+                continue;
+            }
+
+            let effective_value = utxo
+                .effective_value(fee_rate)
+                .ok_or(BuilderError::UnknownInputWeight(utxo.outpoint))?;
+            if effective_value <= 0 {
+                continue;
+            }
+            available += utxo.value;
+            let reason = SelectionReason::Funded {
+                effective_value: effective_value as u64,
+            };
+            self = self.spend_utxo_for(utxo, 0xffff_fffd, reason);
+        }
+
+        let required = required(&self)?;
+        if available >= required {
+            Ok(self)
+        } else {
+            Err(BuilderError::InsufficientFunds {
+                available,
+                required,
+            })
+        }
+    }
+
+    /// Preview the value of the change output that building now would add at `fee_rate`
+    /// sat/vbyte, without modifying the builder. The change pays the `change_address`, or a
+    /// P2WPKH output if none is set. Returns 0 if the change would be dust, and so left to the
+    /// fee. Requires that all inputs were added via `spend_utxo`. The preview assumes the inputs
+    /// pay the fee, and ignores `subtract_fee_from_output`.
+    ///
+    /// Errors with `InsufficientFunds` if the inputs can't cover the outputs and fee.
+    pub fn preview_change(&self, fee_rate: u64) -> BuilderResult<u64> {
+        match self.plan_change(fee_rate, &self.change_script_or_placeholder(), true)? {
+            ChangeDecision::Added { value, .. } => Ok(value),
+            ChangeDecision::DroppedAsDust { .. } => Ok(0),
+        }
+    }
+
+    /// Report the decisions that building now would make at `fee_rate` sat/vbyte, without
+    /// modifying the builder. This lists the spent outpoints and why each is spent, the fee, and
+    /// whether the excess input value is paid to a change output appended to the outputs, or
+    /// dropped to the fee as dust. The change pays the `change_address`, or a P2WPKH output if
+    /// none is set. Requires that all inputs were added via `spend_utxo`. The report assumes the
+    /// inputs pay the fee, and ignores `subtract_fee_from_output`.
+    ///
+    /// Errors with `InsufficientFunds` if the inputs can't cover the outputs and fee.
+    pub fn dry_run(&self, fee_rate: u64) -> BuilderResult<BuildReport> {
+        let input_value = self.input_value()?;
+        let output_value = self.output_value();
+        let script_pubkey = self.change_script_or_placeholder();
+
+        let change = self.plan_change(fee_rate, &script_pubkey, true)?;
+        let (weight, change_value) = match change {
+            ChangeDecision::Added { value, .. } => (
+                self.estimated_weight_with(&TxOut::new(value, script_pubkey))?,
+                value,
+            ),
+            ChangeDecision::DroppedAsDust { .. } => (self.estimated_weight()?, 0),
+        };
+        let inputs = self
+            .vin
+            .iter()
+            .map(|input| {
+                self.prevout_and_reason(&input.outpoint)
+                    .map(|(_, reason)| (input.outpoint, *reason))
+                    .ok_or(BuilderError::MissingPrevout(input.outpoint))
+            })
+            .collect::<BuilderResult<_>>()?;
+        Ok(BuildReport {
+            inputs,
+            input_value,
+            output_value,
+            fee: input_value - output_value - change_value,
+            vsize: weight.div_ceil(4),
+            change,
+        })
+    }
+
+    /// The script pubkey paid by change: the `change_address`, or a P2WPKH placeholder.
+    fn change_script_or_placeholder(&self) -> ScriptPubkey {
+        self.change_script
+            .clone()
+            .unwrap_or_else(|| placeholder_change(0).script_pubkey)
+    }
+
+    /// Decide what becomes of the excess input value at `fee_rate` sat/vbyte, if change pays
+    /// `script_pubkey`. The excess is paid to a change output appended to the outputs, unless
+    /// that output would be dust, in which case it is left to the fee. If `inputs_pay_fee` is
+    /// false, the fee is instead subtracted from an output later, and the change output gets
+    /// the full excess. Requires that all inputs were added via `spend_utxo`.
+    ///
+    /// Errors with `InsufficientFunds` if the inputs can't cover the outputs and fee.
+    fn plan_change(
+        &self,
+        fee_rate: u64,
+        script_pubkey: &ScriptPubkey,
+        inputs_pay_fee: bool,
+    ) -> BuilderResult<ChangeDecision> {
+        let available = self.input_value()?;
+        let output_value = self.output_value();
+        let fee = |weight| {
+            if inputs_pay_fee {
+                self.fee_for_weight(fee_rate, weight)
+            } else {
+                Ok(0)
+            }
+        };
+
+        // An output's weight doesn't depend on its value, so the fee is estimated once
+        let mut change = TxOut::new(0, script_pubkey.clone());
+        let required = output_value + fee(self.estimated_weight_with(&change)?)?;
+        if let Some(value) = available.checked_sub(required) {
+            change.value = value;
+            if !change.is_dust() {
+                return Ok(ChangeDecision::Added {
+                    index: self.vout.len(),
+                    value,
+                });
+            }
+        }
+
+        let required = output_value + fee(self.estimated_weight()?)?;
+        let value = available
+            .checked_sub(required)
+            .ok_or(BuilderError::InsufficientFunds {
+                available,
+                required,
+            })?;
+        Ok(ChangeDecision::DroppedAsDust { value })
+    }
+
+    /// Sum the values of the UTXOs spent by the inputs. Requires that all inputs were added via
+    /// `spend_utxo`.
+    fn input_value(&self) -> BuilderResult<u64> {
+        self.vin
+            .iter()
+            .map(|input| {
+                self.prevout(&input.outpoint)
+                    .map(|utxo| utxo.value)
+                    .ok_or(BuilderError::MissingPrevout(input.outpoint))
+            })
+            .sum()
+    }
+
+    /// Estimate the weight of the transaction with `output` appended to the outputs.
+    fn estimated_weight_with(&self, output: &TxOut) -> BuilderResult<usize> {
+        let outputs = self.vout.len() as u64;
+        Ok(self.estimated_weight()?
+            + output.weight()
+            + 4 * (prefix_byte_len(outputs + 1) - prefix_byte_len(outputs)) as usize)
+    }
+
+    /// Find the UTXO spent by an input, if it was added via `spend_utxo`.
+    fn prevout(&self, outpoint: &BitcoinOutpoint) -> Option<&Utxo> {
+        self.prevout_and_reason(outpoint).map(|(utxo, _)| utxo)
+    }
+
+    /// Find the UTXO spent by an input, and why it is spent, if it was added via `spend_utxo`.
+    fn prevout_and_reason(&self, outpoint: &BitcoinOutpoint) -> Option<&(Utxo, SelectionReason)> {
+        self.prevouts
+            .iter()
+            .find(|(utxo, _)| &utxo.outpoint == outpoint)
+    }
+
+    /// Estimate the weight of the transaction once all inputs are signed. Requires that all
+    /// inputs were added via `spend_utxo`.
+    pub fn estimated_weight(&self) -> BuilderResult<usize> {
+        // version, locktime, and the vin and vout length prefixes
+        let mut weight = 4
+            * (8 + prefix_byte_len(self.vin.len() as u64) as usize
+                + prefix_byte_len(self.vout.len() as u64) as usize);
+
+        let mut legacy_inputs = 0;
+        for input in self.vin.iter() {
+            let utxo = self
+                .prevout(&input.outpoint)
+                .ok_or(BuilderError::MissingPrevout(input.outpoint))?;
+            weight += utxo
+                .predicted_input_weight()
+                .ok_or(BuilderError::UnknownInputWeight(input.outpoint))?;
+            if !utxo.is_witness() {
+                legacy_inputs += 1;
+            }
+        }
+
+        weight += self.vout.iter().map(TxOut::weight).sum::<usize>();
+
+        if self.produce_witness || !self.witnesses.is_empty() {
+            // segwit marker and flag, and an empty witness for each legacy input
+            weight += 2 + legacy_inputs;
+        }
+        Ok(weight)
+    }
+
+    /// Estimate the vsize of the transaction once all inputs are signed.
+    pub fn estimated_vsize(&self) -> BuilderResult<usize> {
+        Ok(self.estimated_weight()?.div_ceil(4))
+    }
+
+    /// Estimate the fee for the transaction at the configured fee rate. Errors if the fee rate
+    /// or fee exceeds the builder's fee guards.
+    pub fn estimated_fee(&self) -> BuilderResult<u64> {
+        let fee_rate = self.fee_rate.ok_or(BuilderError::MissingFeeRate)?;
+        self.fee_for_weight(fee_rate, self.estimated_weight()?)
+    }
+
+    /// Calculate the fee for a transaction of `weight` at `fee_rate`, subject to the fee guards.
+    fn fee_for_weight(&self, fee_rate: u64, weight: usize) -> BuilderResult<u64> {
+        if fee_rate > self.max_fee_rate {
+            return Err(BuilderError::FeeTooHigh {
+                requested: fee_rate,
+                limit: self.max_fee_rate,
+            });
+        }
+        let fee = fee_rate.saturating_mul(weight.div_ceil(4) as u64);
+        if fee > self.max_absolute_fee {
+            return Err(BuilderError::FeeTooHigh {
+                requested: fee,
+                limit: self.max_absolute_fee,
+            });
+        }
+        Ok(fee)
+    }
+
+    /// Error if an output added via `with_extra_outputs` is dust or has a nonstandard script
+    /// pubkey.
+    fn check_extra_outputs(&self) -> BuilderResult<()> {
+        for index in self.extra_outputs.iter().copied() {
+            let output = &self.vout[index];
+            if StandardOutput::try_from(&output.script_pubkey).is_err() {
+                return Err(BuilderError::NonStandardOutput(index));
+            }
+            if output.is_dust() {
+                return Err(BuilderError::DustOutput {
+                    index,
+                    value: output.value,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Error if a locktime is set, but no input has a non-final sequence to enable it.
+    fn check_locktime(&self) -> BuilderResult<()> {
+        if self.locktime != 0
+            && !self.allow_ignored_locktime
+            && self.vin.iter().all(|input| input.sequence == 0xffff_ffff)
+        {
+            return Err(BuilderError::LocktimeIgnored(self.locktime));
+        }
+        Ok(())
+    }
+
+    /// Apply the input and output ordering requested by `sort_bip69`. This must run after
+    /// `apply_fee`, which refers to an output by index.
+    fn apply_ordering(&mut self) -> BuilderResult<()> {
+        if !self.sort_bip69 {
+            return Ok(());
+        }
+        if self.preserve_input_order {
+            return Err(BuilderError::ConflictingInputOrder);
+        }
+
+        let key = |input: &BitcoinTxIn| {
+            (
+                input.outpoint.txid.reversed().as_slice().to_vec(),
+                input.outpoint.idx,
+            )
+        };
+        if !self.witnesses.is_empty() {
+            // inputs without a witness yet get an empty one, as in `WitnessTx::new`, so that
+            // every witness moves with its input
+            self.witnesses.resize(self.vin.len(), Witness::default());
+            let mut pairs: Vec<_> = self.vin.drain(..).zip(self.witnesses.drain(..)).collect();
+            pairs.sort_by_key(|(input, _)| key(input));
+            let (vin, witnesses) = pairs.into_iter().unzip();
+            self.vin = vin;
+            self.witnesses = witnesses;
+        } else {
+            self.vin.sort_by_key(key);
+        }
+        self.vout.sort();
+        Ok(())
+    }
+
+    /// Append the change output requested by `change_address`, unless it would be dust. This
+    /// must run before `apply_fee`, so that the fee subtracted from an output accounts for the
+    /// change output.
+    fn apply_change(&mut self) -> BuilderResult<()> {
+        let script_pubkey = match self.change_script.take() {
+            Some(script_pubkey) => script_pubkey,
+            None => return Ok(()),
+        };
+        let fee_rate = self.fee_rate.ok_or(BuilderError::MissingFeeRate)?;
+        let inputs_pay_fee = self.subtract_fee_from.is_none();
+        if let ChangeDecision::Added { value, .. } =
+            self.plan_change(fee_rate, &script_pubkey, inputs_pay_fee)?
+        {
+            self.vout.push(TxOut::new(value, script_pubkey));
+        }
+        Ok(())
+    }
+
+    /// Reduce the value of the output marked by `subtract_fee_from_output` by the fee.
+    fn apply_fee(&mut self) -> BuilderResult<()> {
+        let index = match self.subtract_fee_from {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        if index >= self.vout.len() {
+            return Err(BuilderError::NoSuchOutput(index));
+        }
+
+        let fee = self.estimated_fee()?;
+        let output = &mut self.vout[index];
+        if output.value < fee {
+            return Err(BuilderError::FeeExceedsOutput {
+                index,
+                value: output.value,
+                fee,
+            });
+        }
+        output.value -= fee;
+        if output.is_dust() {
+            return Err(BuilderError::DustOutput {
+                index,
+                value: output.value,
+            });
+        }
+        self.subtract_fee_from = None;
+        Ok(())
+    }
 }
 
 impl<T> TxBuilder for BitcoinTxBuilder<T>
@@ -112,15 +1128,26 @@ where
 {
     type Encoder = T;
     type Transaction = BitcoinTx;
+    type Error = BuilderError;
 
     fn new() -> Self {
         Self {
             version: 0,
             vin: vec![],
             vout: vec![],
+            extra_outputs: vec![],
             locktime: 0,
             witnesses: vec![],
             produce_witness: false,
+            prevouts: vec![],
+            fee_rate: None,
+            subtract_fee_from: None,
+            change_script: None,
+            max_fee_rate: DEFAULT_MAX_FEE_RATE,
+            max_absolute_fee: DEFAULT_MAX_ABSOLUTE_FEE,
+            allow_ignored_locktime: false,
+            sort_bip69: false,
+            preserve_input_order: false,
             encoder: PhantomData,
         }
     }
@@ -130,9 +1157,19 @@ where
             version: tx.version(),
             vin: tx.inputs().to_vec(),
             vout: tx.outputs().to_vec(),
+            extra_outputs: vec![],
             locktime: tx.locktime(),
             witnesses: tx.witnesses().to_vec(),
             produce_witness: tx.is_witness(),
+            prevouts: vec![],
+            fee_rate: None,
+            subtract_fee_from: None,
+            change_script: None,
+            max_fee_rate: DEFAULT_MAX_FEE_RATE,
+            max_absolute_fee: DEFAULT_MAX_ABSOLUTE_FEE,
+            allow_ignored_locktime: true,
+            sort_bip69: false,
+            preserve_input_order: false,
             encoder: PhantomData,
         }
     }
@@ -142,9 +1179,19 @@ where
             version: tx.version(),
             vin: tx.inputs().to_vec(),
             vout: tx.outputs().to_vec(),
+            extra_outputs: vec![],
             locktime: tx.locktime(),
             witnesses: tx.witnesses().to_vec(),
             produce_witness: tx.is_witness(),
+            prevouts: vec![],
+            fee_rate: None,
+            subtract_fee_from: None,
+            change_script: None,
+            max_fee_rate: DEFAULT_MAX_FEE_RATE,
+            max_absolute_fee: DEFAULT_MAX_ABSOLUTE_FEE,
+            allow_ignored_locktime: true,
+            sort_bip69: false,
+            preserve_input_order: false,
             encoder: PhantomData,
         }
     }
@@ -196,6 +1243,13 @@ where
     ) -> Self {
         let index = std::cmp::min(index, self.vout.len());
         self.vout.insert(index, output);
+        for extra in self
+            .extra_outputs
+            .iter_mut()
+            .filter(|extra| **extra >= index)
+        {
+            *extra += 1;
+        }
         self
     }
 
@@ -212,7 +1266,12 @@ where
         self
     }
 
-    fn build(self) -> Result<Self::Transaction, <Self::Transaction as Transaction>::TxError> {
+    fn build(mut self) -> BuilderResult<Self::Transaction> {
+        self.check_extra_outputs()?;
+        self.check_locktime()?;
+        self.apply_change()?;
+        self.apply_fee()?;
+        self.apply_ordering()?;
         if self.produce_witness || !self.witnesses.is_empty() {
             Ok(<WitnessTx as WitnessTransaction>::new(
                 self.version,
@@ -227,3 +1286,816 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        nets::BitcoinMainnet,
+        test_utils::{test_address, TestWallet},
+    };
+    use coins_core::{nets::Network, ser::ByteFormat};
+
+    #[test]
+    fn it_subtracts_the_fee_from_an_output() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(0, 60_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(1, 40_000), 0xffff_fffd)
+            .pay(70_000, &address)
+            .pay(30_000, &address)
+            .fee_rate(2);
+
+        // 40 overhead + 2 * 272 inputs + 2 * 124 outputs + 2 marker = 834 WU = 209 vbytes
+        assert_eq!(builder.estimated_weight().unwrap(), 834);
+        let fee = builder.estimated_fee().unwrap();
+        assert_eq!(fee, 418);
+
+        let tx = builder.subtract_fee_from_output(0).build().unwrap();
+        assert!(tx.is_witness());
+        assert_eq!(tx.outputs()[0].value, 70_000 - fee);
+        assert_eq!(tx.outputs()[1].value, 30_000);
+
+        let total_out: u64 = tx.outputs().iter().map(|o| o.value).sum();
+        assert_eq!(100_000 - total_out, fee);
+
+        // the estimate assumes 72-byte signatures, so it never undershoots the signed weight
+        let mut tx = tx.into_witness();
+        wallet.sign(&mut tx, &[wallet.utxo(0, 60_000), wallet.utxo(1, 40_000)]);
+        assert!(tx.is_fully_signed(&[
+            TxOut::new(60_000, wallet.script_pubkey()),
+            TxOut::new(40_000, wallet.script_pubkey()),
+        ]));
+        assert!(tx.weight() <= 834);
+        assert_eq!(tx.vsize(), 208);
+    }
+
+    #[test]
+    fn it_errors_if_the_fee_output_is_too_small() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .spend_utxo(&wallet.utxo(0, 100_000), 0xffff_fffd)
+            .pay(500, &address)
+            .fee_rate(2)
+            .subtract_fee_from_output(0);
+
+        match builder.clone().fee_rate(5).build() {
+            Err(BuilderError::FeeExceedsOutput { index: 0, .. }) => {}
+            _ => panic!("expected fee exceeds output error"),
+        }
+        match builder.build() {
+            Err(BuilderError::DustOutput { index: 0, .. }) => {}
+            _ => panic!("expected dust output error"),
+        }
+    }
+
+    #[test]
+    fn it_requires_prevouts_and_fee_rates_to_estimate_fees() {
+        let wallet = TestWallet::new();
+        let builder = BitcoinMainnet::tx_builder().spend(BitcoinOutpoint::default(), 0);
+        match builder.estimated_weight() {
+            Err(BuilderError::MissingPrevout(_)) => {}
+            _ => panic!("expected missing prevout error"),
+        }
+
+        let builder = BitcoinMainnet::tx_builder().spend_utxo(&wallet.utxo(0, 100_000), 0);
+        match builder.estimated_fee() {
+            Err(BuilderError::MissingFeeRate) => {}
+            _ => panic!("expected missing fee rate error"),
+        }
+    }
+
+    #[test]
+    fn it_consolidates_utxos() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let utxos = (0..50).map(|i| wallet.utxo(i, 10_000)).collect::<Vec<_>>();
+
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .consolidate(&utxos, &address, 5, 100)
+            .unwrap();
+        let weight = builder.estimated_weight().unwrap();
+        let fee = builder.estimated_fee().unwrap();
+        assert!(weight < MAX_STANDARD_TX_WEIGHT);
+
+        let tx = builder.build().unwrap();
+        assert_eq!(tx.inputs().len(), 50);
+        assert_eq!(tx.outputs().len(), 1);
+        assert_eq!(tx.outputs()[0].value, 500_000 - fee);
+
+        let builder = BitcoinMainnet::tx_builder()
+            .consolidate(&utxos, &address, 5, 10)
+            .unwrap();
+        assert_eq!(builder.vin.len(), 10);
+
+        // existing inputs count toward the output, less existing outputs, and aren't re-added
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(100, 25_000), 0xffff_fffd)
+            .spend_utxo(&utxos[0], 0xffff_fffd)
+            .pay(15_000, &address)
+            .consolidate(&utxos[..3], &address, 5, 100)
+            .unwrap();
+        assert_eq!(builder.vin.len(), 4);
+        let fee = builder.estimated_fee().unwrap();
+        let tx = builder.build().unwrap();
+        assert_eq!(tx.outputs()[1].value, 25_000 + 30_000 - 15_000 - fee);
+
+        // leading UTXOs that are already inputs don't use up `max_inputs`
+        let builder = BitcoinMainnet::tx_builder()
+            .spend_utxo(&utxos[0], 0xffff_fffd)
+            .spend_utxo(&utxos[1], 0xffff_fffd)
+            .consolidate(&utxos, &address, 5, 3)
+            .unwrap();
+        let spent = builder
+            .vin
+            .iter()
+            .map(|input| input.outpoint.idx)
+            .collect::<Vec<_>>();
+        assert_eq!(spent, vec![0, 1, 2, 3, 4]);
+
+        match BitcoinMainnet::tx_builder()
+            .pay(50_000, &address)
+            .consolidate(&utxos[..3], &address, 5, 100)
+        {
+            Err(BuilderError::InsufficientFunds {
+                available: 30_000,
+                required: 50_000,
+            }) => {}
+            e => panic!("expected insufficient funds error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_stops_consolidating_at_the_weight_limit() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let utxos = (0..2000)
+            .map(|i| wallet.utxo(i, 10_000))
+            .collect::<Vec<_>>();
+
+        let builder = BitcoinMainnet::tx_builder()
+            .consolidate(&utxos, &address, 1, 2000)
+            .unwrap();
+        let weight = builder.estimated_weight().unwrap();
+        assert!(builder.vin.len() < 2000);
+        assert!(weight <= MAX_STANDARD_TX_WEIGHT);
+        assert!(weight + 272 > MAX_STANDARD_TX_WEIGHT);
+    }
+
+    #[test]
+    fn it_funds_targets_skipping_uneconomic_utxos() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        // At 20 sat/vbyte, a wpkh input costs 68 * 20 = 1360 sats
+        let utxos = [
+            wallet.utxo(0, 1_000),
+            wallet.utxo(1, 30_000),
+            wallet.utxo(2, 1_360),
+            wallet.utxo(3, 30_000),
+            wallet.utxo(4, 30_000),
+        ];
+
+        let builder = BitcoinMainnet::tx_builder()
+            .pay(50_000, &address)
+            .fund(&utxos, 50_000, 20)
+            .unwrap();
+        let spent = builder
+            .vin
+            .iter()
+            .map(|input| input.outpoint.idx)
+            .collect::<Vec<_>>();
+        assert_eq!(spent, vec![1, 3]);
+        assert!(60_000 >= 50_000 + builder.estimated_fee().unwrap());
+
+        match BitcoinMainnet::tx_builder()
+            .pay(50_000, &address)
+            .fund(&utxos[..3], 50_000, 20)
+        {
+            Err(BuilderError::InsufficientFunds {
+                available: 30_000, ..
+            }) => {}
+            _ => panic!("expected insufficient funds error"),
+        }
+    }
+
+    #[test]
+    fn it_skips_utxos_with_negative_effective_value() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        // a wpkh input is 68 vbytes
+        let small = wallet.utxo(0, 2_000);
+        assert_eq!(small.effective_value(1), Some(1_932));
+        assert_eq!(small.effective_value(50), Some(-1_400));
+
+        let utxos = [small, wallet.utxo(1, 30_000)];
+        let builder = BitcoinMainnet::tx_builder()
+            .pay(10_000, &address)
+            .fund(&utxos, 10_000, 50)
+            .unwrap();
+        assert_eq!(builder.vin.len(), 1);
+        assert_eq!(builder.vin[0].outpoint.idx, 1);
+
+        let builder = BitcoinMainnet::tx_builder()
+            .pay(29_000, &address)
+            .fund(&utxos, 29_000, 1)
+            .unwrap();
+        assert_eq!(builder.vin.len(), 2);
+    }
+
+    #[test]
+    fn it_rejects_ignored_locktimes() {
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend(BitcoinOutpoint::new(Default::default(), 0), 0xffff_ffff)
+            .pay(10_000, &address)
+            .locktime(700_000);
+
+        match builder.clone().build() {
+            Err(BuilderError::LocktimeIgnored(700_000)) => {}
+            e => panic!("expected ignored locktime error, got {:?}", e),
+        }
+        match builder.clone().build_witness() {
+            Err(BuilderError::LocktimeIgnored(700_000)) => {}
+            e => panic!("expected ignored locktime error, got {:?}", e),
+        }
+        assert!(builder.clone().locktime(0).build().is_ok());
+        assert!(builder.clone().allow_ignored_locktime(true).build().is_ok());
+
+        let tx = builder
+            .spend(BitcoinOutpoint::new(Default::default(), 1), 0xffff_fffe)
+            .build()
+            .unwrap();
+        assert_eq!(tx.locktime(), 700_000);
+
+        // rebuilding an existing transaction keeps its locktime
+        let legacy = LegacyTx::new(
+            2,
+            vec![BitcoinTxIn::new(
+                BitcoinOutpoint::null(),
+                vec![],
+                0xffff_ffff,
+            )],
+            vec![TxOut::new(10_000, vec![0x51])],
+            700_000,
+        )
+        .unwrap();
+        assert!(BitcoinMainnet::builder_from_tx(legacy.into())
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn it_funds_extra_outputs() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let coordinator = TxOut::new(
+            25_000,
+            ScriptPubkey::deserialize_hex("16001479b000887626b294a914501a4cd226b58b235983")
+                .unwrap(),
+        );
+        let utxos = [
+            wallet.utxo(0, 30_000),
+            wallet.utxo(1, 30_000),
+            wallet.utxo(2, 30_000),
+        ];
+
+        let builder = BitcoinMainnet::tx_builder()
+            .pay(30_000, &address)
+            .with_extra_outputs(vec![coordinator.clone()]);
+        assert_eq!(builder.output_value(), 55_000);
+        let target = builder.output_value();
+        let builder = builder.fund(&utxos, target, 10).unwrap();
+        assert_eq!(builder.vin.len(), 2);
+        assert!(60_000 >= 55_000 + builder.estimated_fee().unwrap());
+
+        let tx = builder.build().unwrap();
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(tx.outputs()[1], coordinator);
+
+        let dust = TxOut::new(100, coordinator.script_pubkey.clone());
+        let builder = BitcoinMainnet::tx_builder()
+            .pay(30_000, &address)
+            .with_extra_outputs(vec![coordinator.clone(), dust]);
+        match builder.clone().fund(&utxos, 55_100, 10) {
+            Err(BuilderError::DustOutput {
+                index: 2,
+                value: 100,
+            }) => {}
+            e => panic!("expected dust output error, got {:?}", e),
+        }
+        // inserting an output before the extra outputs moves them
+        match builder
+            .spend_utxo(&utxos[0], 0xffff_fffd)
+            .insert_output(0, TxOut::new(1_000, coordinator.script_pubkey.clone()))
+            .build()
+        {
+            Err(BuilderError::DustOutput {
+                index: 3,
+                value: 100,
+            }) => {}
+            e => panic!("expected dust output error, got {:?}", e),
+        }
+
+        let bare_multisig = TxOut::new(
+            25_000,
+            ScriptPubkey::deserialize_hex(
+                "2551210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c51ae",
+            )
+            .unwrap(),
+        );
+        match BitcoinMainnet::tx_builder()
+            .with_extra_outputs(vec![bare_multisig])
+            .build()
+        {
+            Err(BuilderError::NonStandardOutput(0)) => {}
+            e => panic!("expected nonstandard output error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_guards_against_high_fees() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .spend_utxo(&wallet.utxo(0, 10_000_000), 0xffff_fffd)
+            .pay(10_000_000, &address)
+            .fee_rate(10_000)
+            .subtract_fee_from_output(0);
+
+        match builder.clone().build() {
+            Err(BuilderError::FeeTooHigh {
+                requested: 10_000,
+                limit: DEFAULT_MAX_FEE_RATE,
+            }) => {}
+            _ => panic!("expected fee too high error"),
+        }
+
+        // 438 WU = 110 vbytes, so the fee is 1.1M sats
+        let builder = builder.max_fee_rate(10_000);
+        match builder.estimated_fee() {
+            Err(BuilderError::FeeTooHigh {
+                requested: 1_100_000,
+                limit: DEFAULT_MAX_ABSOLUTE_FEE,
+            }) => {}
+            _ => panic!("expected fee too high error"),
+        }
+
+        let tx = builder.max_absolute_fee(2_000_000).build().unwrap();
+        assert_eq!(tx.outputs()[0].value, 10_000_000 - 1_100_000);
+    }
+
+    #[test]
+    fn it_funds_around_required_inputs() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let required = wallet.utxo(9, 20_000);
+        let utxos = [
+            wallet.utxo(0, 30_000),
+            required.clone(),
+            wallet.utxo(1, 30_000),
+            wallet.utxo(2, 30_000),
+        ];
+
+        let builder = BitcoinMainnet::tx_builder()
+            .pay(45_000, &address)
+            .require_input(&required)
+            .fund(&utxos, 45_000, 10)
+            .unwrap();
+        let spent = builder
+            .vin
+            .iter()
+            .map(|input| input.outpoint.idx)
+            .collect::<Vec<_>>();
+        assert_eq!(spent, vec![9, 0]);
+        assert!(50_000 >= 45_000 + builder.estimated_fee().unwrap());
+    }
+
+    #[test]
+    fn it_previews_change() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(0, 60_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(1, 40_000), 0xffff_fffd)
+            .pay(70_000, &address);
+
+        let change = builder.preview_change(3).unwrap();
+        let builder = builder.pay(change, &address).fee_rate(3);
+        let fee = builder.estimated_fee().unwrap();
+
+        let tx = builder.build().unwrap();
+        assert_eq!(tx.outputs()[1].value, change);
+        assert_eq!(100_000 - 70_000 - change, fee);
+
+        match BitcoinMainnet::tx_builder()
+            .spend_utxo(&wallet.utxo(0, 60_000), 0xffff_fffd)
+            .pay(60_000, &address)
+            .preview_change(3)
+        {
+            Err(BuilderError::InsufficientFunds {
+                available: 60_000, ..
+            }) => {}
+            _ => panic!("expected insufficient funds error"),
+        }
+    }
+
+    #[test]
+    fn it_estimates_vsize_from_kinds() {
+        // A signed 2-input, 2-output P2WPKH transaction
+        let tx = BitcoinTx::deserialize_hex("0200000000010201010101010101010101010101010101010101010101010101010101010101010000000000fdffffff02020202020202020202020202020202020202020202020202020202020202020100000000fdffffff0250c3000000000000160014fc7250a211deddc70ee5a2738de5f07817351cef409c000000000000160014fc7250a211deddc70ee5a2738de5f07817351cef024730440220410f6dc7be4b963207a4dff57b2fbb595e10524f4e5b215beda516a9122219e702205d4bd81fd5642061af79164407f9399d790acda5721a496d51db11e8dbcb0a6e0121034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa02483045022100da44bb68f662675c7d8c5586037312c97f52aa148e1b942d535588cac20c10ab02207050e9d3c54a856c77afbc72d447661c611b7c741dd4a3409448af7b0aeb14810121034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa00000000").unwrap();
+        let actual = tx.vsize();
+        assert_eq!(actual, 209);
+
+        let estimate = estimate_tx_vsize(
+            &[InputKind::Wpkh, InputKind::Wpkh],
+            &[AddressKind::Wpkh, AddressKind::Wpkh],
+        );
+        assert!(estimate >= actual && estimate - actual <= 1);
+
+        // A legacy input adds an empty witness, and the marker only counts once
+        assert_eq!(
+            estimate_tx_vsize(&[InputKind::Pkh], &[AddressKind::Pkh]),
+            (4 * 10 + 592 + 136) / 4
+        );
+        assert_eq!(
+            estimate_tx_vsize(&[InputKind::Pkh, InputKind::ShWpkh], &[AddressKind::ShWpkh]),
+            (4 * 10 + 2 + 1 + 592 + 364 + 128usize).div_ceil(4)
+        );
+    }
+
+    #[test]
+    fn it_reports_dry_runs() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let utxos = [wallet.utxo(0, 60_000), wallet.utxo(1, 40_000)];
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&utxos[0], 0xffff_fffd)
+            .spend_utxo(&utxos[1], 0xffff_fffd)
+            .pay(70_000, &address);
+
+        let report = builder.dry_run(3).unwrap();
+        assert_eq!(
+            report.inputs,
+            vec![
+                (utxos[0].outpoint, SelectionReason::Required),
+                (utxos[1].outpoint, SelectionReason::Required)
+            ]
+        );
+        assert_eq!(report.input_value, 100_000);
+        assert_eq!(report.output_value, 70_000);
+        let change = builder.preview_change(3).unwrap();
+        assert_eq!(
+            report.change,
+            ChangeDecision::Added {
+                index: 1,
+                value: change
+            }
+        );
+        assert_eq!(report.fee, 30_000 - change);
+        assert_eq!(report.fee, 3 * report.vsize as u64);
+
+        // Paying all but 100 sats of the change leaves dust
+        let fee = report.fee;
+        let builder = builder.pay(change - 100, &address);
+        let report = builder.dry_run(3).unwrap();
+        assert_eq!(report.change, ChangeDecision::DroppedAsDust { value: 100 });
+        assert_eq!(report.fee, fee + 100);
+
+        match builder.pay(100, &address).dry_run(3) {
+            Err(BuilderError::InsufficientFunds { .. }) => {}
+            _ => panic!("expected insufficient funds error"),
+        }
+    }
+
+    #[test]
+    fn it_reports_why_utxos_are_spent() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let required = wallet.utxo(0, 20_000);
+        let utxos = [wallet.utxo(1, 10_000), wallet.utxo(2, 50_000)];
+        let selection = LargestFirst::default().select(&utxos, 40_000, 3).unwrap();
+        let funding = [wallet.utxo(3, 30_000), wallet.utxo(4, 40_000)];
+
+        let report = BitcoinMainnet::tx_builder()
+            .version(2)
+            .require_input(&required)
+            .spend_selection(&selection)
+            .pay(90_000, &address)
+            .fund(&funding, 90_000, 3)
+            .unwrap()
+            .dry_run(3)
+            .unwrap();
+        // a P2WPKH input costs 68 vbytes
+        assert_eq!(
+            report.inputs,
+            vec![
+                (required.outpoint, SelectionReason::Required),
+                (utxos[1].outpoint, SelectionReason::Selected),
+                (
+                    funding[0].outpoint,
+                    SelectionReason::Funded {
+                        effective_value: 30_000 - 3 * 68
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_dry_runs_with_the_change_address() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        // a P2PKH change output is larger, and has a higher dust threshold, than P2WPKH
+        let change_address =
+            BitcoinMainnet::string_to_address("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(0, 60_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(1, 40_000), 0xffff_fffd)
+            .fee_rate(3)
+            .change_address(&change_address);
+        let fee =
+            |tx: &BitcoinTx| 100_000 - tx.outputs().iter().map(|output| output.value).sum::<u64>();
+
+        let payment = builder.clone().pay(70_000, &address);
+        let report = payment.dry_run(3).unwrap();
+        let tx = payment.clone().build().unwrap();
+        let change = payment.preview_change(3).unwrap();
+        assert_eq!(
+            report.change,
+            ChangeDecision::Added {
+                index: 1,
+                value: change
+            }
+        );
+        assert_eq!(tx.outputs()[1].value, change);
+        assert_eq!(report.fee, fee(&tx));
+
+        // 400 sats of change would be dust with P2PKH, but not with P2WPKH
+        let payment = builder.pay(70_000, &address).pay(change - 400, &address);
+        let report = payment.dry_run(3).unwrap();
+        let tx = payment.clone().build().unwrap();
+        assert!(matches!(
+            report.change,
+            ChangeDecision::DroppedAsDust { .. }
+        ));
+        assert_eq!(payment.preview_change(3).unwrap(), 0);
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(report.fee, fee(&tx));
+    }
+
+    #[test]
+    fn it_wraps_transaction_errors() {
+        let wallet = TestWallet::new();
+        let builder = BitcoinMainnet::tx_builder().spend_utxo(&wallet.utxo(0, 10_000), 0xffff_fffd);
+        match builder.clone().build() {
+            Err(BuilderError::TxError(TxError::EmptyVout)) => {}
+            e => panic!("expected empty vout error, got {:?}", e),
+        }
+        match builder.build_legacy() {
+            Err(BuilderError::TxError(TxError::EmptyVout)) => {}
+            e => panic!("expected empty vout error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_orders_inputs() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(2, 30_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(0, 10_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(1, 20_000), 0xffff_fffd)
+            .pay(50_000, &address)
+            .pay(5_000, &address);
+        let indices = |tx: &BitcoinTx| -> Vec<u32> {
+            tx.inputs().iter().map(|input| input.outpoint.idx).collect()
+        };
+
+        let tx = builder.clone().preserve_input_order(true).build().unwrap();
+        assert_eq!(indices(&tx), vec![2, 0, 1]);
+        assert_eq!(tx.outputs()[0].value, 50_000);
+
+        let tx = builder.clone().sort_bip69(true).build().unwrap();
+        assert_eq!(indices(&tx), vec![0, 1, 2]);
+        assert_eq!(tx.outputs()[0].value, 5_000);
+
+        match builder.sort_bip69(true).preserve_input_order(true).build() {
+            Err(BuilderError::ConflictingInputOrder) => {}
+            e => panic!("expected conflicting input order error, got {:?}", e),
+        }
+
+        // selectors keep the caller's order, rather than ordering by value or by BIP69
+        let utxos = [
+            wallet.utxo(2, 30_000),
+            wallet.utxo(0, 10_000),
+            wallet.utxo(1, 50_000),
+        ];
+        let selectors: [&dyn CoinSelector; 2] =
+            [&LargestFirst::default(), &BranchAndBound::default()];
+        for selector in selectors.iter() {
+            let selection = selector.select(&utxos, 60_000, 1).unwrap();
+            let tx = BitcoinMainnet::tx_builder()
+                .version(2)
+                .spend_selection(&selection)
+                .pay(60_000, &address)
+                .preserve_input_order(true)
+                .build()
+                .unwrap();
+            assert_eq!(indices(&tx), vec![2, 1]);
+        }
+    }
+
+    #[test]
+    fn it_orders_inputs_with_partial_witnesses() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let witness: Witness = vec![vec![0xaa].into()];
+        let tx = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(2, 30_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(0, 10_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(1, 20_000), 0xffff_fffd)
+            .pay(50_000, &address)
+            .extend_witnesses(vec![witness.clone()])
+            .sort_bip69(true)
+            .build()
+            .unwrap();
+        let tx = match tx {
+            BitcoinTx::Witness(tx) => tx,
+            _ => panic!("expected a witness tx"),
+        };
+        let indices: Vec<u32> = tx.inputs().iter().map(|input| input.outpoint.idx).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        // the witness supplied for the first input (output 2) moves with it
+        assert_eq!(
+            tx.witnesses(),
+            &[Witness::default(), Witness::default(), witness][..]
+        );
+    }
+
+    #[test]
+    fn it_selects_largest_utxos_first() {
+        let wallet = TestWallet::new();
+        let utxos = [
+            wallet.utxo(0, 10_000),
+            wallet.utxo(1, 50_000),
+            wallet.utxo(2, 30_000),
+        ];
+        let selector = LargestFirst::default();
+        assert_eq!(selector.dust_threshold, 294);
+
+        // each input costs 68 vbytes, and the change output 31
+        let selection = selector.select(&utxos, 60_000, 1).unwrap();
+        assert_eq!(selection.utxos, vec![utxos[1].clone(), utxos[2].clone()]);
+        assert_eq!(selection.value, 80_000);
+        assert_eq!(selection.fee, 2 * 68 + 31);
+        assert_eq!(selection.change, 80_000 - 60_000 - 2 * 68 - 31);
+
+        // an excess of 164 sats would leave dust change, so it is dropped to the fee
+        let selection = selector.select(&utxos, 79_700, 1).unwrap();
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 300);
+
+        match selector.select(&utxos, 100_000, 1) {
+            Err(BuilderError::InsufficientFunds {
+                available: 90_000,
+                required: 100_204,
+            }) => {}
+            e => panic!("expected insufficient funds error, got {:?}", e),
+        }
+
+        let address = test_address();
+        let selection = selector.select(&utxos, 60_000, 1).unwrap();
+        let builder = BitcoinMainnet::tx_builder()
+            .spend_selection(&selection)
+            .pay(60_000, &address);
+        assert_eq!(builder.input_value().unwrap(), selection.value);
+    }
+
+    #[test]
+    fn it_rejects_unreachable_targets() {
+        let wallet = TestWallet::new();
+        let utxos = [wallet.utxo(0, 10_000), wallet.utxo(1, 50_000)];
+        let selectors: [&dyn CoinSelector; 2] =
+            [&LargestFirst::default(), &BranchAndBound::default()];
+        for selector in selectors.iter() {
+            for (target, fee_rate) in [(u64::MAX, 1), (u64::MAX - 100, 1), (1_000, u64::MAX)] {
+                match selector.select(&utxos, target, fee_rate) {
+                    Err(BuilderError::InsufficientFunds { .. }) => {}
+                    e => panic!("expected insufficient funds error, got {:?}", e),
+                }
+            }
+        }
+
+        match BitcoinMainnet::tx_builder().fund(&utxos, u64::MAX, 1) {
+            Err(BuilderError::InsufficientFunds {
+                available: 60_000,
+                required: u64::MAX,
+            }) => {}
+            e => panic!("expected insufficient funds error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_selects_changeless_utxos_by_branch_and_bound() {
+        let wallet = TestWallet::new();
+        let utxos = [
+            wallet.utxo(0, 10_000),
+            wallet.utxo(1, 50_000),
+            wallet.utxo(2, 30_000),
+            wallet.utxo(3, 20_000),
+            // costs more to spend than it is worth
+            wallet.utxo(4, 60),
+        ];
+        let selector = BranchAndBound::default();
+
+        // the effective values of the 30,000 and 10,000 sat UTXOs exactly match the target
+        let selection = selector.select(&utxos, 39_864, 1).unwrap();
+        assert_eq!(selection.utxos, vec![utxos[0].clone(), utxos[2].clone()]);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 2 * 68);
+
+        // excess below the change output fee plus the dust threshold is dropped
+        let selection = selector.select(&utxos, 39_600, 1).unwrap();
+        assert_eq!(selection.utxos.len(), 2);
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 2 * 68 + 264);
+
+        // no changeless selection exists, so it falls back to largest first
+        let selection = selector.select(&utxos, 45_000, 1).unwrap();
+        assert_eq!(selection.utxos, vec![utxos[1].clone()]);
+        assert_eq!(selection.change, 50_000 - 45_000 - 68 - 31);
+        assert_eq!(
+            selection,
+            LargestFirst::default().select(&utxos, 45_000, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_adds_change_outputs() {
+        let wallet = TestWallet::new();
+        let address = test_address();
+        let change_address =
+            BitcoinMainnet::string_to_address("bc1q0xcqpzrky6eff2g52qdye53xkk9jxkvrh6yhyw")
+                .unwrap();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend_utxo(&wallet.utxo(0, 60_000), 0xffff_fffd)
+            .spend_utxo(&wallet.utxo(1, 40_000), 0xffff_fffd)
+            .fee_rate(2)
+            .change_address(&change_address);
+
+        // 2 inputs and 2 outputs are 209 vbytes, so the fee is 418
+        let tx = builder.clone().pay(70_000, &address).build().unwrap();
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(tx.outputs()[1].value, 100_000 - 70_000 - 418);
+        assert_eq!(
+            tx.outputs()[1].script_pubkey,
+            BitcoinMainnet::decode_address(&change_address)
+        );
+
+        // the fee is subtracted from the payment, so the change is the full excess
+        let tx = builder
+            .clone()
+            .pay(70_000, &address)
+            .subtract_fee_from_output(0)
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs()[0].value, 70_000 - 418);
+        assert_eq!(tx.outputs()[1].value, 30_000);
+
+        // 282 sats of change would be dust, so it is left to the fee
+        let tx = builder.clone().pay(99_300, &address).build().unwrap();
+        assert_eq!(tx.outputs().len(), 1);
+        assert_eq!(tx.outputs()[0].value, 99_300);
+
+        // without change, the tx is 178 vbytes, so the fee is 356
+        match builder.clone().pay(99_700, &address).build() {
+            Err(BuilderError::InsufficientFunds {
+                available: 100_000,
+                required: 100_056,
+            }) => {}
+            e => panic!("expected insufficient funds error, got {:?}", e),
+        }
+
+        match BitcoinMainnet::tx_builder()
+            .spend_utxo(&wallet.utxo(0, 60_000), 0xffff_fffd)
+            .pay(50_000, &address)
+            .change_address(&change_address)
+            .build()
+        {
+            Err(BuilderError::MissingFeeRate) => {}
+            e => panic!("expected missing fee rate error, got {:?}", e),
+        }
+    }
+}
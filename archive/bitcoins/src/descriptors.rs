@@ -0,0 +1,781 @@
+//! A compact parser for multisig output descriptors.
+//!
+//! This supports the `multi` and `sortedmulti` descriptor functions, optionally wrapped in `sh`,
+//! `wsh`, or `sh(wsh(...))`. Keys are extended public keys followed by a non-hardened derivation
+//! path, optionally terminated by a `/*` wildcard. Keys may be prefixed with their origin, e.g.
+//! `[d34db33f/84'/0'/0']xpub.../0/*`. A trailing `#checksum` is verified if present.
+//!
+//! For single-key wallets, `derive_script_range` derives a batch of script pubkeys from an
+//! account xpub. `ElectrumKey` parses the bare pubkeys and xpubs exported by Electrum.
+//!
+//! ```
+//! use bitcoins::{descriptors::MultisigDescriptor, enc::MainnetEncoder};
+//! use coins_bip32::enc::MainnetEncoder as XKeyMainnetEncoder;
+//! use coins_core::enc::AddressEncoder;
+//!
+//! let descriptor = MultisigDescriptor::parse::<XKeyMainnetEncoder>(
+//!     "wsh(sortedmulti(2,\
+//!     xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/0/*,\
+//!     xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/0/*))"
+//! ).unwrap();
+//!
+//! let spk = descriptor.script_pubkey(0).unwrap();
+//! let address = MainnetEncoder::encode_address(&spk).unwrap();
+//! assert_eq!(
+//!     address.as_string(),
+//!     "bc1qpsnmhwhv2cvnyp25efl8xvpyyenc8qr7zt0fj47dklj0txpc4e0q7vlzgy"
+//! );
+//! ```
+
+use coins_bip32::{
+    enc::XKeyEncoder,
+    k256::ecdsa::VerifyingKey,
+    path::{DerivationPath, KeyDerivation},
+    primitives::KeyFingerprint,
+    xkeys::Parent,
+    xkeys::XPub,
+    Bip32Error, BIP32_HARDEN,
+};
+use coins_core::hashes::{Digest, Hash160};
+use thiserror::Error;
+
+use crate::{
+    enc::encoder::{Address, BitcoinEncoderMarker},
+    types::{
+        script::{Script, ScriptPubkey},
+        taproot::taproot_output_key,
+    },
+};
+
+/// Characters permitted in a descriptor, in the order used by the checksum algorithm.
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+/// Characters used to encode the descriptor checksum.
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Errors encountered while parsing or deriving descriptors.
+#[derive(Debug, Error)]
+pub enum DescriptorError {
+    /// The descriptor string could not be parsed.
+    #[error("Malformatted descriptor: {0}")]
+    Malformatted(String),
+
+    /// The descriptor checksum did not match its contents.
+    #[error("Bad descriptor checksum. Got {got}. Expected {expected}")]
+    BadChecksum {
+        /// The checksum in the descriptor string
+        got: String,
+        /// The checksum we computed
+        expected: String,
+    },
+
+    /// The multisig threshold was 0, or greater than the number of keys.
+    #[error("Invalid threshold {m} of {n} keys")]
+    InvalidThreshold {
+        /// The threshold
+        m: usize,
+        /// The number of keys
+        n: usize,
+    },
+
+    /// Descriptor keys do not contain private data, so hardened derivation is impossible.
+    #[error("Hardened derivation is not supported for descriptor xpubs: {0}")]
+    HardenedDerivation(String),
+
+    /// Bubbled up from bip32.
+    #[error(transparent)]
+    Bip32Error(#[from] Bip32Error),
+}
+
+/// Type alias for result with DescriptorError
+pub type DescriptorResult<T> = Result<T, DescriptorError>;
+
+/// Compute the 8-character checksum of a descriptor string. Returns `None` if the descriptor
+/// contains characters outside the descriptor character set.
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    fn polymod(c: u64, val: u64) -> u64 {
+        let c0 = c >> 35;
+        let mut c = ((c & 0x7_ffff_ffff) << 5) ^ val;
+        if c0 & 1 != 0 {
+            c ^= 0xf5_dee5_1989;
+        }
+        if c0 & 2 != 0 {
+            c ^= 0xa9_fdca_3312;
+        }
+        if c0 & 4 != 0 {
+            c ^= 0x1b_ab10_e32d;
+        }
+        if c0 & 8 != 0 {
+            c ^= 0x37_06b1_677a;
+        }
+        if c0 & 16 != 0 {
+            c ^= 0x64_4d62_6ffd;
+        }
+        c
+    }
+
+    let mut c = 1u64;
+    let mut cls = 0u64;
+    let mut cls_count = 0;
+    for ch in descriptor.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = polymod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        cls_count += 1;
+        if cls_count == 3 {
+            c = polymod(c, cls);
+            cls = 0;
+            cls_count = 0;
+        }
+    }
+    if cls_count > 0 {
+        c = polymod(c, cls);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+/// An extended public key in a descriptor, with its (unhardened) derivation path and an optional
+/// trailing wildcard. E.g. `xpub.../0/*`. The key may be prefixed with its origin, which is the
+/// root key fingerprint and the (possibly hardened) path from the root to the xpub. E.g.
+/// `[d34db33f/84'/0'/0']xpub.../0/*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DescriptorKey {
+    /// The origin of the xpub, if specified
+    pub origin: Option<KeyDerivation>,
+    /// The extended public key
+    pub xpub: XPub,
+    /// The derivation path from the xpub, excluding the wildcard
+    pub path: DerivationPath,
+    /// True if the key ends in `/*`
+    pub wildcard: bool,
+}
+
+impl DescriptorKey {
+    /// Parse a descriptor key, using the specified encoder to deserialize the xpub.
+    pub fn parse<E: XKeyEncoder>(s: &str) -> DescriptorResult<Self> {
+        let malformatted = || DescriptorError::Malformatted(s.to_owned());
+
+        let (origin, key) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let end = rest.find(']').ok_or_else(malformatted)?;
+                (
+                    Some(parse_origin(&rest[..end]).ok_or_else(malformatted)?),
+                    &rest[end + 1..],
+                )
+            }
+            None => (None, s),
+        };
+
+        let mut segments = key.split('/');
+        let xpub = E::xpub_from_base58(segments.next().unwrap_or_default())?;
+
+        let mut indices = vec![];
+        let mut wildcard = false;
+        for segment in segments {
+            if wildcard {
+                return Err(DescriptorError::Malformatted(s.to_owned()));
+            }
+            match segment {
+                "*" => wildcard = true,
+                "*'" | "*h" => return Err(DescriptorError::HardenedDerivation(s.to_owned())),
+                _ if segment.ends_with('\'') || segment.ends_with('h') => {
+                    return Err(DescriptorError::HardenedDerivation(s.to_owned()))
+                }
+                _ => {
+                    let index = segment
+                        .parse::<u32>()
+                        .map_err(|_| DescriptorError::Malformatted(s.to_owned()))?;
+                    if index >= BIP32_HARDEN {
+                        return Err(DescriptorError::HardenedDerivation(s.to_owned()));
+                    }
+                    indices.push(index);
+                }
+            }
+        }
+
+        Ok(Self {
+            origin,
+            xpub,
+            path: indices.into(),
+            wildcard,
+        })
+    }
+
+    /// Derive the key at `index`. Keys without a wildcard ignore the index.
+    pub fn derive(&self, index: u32) -> DescriptorResult<XPub> {
+        let path = if self.wildcard {
+            self.path.extended(index)
+        } else {
+            self.path.clone()
+        };
+        Ok(self.xpub.derive_path(&path)?)
+    }
+}
+
+/// Parse a key origin of the form `fingerprint/path`, where the fingerprint is 8 hex characters.
+fn parse_origin(origin: &str) -> Option<KeyDerivation> {
+    let (fingerprint, path) = match origin.find('/') {
+        Some(pos) => (&origin[..pos], &origin[pos + 1..]),
+        None => (origin, ""),
+    };
+    if fingerprint.len() != 8 {
+        return None;
+    }
+    let mut root = [0u8; 4];
+    hex::decode_to_slice(fingerprint, &mut root).ok()?;
+    let path = if path.is_empty() {
+        DerivationPath::default()
+    } else {
+        path.parse().ok()?
+    };
+    Some(KeyDerivation {
+        root: KeyFingerprint(root),
+        path,
+    })
+}
+
+/// The script wrapper applied to a multisig script.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MultisigWrapper {
+    /// A bare multisig output. `multi(...)`
+    Bare,
+    /// Pay to Scripthash. `sh(multi(...))`
+    Sh,
+    /// Pay to Witness Scripthash. `wsh(multi(...))`
+    Wsh,
+    /// Pay to Witness Scripthash nested in Pay to Scripthash. `sh(wsh(multi(...)))`
+    ShWsh,
+}
+
+/// A parsed `multi` or `sortedmulti` descriptor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultisigDescriptor {
+    /// The script wrapper
+    pub wrapper: MultisigWrapper,
+    /// The number of signatures required
+    pub threshold: usize,
+    /// The keys, in descriptor order
+    pub keys: Vec<DescriptorKey>,
+    /// True for `sortedmulti`. The derived pubkeys are sorted according to BIP67.
+    pub sorted: bool,
+}
+
+impl MultisigDescriptor {
+    /// Parse a descriptor, using the specified encoder to deserialize xpubs.
+    pub fn parse<E: XKeyEncoder>(s: &str) -> DescriptorResult<Self> {
+        let body = match s.rfind('#') {
+            Some(pos) => {
+                let (body, checksum) = (&s[..pos], &s[pos + 1..]);
+                let expected = descriptor_checksum(body)
+                    .ok_or_else(|| DescriptorError::Malformatted(s.to_owned()))?;
+                if checksum != expected {
+                    return Err(DescriptorError::BadChecksum {
+                        got: checksum.to_owned(),
+                        expected,
+                    });
+                }
+                body
+            }
+            None => s,
+        };
+
+        let malformatted = || DescriptorError::Malformatted(s.to_owned());
+
+        let (wrapper, inner) = if let Some(inner) = unwrap_function(body, "sh") {
+            match unwrap_function(inner, "wsh") {
+                Some(inner) => (MultisigWrapper::ShWsh, inner),
+                None => (MultisigWrapper::Sh, inner),
+            }
+        } else if let Some(inner) = unwrap_function(body, "wsh") {
+            (MultisigWrapper::Wsh, inner)
+        } else {
+            (MultisigWrapper::Bare, body)
+        };
+
+        let (sorted, args) = if let Some(args) = unwrap_function(inner, "sortedmulti") {
+            (true, args)
+        } else if let Some(args) = unwrap_function(inner, "multi") {
+            (false, args)
+        } else {
+            return Err(malformatted());
+        };
+
+        let mut args = args.split(',');
+        let threshold = args
+            .next()
+            .and_then(|m| m.parse::<usize>().ok())
+            .ok_or_else(malformatted)?;
+        let keys = args
+            .map(DescriptorKey::parse::<E>)
+            .collect::<DescriptorResult<Vec<_>>>()?;
+
+        if threshold == 0 || threshold > keys.len() || keys.len() > 16 {
+            return Err(DescriptorError::InvalidThreshold {
+                m: threshold,
+                n: keys.len(),
+            });
+        }
+
+        Ok(Self {
+            wrapper,
+            threshold,
+            keys,
+            sorted,
+        })
+    }
+
+    /// Derive the compressed pubkeys at `index`, in script order.
+    pub fn derive_pubkeys(&self, index: u32) -> DescriptorResult<Vec<[u8; 33]>> {
+        let mut pubkeys = self
+            .keys
+            .iter()
+            .map(|k| k.derive(index).map(|xpub| xpub.to_sec1_bytes()))
+            .collect::<DescriptorResult<Vec<_>>>()?;
+        if self.sorted {
+            pubkeys.sort_unstable();
+        }
+        Ok(pubkeys)
+    }
+
+    /// Derive the multisig script at `index`. This is the redeem script for `sh`, and the
+    /// witness script for `wsh` and `sh(wsh)`.
+    pub fn multisig_script(&self, index: u32) -> DescriptorResult<Script> {
+        let pubkeys = self.derive_pubkeys(index)?;
+
+        let mut v: Vec<u8> = vec![0x50 + self.threshold as u8]; // OP_M
+        for pubkey in pubkeys.iter() {
+            v.push(0x21); // PUSH_33
+            v.extend(pubkey);
+        }
+        v.push(0x50 + pubkeys.len() as u8); // OP_N
+        v.push(0xae); // CHECKMULTISIG
+        Ok(v.into())
+    }
+
+    /// Derive the scriptPubkey at `index`.
+    pub fn script_pubkey(&self, index: u32) -> DescriptorResult<ScriptPubkey> {
+        let script = self.multisig_script(index)?;
+        Ok(match self.wrapper {
+            MultisigWrapper::Bare => (&script).into(),
+            MultisigWrapper::Sh => ScriptPubkey::p2sh(&script),
+            MultisigWrapper::Wsh => ScriptPubkey::p2wsh(&script),
+            MultisigWrapper::ShWsh => ScriptPubkey::p2sh(&(&ScriptPubkey::p2wsh(&script)).into()),
+        })
+    }
+}
+
+/// The kind of single-key script pubkey to derive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddressKind {
+    /// Legacy Pay to Pubkeyhash. `pkh(...)`
+    Pkh,
+    /// Legacy Pay to Pubkeyhash of the uncompressed pubkey
+    PkhUncompressed,
+    /// Pay to Witness Pubkeyhash nested in Pay to Scripthash. `sh(wpkh(...))`
+    ShWpkh,
+    /// Pay to Witness Pubkeyhash. `wpkh(...)`
+    Wpkh,
+    /// Pay to Taproot, with no script tree. `tr(...)`
+    Tr,
+}
+
+impl AddressKind {
+    /// All address kinds.
+    pub const ALL: [AddressKind; 5] = [
+        AddressKind::Pkh,
+        AddressKind::PkhUncompressed,
+        AddressKind::ShWpkh,
+        AddressKind::Wpkh,
+        AddressKind::Tr,
+    ];
+
+    /// Build the script pubkey of this kind for `key`.
+    pub fn script_pubkey<K>(&self, key: &K) -> ScriptPubkey
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        match self {
+            AddressKind::Pkh => ScriptPubkey::p2pkh(key),
+            AddressKind::PkhUncompressed => ScriptPubkey::p2pkh_uncompressed(key),
+            AddressKind::ShWpkh => ScriptPubkey::p2sh(&(&ScriptPubkey::p2wpkh(key)).into()),
+            AddressKind::Wpkh => ScriptPubkey::p2wpkh(key),
+            AddressKind::Tr => {
+                ScriptPubkey::p2tr(&taproot_output_key(key, None), false).expect("unvalidated")
+            }
+        }
+    }
+}
+
+/// Encode every kind of address `key` can produce, using the encoder `E`. This is useful when
+/// importing a private key, whose funds may be held at any of them.
+pub fn all_addresses<E, K>(key: &K) -> Vec<(AddressKind, Address)>
+where
+    E: BitcoinEncoderMarker,
+    K: AsRef<VerifyingKey>,
+{
+    AddressKind::ALL
+        .iter()
+        .map(|kind| {
+            let address = E::encode_address(&kind.script_pubkey(key))
+                .expect("standard script pubkeys are encodable");
+            (*kind, address)
+        })
+        .collect()
+}
+
+/// Derive `count` script pubkeys of the specified kind from an account xpub, starting at index
+/// `start`. Keys are derived at `account/0/i` for receive scripts and `account/1/i` for change
+/// scripts. Script pubkeys do not depend on the network, so they may be encoded with any
+/// network's address encoder.
+pub fn derive_script_range(
+    account_xpub: &XPub,
+    is_change: bool,
+    start: u32,
+    count: u32,
+    kind: AddressKind,
+) -> DescriptorResult<Vec<ScriptPubkey>> {
+    let chain = account_xpub.derive_child(is_change as u32)?;
+    (start..start.saturating_add(count))
+        .map(|index| Ok(kind.script_pubkey(&chain.derive_child(index)?)))
+        .collect()
+}
+
+/// A key string as exported by Electrum. Electrum exports imported-key wallets as bare hex
+/// pubkeys, and HD wallets as account xpubs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElectrumKey {
+    /// A SEC1-encoded pubkey, compressed or uncompressed. Electrum treats these as legacy P2PKH.
+    Pubkey(Vec<u8>),
+    /// An account xpub. Electrum derives receive keys at `xpub/0/i` and change keys at `xpub/1/i`.
+    Xpub(XPub),
+}
+
+impl ElectrumKey {
+    /// Parse an Electrum key string, using the specified encoder to deserialize xpubs. Hex strings
+    /// are parsed as pubkeys, and anything else as an xpub.
+    pub fn parse<E: XKeyEncoder>(s: &str) -> DescriptorResult<Self> {
+        match hex::decode(s) {
+            Ok(pubkey) => {
+                VerifyingKey::from_sec1_bytes(&pubkey)
+                    .map_err(|_| DescriptorError::Malformatted(s.to_owned()))?;
+                Ok(ElectrumKey::Pubkey(pubkey))
+            }
+            Err(_) => Ok(ElectrumKey::Xpub(E::xpub_from_base58(s)?)),
+        }
+    }
+
+    /// Derive the receive script pubkey at `index`. Pubkeys are always P2PKH, and ignore the
+    /// index and `kind`. Xpubs derive a script of the configured `kind`.
+    pub fn script_pubkey(&self, index: u32, kind: AddressKind) -> DescriptorResult<ScriptPubkey> {
+        match self {
+            ElectrumKey::Pubkey(pubkey) => {
+                let mut v: Vec<u8> = vec![0x76, 0xa9, 0x14]; // DUP, HASH160, PUSH_20
+                v.extend(Hash160::digest(pubkey));
+                v.extend(&[0x88, 0xac]); // EQUALVERIFY, CHECKSIG
+                Ok(v.into())
+            }
+            ElectrumKey::Xpub(xpub) => Ok(derive_script_range(xpub, false, index, 1, kind)?
+                .pop()
+                .expect("one script")),
+        }
+    }
+}
+
+/// If `s` is `name(...)`, return the contents of the parentheses.
+fn unwrap_function<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enc::MainnetEncoder;
+    use coins_bip32::{enc::MainnetEncoder as XKeyMainnetEncoder, xkeys::XPriv};
+    use coins_core::enc::AddressEncoder;
+
+    const XPUB_1: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+    const XPUB_2: &str = "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB";
+
+    #[test]
+    fn it_derives_sortedmulti_scripts() {
+        // (descriptor, index, witness/redeem script, script pubkey)
+        let cases = [
+            (
+                format!("wsh(sortedmulti(2,{}/0/*,{}/0/*))", XPUB_1, XPUB_2),
+                0,
+                "52210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c2102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f552ae",
+                "00200c27bbbaec5619320554ca7e733024266783807e12de9957cdb7e4f59838ae5e",
+            ),
+            (
+                format!("wsh(sortedmulti(2,{}/0/*,{}/0/*))#3dpau5qj", XPUB_1, XPUB_2),
+                1,
+                "522102d27a781fd1b3ec5ba5017ca55b9b900fde598459a0204597b37e6c66a0e35c982102e740d213a1aa5746c66bae1ecda3b95d7f64d4bf8aff9d93702fc302f28df0f152ae",
+                "0020091c2205d8a5aa0a61fc5fd60ccbb41399d4d070d36f09aba3e39562476cb6c6",
+            ),
+            (
+                format!("sh(multi(1,{}/0/*,{}/0/*))", XPUB_2, XPUB_1),
+                0,
+                "51210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c2102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f552ae",
+                "a91470dbdec21db40931fe9a964f69a2fd54efb6016387",
+            ),
+            (
+                format!("sh(wsh(sortedmulti(1,{}/0/*,{}/0/*)))", XPUB_2, XPUB_1),
+                0,
+                "51210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c2102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f552ae",
+                "a91432f4c5e5bed58ab74b3724c18f33dc628a53aea787",
+            ),
+        ];
+        for case in cases.iter() {
+            let descriptor = MultisigDescriptor::parse::<XKeyMainnetEncoder>(&case.0).unwrap();
+            assert_eq!(
+                descriptor.multisig_script(case.1).unwrap(),
+                hex::decode(case.2).unwrap().into()
+            );
+            assert_eq!(
+                descriptor.script_pubkey(case.1).unwrap(),
+                hex::decode(case.3).unwrap().into()
+            );
+        }
+    }
+
+    #[test]
+    fn it_preserves_key_order_for_multi() {
+        let cases = [
+            (
+                format!("wsh(multi(2,{}/0/*,{}/0/*))", XPUB_2, XPUB_1),
+                "52210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c2102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f552ae",
+            ),
+            (
+                format!("wsh(multi(2,{}/0/*,{}/0/*))", XPUB_1, XPUB_2),
+                "522102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f5210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c52ae",
+            ),
+        ];
+        for case in cases.iter() {
+            let descriptor = MultisigDescriptor::parse::<XKeyMainnetEncoder>(&case.0).unwrap();
+            assert_eq!(
+                descriptor.multisig_script(0).unwrap(),
+                hex::decode(case.1).unwrap().into()
+            );
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_descriptors() {
+        let parse = |s: &str| MultisigDescriptor::parse::<XKeyMainnetEncoder>(s).map(|_| ());
+
+        let descriptor = format!("wsh(sortedmulti(2,{}/0/*,{}/0/*))#3dpau5qq", XPUB_1, XPUB_2);
+        match parse(&descriptor) {
+            Err(DescriptorError::BadChecksum { got, expected })
+                if got == "3dpau5qq" && expected == "3dpau5qj" => {}
+            e => panic!("expected bad checksum, got {:?}", e),
+        }
+
+        let descriptor = format!("wsh(sortedmulti(3,{}/0/*,{}/0/*))", XPUB_1, XPUB_2);
+        match parse(&descriptor) {
+            Err(DescriptorError::InvalidThreshold { m: 3, n: 2 }) => {}
+            e => panic!("expected invalid threshold, got {:?}", e),
+        }
+
+        let descriptor = format!("wsh(sortedmulti(0,{}/0/*,{}/0/*))", XPUB_1, XPUB_2);
+        match parse(&descriptor) {
+            Err(DescriptorError::InvalidThreshold { m: 0, n: 2 }) => {}
+            e => panic!("expected invalid threshold, got {:?}", e),
+        }
+
+        let descriptor = format!("wsh(sortedmulti(1,{}/0'/*,{}/0/*))", XPUB_1, XPUB_2);
+        match parse(&descriptor) {
+            Err(DescriptorError::HardenedDerivation(key)) if key == format!("{}/0'/*", XPUB_1) => {}
+            e => panic!("expected hardened derivation, got {:?}", e),
+        }
+
+        // the wildcard must be the last step
+        let descriptor = format!("wsh(sortedmulti(1,{}/*/0,{}/0/*))", XPUB_1, XPUB_2);
+        match parse(&descriptor) {
+            Err(DescriptorError::Malformatted(key)) if key == format!("{}/*/0", XPUB_1) => {}
+            e => panic!("expected malformatted key, got {:?}", e),
+        }
+
+        // only sortedmulti is supported
+        let descriptor = format!("wsh(pk({}/0/*))", XPUB_1);
+        match parse(&descriptor) {
+            Err(DescriptorError::Malformatted(s)) if s == descriptor => {}
+            e => panic!("expected malformatted descriptor, got {:?}", e),
+        }
+
+        // unbalanced parentheses
+        let descriptor = format!("wsh(sortedmulti(1,{}/0/*)", XPUB_1);
+        match parse(&descriptor) {
+            Err(DescriptorError::Malformatted(s)) if s == descriptor => {}
+            e => panic!("expected malformatted descriptor, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_derives_script_ranges() {
+        let xpub = XKeyMainnetEncoder::xpub_from_base58(XPUB_1).unwrap();
+        let encode = |spk: &ScriptPubkey| MainnetEncoder::encode_address(spk).unwrap().as_string();
+
+        let receive = derive_script_range(&xpub, false, 0, 20, AddressKind::Wpkh).unwrap();
+        assert_eq!(receive.len(), 20);
+        assert_eq!(
+            encode(&receive[0]),
+            "bc1qp5wfcq48h6d63wyy9qz0awtpfqwwv4sma86mhz"
+        );
+        assert_eq!(
+            encode(&receive[19]),
+            "bc1q3g86ua7nywpq05zsfw945s9ql54v76gfy54ec6"
+        );
+
+        let nested = derive_script_range(&xpub, false, 0, 1, AddressKind::ShWpkh).unwrap();
+        assert_eq!(encode(&nested[0]), "3AfyxhpBVVLmBR4ZYX2onGzRqjv5QZ7FqD");
+
+        let change = derive_script_range(&xpub, true, 5, 1, AddressKind::Pkh).unwrap();
+        assert_eq!(encode(&change[0]), "1LWYcEi1bUMfiCoXJwTXF4q8iRGnqhb8jr");
+    }
+
+    #[test]
+    fn it_parses_keys_with_origins() {
+        let key = DescriptorKey::parse::<XKeyMainnetEncoder>(&format!(
+            "[d34db33f/84'/0'/0']{}/0/*",
+            XPUB_1
+        ))
+        .unwrap();
+        let origin = key.origin.as_ref().unwrap();
+        assert_eq!(origin.root, KeyFingerprint([0xd3, 0x4d, 0xb3, 0x3f]));
+        assert_eq!(
+            origin.path,
+            vec![84 | BIP32_HARDEN, BIP32_HARDEN, BIP32_HARDEN].into()
+        );
+        assert_eq!(key.path, vec![0].into());
+        assert!(key.wildcard);
+        // XPUB_1 is the BIP32 test vector 1 master key, so public derivation must match
+        // private derivation from its seed
+        let xpriv = XPriv::from_hex_seed("000102030405060708090a0b0c0d0e0f", None).unwrap();
+        let expected = xpriv.derive_path(&[0, 5][..]).unwrap().verify_key();
+        assert_eq!(key.derive(5).unwrap(), expected);
+        assert_eq!(
+            hex::encode(key.derive(5).unwrap().to_sec1_bytes()),
+            "0364a609ea30f2f9e137c3069b387321e6949baa097168e6dbfea48f13fbbe9f79"
+        );
+
+        let bare =
+            DescriptorKey::parse::<XKeyMainnetEncoder>(&format!("[d34db33f]{}", XPUB_1)).unwrap();
+        assert_eq!(bare.origin.unwrap().path, DerivationPath::default());
+
+        let cases = [
+            format!("[d34db33]{}/0/*", XPUB_1),
+            format!("[d34db33g/84']{}/0/*", XPUB_1),
+            format!("[d34db33f/84'/x]{}/0/*", XPUB_1),
+            format!("[d34db33f/84'{}/0/*", XPUB_1),
+        ];
+        for case in cases.iter() {
+            match DescriptorKey::parse::<XKeyMainnetEncoder>(case) {
+                Err(DescriptorError::Malformatted(_)) => {}
+                _ => panic!("expected malformatted error for {}", case),
+            }
+        }
+    }
+
+    #[test]
+    fn it_parses_electrum_keys() {
+        let encode = |spk: &ScriptPubkey| MainnetEncoder::encode_address(spk).unwrap().as_string();
+        let cases = [
+            (
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+                "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            ),
+            (
+                "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+                "76a91491b24bf9f5288532960ac687abb035127b1d28a588ac",
+                "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+            ),
+        ];
+        for (key, script, address) in cases.iter() {
+            let key = ElectrumKey::parse::<XKeyMainnetEncoder>(key).unwrap();
+            // pubkeys are always p2pkh
+            let spk = key.script_pubkey(3, AddressKind::Wpkh).unwrap();
+            assert_eq!(spk, hex::decode(script).unwrap().into());
+            assert_eq!(encode(&spk), *address);
+        }
+
+        let key = ElectrumKey::parse::<XKeyMainnetEncoder>(XPUB_1).unwrap();
+        let spk = key.script_pubkey(0, AddressKind::Wpkh).unwrap();
+        assert_eq!(encode(&spk), "bc1qp5wfcq48h6d63wyy9qz0awtpfqwwv4sma86mhz");
+
+        // not a curve point
+        match ElectrumKey::parse::<XKeyMainnetEncoder>(&format!("02{}", "00".repeat(32))) {
+            Err(DescriptorError::Malformatted(_)) => {}
+            e => panic!("expected malformatted error, got {:?}", e),
+        }
+        let bad_checksum = format!("{}9", &XPUB_1[..XPUB_1.len() - 1]);
+        assert!(ElectrumKey::parse::<XKeyMainnetEncoder>(&bad_checksum).is_err());
+    }
+
+    #[test]
+    fn it_produces_all_addresses_for_a_key() {
+        use coins_bip32::primitives::{ChainCode, Hint, XKeyInfo};
+
+        let xpub = |sec1: &str| {
+            XPub::new(
+                VerifyingKey::from_sec1_bytes(&hex::decode(sec1).unwrap()).unwrap(),
+                XKeyInfo {
+                    depth: 0,
+                    parent: KeyFingerprint([0u8; 4]),
+                    index: 0,
+                    chain_code: ChainCode([0u8; 32]),
+                    hint: Hint::SegWit,
+                },
+            )
+        };
+
+        // The generator point. Its P2WPKH address is the BIP173 example address
+        let key = xpub("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        let addresses = all_addresses::<MainnetEncoder, _>(&key)
+            .into_iter()
+            .map(|(kind, address)| (kind, address.as_string()))
+            .collect::<Vec<_>>();
+        let expected = [
+            (AddressKind::Pkh, "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"),
+            (
+                AddressKind::PkhUncompressed,
+                "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm",
+            ),
+            (AddressKind::ShWpkh, "3JvL6Ymt8MVWiCNHC7oWU6nLeHNJKLZGLN"),
+            (
+                AddressKind::Wpkh,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            ),
+            (
+                AddressKind::Tr,
+                "bc1pmfr3p9j00pfxjh0zmgp99y8zftmd3s5pmedqhyptwy6lm87hf5sspknck9",
+            ),
+        ];
+        assert_eq!(addresses.len(), expected.len());
+        for ((kind, address), (expected_kind, expected_address)) in
+            addresses.iter().zip(expected.iter())
+        {
+            assert_eq!(kind, expected_kind);
+            assert_eq!(address, expected_address);
+        }
+
+        // The first BIP86 receive key. Its y coordinate is odd, so must be negated before the
+        // tweak.
+        let key = xpub("03cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115");
+        let spk = AddressKind::Tr.script_pubkey(&key);
+        assert_eq!(
+            MainnetEncoder::encode_address(&spk).unwrap().as_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+}
@@ -3,35 +3,105 @@
 
 use bech32::Error as BechError;
 use coins_core::enc::{
-    decode_bech32 as core_decode_bech32, encode_bech32 as core_encode_bech32, EncodingError,
-    EncodingResult,
+    decode_bech32 as core_decode_bech32, decode_bech32m as core_decode_bech32m,
+    encode_bech32 as core_encode_bech32, encode_bech32m as core_encode_bech32m, Bech32Variant,
+    EncodingError, EncodingResult,
 };
 
-/// Encode a byte vector to bech32. This function expects `v` to be a witness program, and will
-/// return an `UnknownScriptType` if it does not meet the witness program format.
-pub fn encode_bech32(hrp: &str, v: &[u8]) -> EncodingResult<String> {
-    if v.len() < 2 || v.len() > 40 {
+/// Split a witness program into its witness version and payload. The version byte must be
+/// `OP_0` or one of `OP_1` through `OP_16`, and the push length must match the payload.
+fn split_witness_program(v: &[u8]) -> EncodingResult<(u8, &[u8])> {
+    if v.len() < 4 || v.len() > 42 {
         return Err(BechError::InvalidLength.into());
     }
 
     let (version_and_len, payload) = v.split_at(2);
-    if version_and_len[0] > 16 || version_and_len[1] as usize != payload.len() {
+    if version_and_len[1] as usize != payload.len() {
         return Err(EncodingError::UnknownScriptType);
+    }
+
+    let version = match version_and_len[0] {
+        0x00 => 0,
+        op @ 0x51..=0x60 => op - 0x50,
+        _ => return Err(EncodingError::UnknownScriptType),
     };
+    Ok((version, payload))
+}
 
-    core_encode_bech32(hrp, version_and_len[0], payload)
+/// Build a witness program from a witness version and payload. The inverse of
+/// `split_witness_program`.
+fn join_witness_program(version: u8, data: &[u8]) -> Vec<u8> {
+    let op = if version == 0 { 0x00 } else { 0x50 + version };
+    let mut s: Vec<u8> = vec![op, data.len() as u8];
+    s.extend(data);
+    s
 }
 
-/// Decode a witness program from a bech32 string. Caller specifies an expected HRP. If a
-/// different HRP is found, returns `WrongHrp`.
-pub fn decode_bech32(expected_hrp: &str, s: &str) -> EncodingResult<Vec<u8>> {
-    let (version, data) = core_decode_bech32(expected_hrp, s)?;
+/// Encode a byte vector to bech32. This function expects `v` to be a witness program, and will
+/// return an `UnknownScriptType` if it does not meet the witness program format. Per BIP350, v0
+/// programs use the bech32 checksum, while v1 and later programs use bech32m.
+pub fn encode_bech32(hrp: &str, v: &[u8]) -> EncodingResult<String> {
+    let (version, payload) = split_witness_program(v)?;
+    if version == 0 {
+        core_encode_bech32(hrp, version, payload)
+    } else {
+        core_encode_bech32m(hrp, version, payload)
+    }
+}
+
+/// Decode a witness program from a bech32 or bech32m string, returning the checksum variant
+/// found. Caller specifies an expected HRP. If a different HRP is found, returns `WrongHrp`.
+///
+/// # Note:
+///
+/// This does not check the variant against the witness version. Use `check_bech32_variant`
+/// before accepting the program as an address.
+pub fn decode_bech32(expected_hrp: &str, s: &str) -> EncodingResult<(Bech32Variant, Vec<u8>)> {
+    let (variant, (version, data)) = match core_decode_bech32(expected_hrp, s) {
+        Ok(decoded) => (Bech32Variant::Bech32, decoded),
+        Err(EncodingError::BechError(BechError::InvalidChecksum)) => (
+            Bech32Variant::Bech32m,
+            core_decode_bech32m(expected_hrp, s)?,
+        ),
+        Err(e) => return Err(e),
+    };
+    Ok((variant, join_witness_program(version, &data)))
+}
+
+/// Check that a decoded witness program used the checksum variant BIP350 requires for its
+/// witness version. Returns `WrongBech32Variant` if not.
+pub fn check_bech32_variant(variant: Bech32Variant, program: &[u8]) -> EncodingResult<()> {
+    let (version, _) = split_witness_program(program)?;
+    if variant != Bech32Variant::for_witness_version(version) {
+        return Err(EncodingError::WrongBech32Variant {
+            version,
+            got: variant,
+        });
+    }
+    Ok(())
+}
 
-    // Encode as witness program: witness version 0, then len(payload), then payload.
-    let mut s: Vec<u8> = vec![version, data.len() as u8];
-    s.extend(&data);
+/// Encode a witness program to bech32m. This function expects `v` to be a v1 or later witness
+/// program, and will return an `UnknownScriptType` if it is a v0 program, or does not meet the
+/// witness program format.
+pub fn encode_bech32m(hrp: &str, v: &[u8]) -> EncodingResult<String> {
+    match split_witness_program(v)? {
+        (0, _) => Err(EncodingError::UnknownScriptType),
+        (version, payload) => core_encode_bech32m(hrp, version, payload),
+    }
+}
 
-    Ok(s)
+/// Decode a v1 or later witness program from a bech32m string. Caller specifies an expected HRP.
+/// If a different HRP is found, returns `WrongHrp`. v0 programs must use bech32, and return
+/// `WrongBech32Variant`.
+pub fn decode_bech32m(expected_hrp: &str, s: &str) -> EncodingResult<Vec<u8>> {
+    match core_decode_bech32m(expected_hrp, s)? {
+        (0, _) => Err(EncodingError::WrongBech32Variant {
+            version: 0,
+            got: Bech32Variant::Bech32m,
+        }),
+        (version, data) => Ok(join_witness_program(version, &data)),
+    }
 }
 
 #[cfg(test)]
@@ -53,9 +123,71 @@ mod test {
             "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej",
         ];
         for addr in addrs.iter() {
-            let s = decode_bech32(hrp, addr).unwrap();
+            let (variant, s) = decode_bech32(hrp, addr).unwrap();
+            assert_eq!(variant, Bech32Variant::Bech32);
+            check_bech32_variant(variant, &s).unwrap();
             let reencoded = encode_bech32(hrp, &s).unwrap();
             assert_eq!(*addr, reencoded);
         }
     }
+
+    #[test]
+    fn it_should_encode_and_decode_bech32m() {
+        // BIP350 valid segwit addresses with witness version 1 or later
+        let cases = [
+            (
+                "bc",
+                "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+                "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            ("bc", "bc1sw50qgdz25j", "6002751e"),
+            (
+                "tb",
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+            (
+                "bc",
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+        ];
+        for (hrp, addr, program) in cases.iter() {
+            let s = decode_bech32m(hrp, addr).unwrap();
+            assert_eq!(s, hex::decode(program).unwrap());
+            assert_eq!(
+                decode_bech32(hrp, addr).unwrap(),
+                (Bech32Variant::Bech32m, s.clone())
+            );
+            assert_eq!(*addr, encode_bech32m(hrp, &s).unwrap());
+            assert_eq!(*addr, encode_bech32(hrp, &s).unwrap());
+        }
+
+        let v0 = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+        let (_, s) = decode_bech32("bc", v0).unwrap();
+        assert!(encode_bech32m("bc", &s).is_err());
+        assert!(decode_bech32m("bc", v0).is_err());
+    }
+
+    #[test]
+    fn it_rejects_mismatched_bech32_variants() {
+        // BIP350 invalid addresses
+        let addrs = [
+            // v1 program with a bech32 checksum
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+            // v0 program with a bech32m checksum
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+        ];
+        let versions = [(1, Bech32Variant::Bech32), (0, Bech32Variant::Bech32m)];
+        for (addr, (version, got)) in addrs.iter().zip(versions.iter()) {
+            let (variant, program) = decode_bech32("bc", addr).unwrap();
+            assert_eq!(variant, *got);
+            match check_bech32_variant(variant, &program) {
+                Err(EncodingError::WrongBech32Variant { version: v, got: g })
+                    if v == *version && g == *got => {}
+                e => panic!("expected err WrongBech32Variant, got {:?}", e),
+            }
+            assert!(decode_bech32m("bc", addr).is_err());
+        }
+    }
 }
@@ -9,7 +9,9 @@ use coins_core::{
 };
 
 use crate::{
-    enc::bases::{decode_bech32, encode_bech32},
+    enc::bases::{
+        check_bech32_variant, decode_bech32, decode_bech32m, encode_bech32, encode_bech32m,
+    },
     types::script::{ScriptPubkey, ScriptType},
 };
 
@@ -24,6 +26,8 @@ pub enum Address {
     Wpkh(String),
     /// Witness Pay to Scripthash
     Wsh(String),
+    /// Witness v1 Pay to Taproot
+    Tr(String),
 }
 
 impl std::fmt::Display for Address {
@@ -33,6 +37,7 @@ impl std::fmt::Display for Address {
             Address::Sh(s) => s,
             Address::Wpkh(s) => s,
             Address::Wsh(s) => s,
+            Address::Tr(s) => s,
         };
         write!(f, "{}", addr)
     }
@@ -45,6 +50,7 @@ impl AsRef<str> for Address {
             Address::Sh(s) => s,
             Address::Wpkh(s) => s,
             Address::Wsh(s) => s,
+            Address::Tr(s) => s,
         }
     }
 }
@@ -57,6 +63,17 @@ impl Address {
             Address::Sh(s) => s.clone(),
             Address::Wpkh(s) => s.clone(),
             Address::Wsh(s) => s.clone(),
+            Address::Tr(s) => s.clone(),
+        }
+    }
+
+    /// The witness version of a segwit address. 0 for `Wpkh` and `Wsh`, 1 for `Tr`, and `None`
+    /// for the legacy `Pkh` and `Sh`.
+    pub fn witness_version(&self) -> Option<u8> {
+        match self {
+            Address::Pkh(_) | Address::Sh(_) => None,
+            Address::Wpkh(_) | Address::Wsh(_) => Some(0),
+            Address::Tr(_) => Some(1),
         }
     }
 
@@ -64,6 +81,18 @@ impl Address {
     pub fn to_descriptor(&self) -> String {
         format!("addr({})", self.as_string())
     }
+
+    /// Decode the address to a script pubkey using the encoder `E`, and re-encode it. Returns
+    /// `true` if this produces the identical address, and `false` if the address is invalid for
+    /// `E`, or if the encoder and decoder disagree.
+    pub fn roundtrip_check<E: BitcoinEncoderMarker>(&self) -> bool {
+        match E::string_to_address(self.as_ref()) {
+            Ok(parsed) if &parsed == self => {}
+            _ => return false,
+        }
+        let script = E::decode_address(self);
+        matches!(E::encode_address(&script), Ok(ref encoded) if encoded == self)
+    }
 }
 
 /// NetworkParams holds the encoding paramteres for a bitcoin-like network. Currently this is
@@ -78,10 +107,52 @@ pub trait NetworkParams {
     const SH_VERSION: u8;
 }
 
+/// Options controlling how strictly `BitcoinEncoderMarker::string_to_address_with` parses
+/// address strings. The default is strict, and matches `string_to_address`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Trim leading and trailing whitespace before decoding
+    pub trim_whitespace: bool,
+    /// Accept all-uppercase bech32 addresses, e.g. from QR codes. These are returned in their
+    /// canonical lowercase form.
+    pub accept_uppercase: bool,
+}
+
+impl DecodeOptions {
+    /// Reject any deviation from the canonical address string.
+    pub fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Trim surrounding whitespace, and accept uppercase bech32.
+    pub fn permissive() -> Self {
+        Self {
+            trim_whitespace: true,
+            accept_uppercase: true,
+        }
+    }
+}
+
 /// Marker trait to simplify encoder representation elsewhere
 pub trait BitcoinEncoderMarker:
     AddressEncoder<Address = Address, Error = EncodingError, RecipientIdentifier = ScriptPubkey>
 {
+    /// Attempt to convert a string into an `Address`, with the given `DecodeOptions`. Strict
+    /// options behave exactly as `string_to_address`.
+    fn string_to_address_with(string: &str, options: DecodeOptions) -> EncodingResult<Address> {
+        let s = if options.trim_whitespace {
+            string.trim()
+        } else {
+            string
+        };
+        match Self::string_to_address(s) {
+            // bech32 may be all uppercase. Base58check is case sensitive, so is tried first.
+            Err(e) if options.accept_uppercase && !s.chars().any(|c| c.is_ascii_lowercase()) => {
+                Self::string_to_address(&s.to_ascii_lowercase()).map_err(|_| e)
+            }
+            result => result,
+        }
+    }
 }
 
 /// The standard encoder for Bitcoin networks. Parameterized by a `NetworkParams` type and an
@@ -112,8 +183,11 @@ impl<P: NetworkParams> AddressEncoder for BitcoinEncoder<P> {
             }
             ScriptType::Wsh(_) => Ok(Address::Wsh(encode_bech32(P::HRP, s.items())?)),
             ScriptType::Wpkh(_) => Ok(Address::Wpkh(encode_bech32(P::HRP, s.items())?)),
+            ScriptType::Tr(_) => Ok(Address::Tr(encode_bech32m(P::HRP, s.items())?)),
             ScriptType::OpReturn(_) => Err(EncodingError::NullDataScript),
-            ScriptType::NonStandard => Err(EncodingError::UnknownScriptType),
+            ScriptType::CltvP2pk(..) | ScriptType::CsvP2pk(..) | ScriptType::NonStandard => {
+                Err(EncodingError::UnknownScriptType)
+            }
         }
     }
 
@@ -131,19 +205,26 @@ impl<P: NetworkParams> AddressEncoder for BitcoinEncoder<P> {
                 v.extend(&[0x87]); // EUQAL
                 v.into()
             }
-            Address::Wpkh(s) | Address::Wsh(s) => decode_bech32(P::HRP, s).unwrap().into(),
+            Address::Wpkh(s) | Address::Wsh(s) => decode_bech32(P::HRP, s).unwrap().1.into(),
+            Address::Tr(s) => decode_bech32m(P::HRP, s).unwrap().into(),
         }
     }
 
     fn string_to_address(string: &str) -> EncodingResult<Address> {
         let s = string.to_owned();
+        // The HRP check must come first. Regtest shares base58check versions with testnet, so
+        // only the HRP distinguishes the two.
         if s.starts_with(P::HRP) {
-            let result = decode_bech32(P::HRP, &s)?;
-            match result.len() {
-                22 => Ok(Address::Wpkh(s)),
-                34 => Ok(Address::Wsh(s)),
-                _ => Err(EncodingError::UnknownScriptType),
-            }
+            // Taproot is the only known v1 program.
+            let (variant, result) = decode_bech32(P::HRP, &s)?;
+            let address = match (result[0], result.len()) {
+                (0, 22) => Address::Wpkh(s),
+                (0, 34) => Address::Wsh(s),
+                (0x51, 34) => Address::Tr(s),
+                _ => return Err(EncodingError::UnknownScriptType),
+            };
+            check_bech32_variant(variant, &result)?;
+            Ok(address)
         } else if decode_base58(P::PKH_VERSION, &s).is_ok() {
             Ok(Address::Pkh(s))
         } else if decode_base58(P::SH_VERSION, &s).is_ok() {
@@ -186,6 +267,17 @@ impl NetworkParams for Sig {
     const SH_VERSION: u8 = 0x57;
 }
 
+/// A param struct for Bitcoin Regtest. Regtest shares testnet's base58check version bytes, and
+/// differs only in its bech32 HRP.
+#[derive(Debug, Clone)]
+pub struct Reg;
+
+impl NetworkParams for Reg {
+    const HRP: &'static str = "bcrt";
+    const PKH_VERSION: u8 = 0x6f;
+    const SH_VERSION: u8 = 0xc4;
+}
+
 /// An encoder for Bitcoin Mainnet
 pub type MainnetEncoder = BitcoinEncoder<Main>;
 
@@ -195,9 +287,100 @@ pub type TestnetEncoder = BitcoinEncoder<Test>;
 /// An encoder for Bitcoin Signet
 pub type SignetEncoder = BitcoinEncoder<Sig>;
 
+/// An encoder for Bitcoin Regtest
+pub type RegtestEncoder = BitcoinEncoder<Reg>;
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use coins_core::enc::Bech32Variant;
+
+    // p2pkh, p2sh, p2wpkh, and p2wsh script pubkeys
+    static SCRIPTS: [&str; 4] = [
+        "76a914758ce550380d964051086798d6546bebdca27a7388ac",
+        "a914758ce550380d964051086798d6546bebdca27a7387",
+        "0014758ce550380d964051086798d6546bebdca27a73",
+        "00200c27bb5dd9561932055aca7e7330242667838287e12de9957cdb7e4f5983845c",
+    ];
+
+    /// Assert that every address in the corpus round-trips through the encoder `E`, and decodes
+    /// to the corresponding script in `SCRIPTS`.
+    fn validate_address_corpus<E: BitcoinEncoderMarker>(corpus: &[Address]) {
+        for (address, script) in corpus.iter().zip(SCRIPTS.iter()) {
+            assert!(
+                address.roundtrip_check::<E>(),
+                "{} failed roundtrip",
+                address
+            );
+            assert_eq!(
+                E::decode_address(address).items(),
+                &hex::decode(script).unwrap()[..]
+            );
+        }
+    }
+
+    #[test]
+    fn it_roundtrips_addresses() {
+        validate_address_corpus::<MainnetEncoder>(&[
+            Address::Pkh("1BiYr44pgRG3Z2T5dgMpcAH4gpPWV4KdGA".to_owned()),
+            Address::Sh("3CQZmbZGEKaReC9Wkn2R2ndzqLgE5QaPYZ".to_owned()),
+            Address::Wpkh("bc1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnhtq88f".to_owned()),
+            Address::Wsh(
+                "bc1qpsnmkhwe2cvnyp26efl8xvpyyenc8q58uyk7n9tumdly7kvrs3wqkk4u0y".to_owned(),
+            ),
+        ]);
+        validate_address_corpus::<TestnetEncoder>(&[
+            Address::Pkh("mrEW979oVShJL8vhMFLCS5VPYozDM1fqqi".to_owned()),
+            Address::Sh("2N3xmqLVHqn5mqyn4RueHejdG3gtPsCCXND".to_owned()),
+            Address::Wpkh("tb1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnadm5u6".to_owned()),
+            Address::Wsh(
+                "tb1qpsnmkhwe2cvnyp26efl8xvpyyenc8q58uyk7n9tumdly7kvrs3wqp7rn4t".to_owned(),
+            ),
+        ]);
+        let signet = SCRIPTS
+            .iter()
+            .map(|s| SignetEncoder::encode_address(&hex::decode(s).unwrap().into()).unwrap())
+            .collect::<Vec<_>>();
+        validate_address_corpus::<SignetEncoder>(&signet);
+        validate_address_corpus::<RegtestEncoder>(&[
+            Address::Pkh("mrEW979oVShJL8vhMFLCS5VPYozDM1fqqi".to_owned()),
+            Address::Sh("2N3xmqLVHqn5mqyn4RueHejdG3gtPsCCXND".to_owned()),
+            Address::Wpkh("bcrt1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnlyzetn".to_owned()),
+            Address::Wsh(
+                "bcrt1qpsnmkhwe2cvnyp26efl8xvpyyenc8q58uyk7n9tumdly7kvrs3wqv8f4q3".to_owned(),
+            ),
+        ]);
+
+        // Wrong network, and wrong address type
+        let mainnet = Address::Wpkh("bc1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnhtq88f".to_owned());
+        assert!(!mainnet.roundtrip_check::<TestnetEncoder>());
+        let regtest = Address::Wpkh("bcrt1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnlyzetn".to_owned());
+        assert!(!regtest.roundtrip_check::<MainnetEncoder>());
+        assert!(!regtest.roundtrip_check::<TestnetEncoder>());
+        let mislabeled = Address::Sh("1BiYr44pgRG3Z2T5dgMpcAH4gpPWV4KdGA".to_owned());
+        assert!(!mislabeled.roundtrip_check::<MainnetEncoder>());
+    }
+
+    #[test]
+    fn it_reports_witness_versions() {
+        let cases = [
+            ("1BiYr44pgRG3Z2T5dgMpcAH4gpPWV4KdGA", None),
+            ("3CQZmbZGEKaReC9Wkn2R2ndzqLgE5QaPYZ", None),
+            ("bc1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnhtq88f", Some(0)),
+            (
+                "bc1qpsnmkhwe2cvnyp26efl8xvpyyenc8q58uyk7n9tumdly7kvrs3wqkk4u0y",
+                Some(0),
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                Some(1),
+            ),
+        ];
+        for (addr, version) in cases.iter() {
+            let address = MainnetEncoder::string_to_address(addr).unwrap();
+            assert_eq!(address.witness_version(), *version, "{}", addr);
+        }
+    }
 
     #[test]
     fn it_wraps_address_strings() {
@@ -293,6 +476,114 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_encodes_and_decodes_taproot_addresses() {
+        let cases = [
+            (
+                "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            ),
+            (
+                "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+                "bc1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvses7epu4h",
+            ),
+        ];
+        for case in cases.iter() {
+            let script = ScriptPubkey::new(hex::decode(case.0).unwrap());
+            let address = Address::Tr(case.1.to_owned());
+            assert_eq!(MainnetEncoder::encode_address(&script).unwrap(), address);
+            assert_eq!(MainnetEncoder::string_to_address(case.1).unwrap(), address);
+            assert_eq!(MainnetEncoder::decode_address(&address), script);
+            assert!(address.roundtrip_check::<MainnetEncoder>());
+        }
+        assert_eq!(
+            TestnetEncoder::string_to_address(
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c"
+            )
+            .unwrap(),
+            Address::Tr(
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c".to_owned()
+            )
+        );
+
+        let mismatched = [
+            // v1 program with a bech32 checksum
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+                1,
+                Bech32Variant::Bech32,
+            ),
+            // v0 program with a bech32m checksum
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+                0,
+                Bech32Variant::Bech32m,
+            ),
+        ];
+        for (addr, version, got) in mismatched.iter() {
+            match MainnetEncoder::string_to_address(addr) {
+                Err(EncodingError::WrongBech32Variant { version: v, got: g })
+                    if v == *version && g == *got => {}
+                e => panic!("expected err WrongBech32Variant, got {:?}", e),
+            }
+        }
+
+        let errors = [
+            // 40-byte v1 program
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+        ];
+        for case in errors.iter() {
+            match MainnetEncoder::string_to_address(case) {
+                Err(EncodingError::UnknownScriptType) => {}
+                e => panic!("expected err UnknownScriptType, got {:?}", e),
+            }
+        }
+        let future = ScriptPubkey::new(
+            hex::decode(
+                "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+            )
+            .unwrap(),
+        );
+        match MainnetEncoder::encode_address(&future) {
+            Err(EncodingError::UnknownScriptType) => {}
+            e => panic!("expected err UnknownScriptType, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_decodes_addresses_strictly_or_permissively() {
+        let wpkh = "bc1qvyyvsdcd0t9863stt7u9rf37wx443lzasg0usy";
+        let pkh = "1AqE7oGF1EUoJviX1uuYrwpRBdEBTuGhES";
+
+        let padded = format!("  {}\n", wpkh);
+        assert!(MainnetEncoder::string_to_address(&padded).is_err());
+        assert!(MainnetEncoder::string_to_address_with(&padded, DecodeOptions::default()).is_err());
+        assert_eq!(
+            MainnetEncoder::string_to_address_with(&padded, DecodeOptions::permissive()).unwrap(),
+            Address::Wpkh(wpkh.to_owned())
+        );
+
+        let upper = wpkh.to_uppercase();
+        assert!(MainnetEncoder::string_to_address_with(&upper, DecodeOptions::strict()).is_err());
+        assert_eq!(
+            MainnetEncoder::string_to_address_with(&upper, DecodeOptions::permissive()).unwrap(),
+            Address::Wpkh(wpkh.to_owned())
+        );
+
+        // base58check is case sensitive, and mixed-case bech32 is always invalid
+        let mixed = format!("BC1Q{}", &wpkh[4..]);
+        let cases = [pkh.to_uppercase(), pkh.to_lowercase(), mixed];
+        for case in cases.iter() {
+            assert!(
+                MainnetEncoder::string_to_address_with(case, DecodeOptions::permissive()).is_err()
+            );
+        }
+        assert_eq!(
+            MainnetEncoder::string_to_address_with(pkh, DecodeOptions::permissive()).unwrap(),
+            Address::Pkh(pkh.to_owned())
+        );
+    }
+
     #[test]
     fn it_allows_you_to_unwrap_strings_from_addresses() {
         let cases = [
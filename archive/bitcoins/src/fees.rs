@@ -0,0 +1,91 @@
+//! Fee rate estimation from mempool data.
+
+/// The consensus maximum block weight.
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// A histogram of mempool fee rates, as served by Electrum servers' `mempool.get_fee_histogram`.
+/// Each bucket is a fee rate in sat/vbyte, and the total vsize of mempool transactions paying
+/// at least that rate, but less than the next higher bucket's rate.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MempoolFeeHistogram {
+    buckets: Vec<(f64, u64)>,
+}
+
+impl From<Vec<(f64, u64)>> for MempoolFeeHistogram {
+    fn from(buckets: Vec<(f64, u64)>) -> Self {
+        Self::new(buckets)
+    }
+}
+
+impl MempoolFeeHistogram {
+    /// Instantiate a histogram from `(sat_per_vb, vsize)` buckets, in any order. Buckets with a
+    /// NaN or infinite fee rate are discarded.
+    pub fn new(mut buckets: Vec<(f64, u64)>) -> Self {
+        buckets.retain(|(rate, _)| rate.is_finite());
+        buckets.sort_by(|a, b| b.0.partial_cmp(&a.0).expect("rates are finite"));
+        Self { buckets }
+    }
+
+    /// The buckets, ordered from highest to lowest fee rate.
+    pub fn buckets(&self) -> &[(f64, u64)] {
+        &self.buckets
+    }
+
+    /// The total vsize of all transactions in the histogram.
+    pub fn total_vsize(&self) -> u64 {
+        self.buckets.iter().map(|(_, vsize)| vsize).sum()
+    }
+
+    /// The fee rate, in sat/vbyte, at which the transactions paying that rate or more fill a
+    /// block of `block_weight_limit`. A transaction must pay more than this to be mined in the
+    /// next block, assuming no new transactions arrive. Returns 0 if the whole mempool fits in
+    /// the block. Callers should still respect the minimum relay fee.
+    pub fn rate_to_clear(&self, block_weight_limit: u64) -> f64 {
+        let capacity = block_weight_limit / 4;
+        let mut cumulative = 0u64;
+        for (rate, vsize) in self.buckets.iter() {
+            cumulative = cumulative.saturating_add(*vsize);
+            if cumulative >= capacity {
+                return *rate;
+            }
+        }
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_computes_the_rate_to_clear_a_mempool_histogram() {
+        let histogram = MempoolFeeHistogram::new(vec![
+            (2.0, 600_000),
+            (50.0, 100_000),
+            (f64::NAN, 1_000_000),
+            (10.0, 400_000),
+            (20.0, 300_000),
+        ]);
+        assert_eq!(
+            histogram.buckets(),
+            &[
+                (50.0, 100_000),
+                (20.0, 300_000),
+                (10.0, 400_000),
+                (2.0, 600_000)
+            ]
+        );
+        assert_eq!(histogram.total_vsize(), 1_400_000);
+
+        // 50 and 20 sat/vb fill 400k vbytes, 10 sat/vb fills 800k, and 2 sat/vb fills 1.4M.
+        assert_eq!(histogram.rate_to_clear(MAX_BLOCK_WEIGHT), 2.0);
+        assert_eq!(histogram.rate_to_clear(1_600_000), 20.0);
+        assert_eq!(histogram.rate_to_clear(3_200_000), 10.0);
+        // the whole mempool fits
+        assert_eq!(histogram.rate_to_clear(8_000_000), 0.0);
+        assert_eq!(
+            MempoolFeeHistogram::default().rate_to_clear(MAX_BLOCK_WEIGHT),
+            0.0
+        );
+    }
+}
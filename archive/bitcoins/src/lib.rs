@@ -6,11 +6,16 @@
 #![warn(unused_extern_crates)]
 
 pub mod builder;
+pub mod descriptors;
 pub mod enc;
+pub mod fees;
 pub mod hashes;
 pub mod nets;
 pub mod types;
 
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
 /// Common re-exports
 pub mod prelude;
 
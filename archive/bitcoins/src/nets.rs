@@ -29,14 +29,19 @@
 //! ```
 use std::marker::PhantomData;
 
+use coins_bip32::enc::WifNetwork;
 use coins_core::{
-    enc::{AddressEncoder, EncodingError},
-    nets::Network,
+    enc::{bases::decode_base58, AddressEncoder, EncodingError},
+    nets,
 };
 
 use crate::{
     builder::BitcoinTxBuilder,
-    enc::encoder::{Address, BitcoinEncoderMarker, MainnetEncoder, SignetEncoder, TestnetEncoder},
+    enc::bases::{check_bech32_variant, decode_bech32},
+    enc::encoder::{
+        Address, BitcoinEncoderMarker, Main, MainnetEncoder, NetworkParams, Reg, RegtestEncoder,
+        Sig, SignetEncoder, Test, TestnetEncoder,
+    },
     types::{
         BitcoinTransaction, BitcoinTx, BitcoinTxIn, ScriptPubkey, TxOut, WitnessTransaction,
         WitnessTx,
@@ -45,7 +50,7 @@ use crate::{
 
 /// A trait for a Bitcoin network. Specifies that Witness Txns must use the same Input and Output
 /// format as Legacy transactions.
-pub trait BitcoinNetwork: Network {
+pub trait BitcoinNetwork: nets::Network {
     /// An associated witness transaction type.
     type WTx: WitnessTransaction + BitcoinTransaction;
 }
@@ -55,7 +60,7 @@ pub trait BitcoinNetwork: Network {
 #[derive(Debug)]
 pub struct Bitcoin<T: AddressEncoder>(PhantomData<fn(T) -> T>);
 
-impl<T> Network for Bitcoin<T>
+impl<T> nets::Network for Bitcoin<T>
 where
     T: BitcoinEncoderMarker,
 {
@@ -85,11 +90,160 @@ pub type BitcoinTestnet = Bitcoin<TestnetEncoder>;
 /// A fully-parameterized BitcoinSignet. This is the main interface for accessing the library.
 pub type BitcoinSignet = Bitcoin<SignetEncoder>;
 
+/// A fully-parameterized BitcoinRegtest. This is the main interface for accessing the library.
+pub type BitcoinRegtest = Bitcoin<RegtestEncoder>;
+
+/// A runtime identifier for a Bitcoin network, carrying the metadata needed for address encoding
+/// and P2P communication. This allows generic code to take a single `Network` value, rather than
+/// an encoder type parameter and separate P2P configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    /// Bitcoin mainnet
+    Mainnet,
+    /// Bitcoin testnet3
+    Testnet,
+    /// The default Bitcoin signet
+    Signet,
+    /// A local regtest network
+    Regtest,
+}
+
+impl Network {
+    /// All known networks.
+    pub const ALL: [Network; 4] = [
+        Network::Mainnet,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ];
+
+    /// The network's name, as used by Bitcoin Core's `-chain` option.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    /// The address encoding parameters: the bech32 HRP, and the base58check PKH and SH version
+    /// bytes. These match the network's `NetworkParams`.
+    fn params(&self) -> (&'static str, u8, u8) {
+        fn of<P: NetworkParams>() -> (&'static str, u8, u8) {
+            (P::HRP, P::PKH_VERSION, P::SH_VERSION)
+        }
+        match self {
+            Network::Mainnet => of::<Main>(),
+            Network::Testnet => of::<Test>(),
+            Network::Signet => of::<Sig>(),
+            Network::Regtest => of::<Reg>(),
+        }
+    }
+
+    /// The bech32 HRP for segwit addresses on this network.
+    pub fn hrp(&self) -> &'static str {
+        self.params().0
+    }
+
+    /// The base58check version byte for PKH addresses on this network.
+    pub fn pkh_version(&self) -> u8 {
+        self.params().1
+    }
+
+    /// The base58check version byte for SH addresses on this network.
+    pub fn sh_version(&self) -> u8 {
+        self.params().2
+    }
+
+    /// The WIF private key network. Every network other than mainnet shares testnet's WIF
+    /// version byte.
+    pub fn wif_network(&self) -> WifNetwork {
+        match self {
+            Network::Mainnet => WifNetwork::Mainnet,
+            _ => WifNetwork::Testnet,
+        }
+    }
+
+    /// The 4-byte magic that prefixes every P2P message on this network.
+    pub fn magic(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    /// Look up the network using the P2P message magic `magic`. Returns `None` if the magic is
+    /// unknown.
+    pub fn from_magic(magic: &[u8; 4]) -> Option<Network> {
+        Network::ALL.iter().copied().find(|n| &n.magic() == magic)
+    }
+
+    /// The default P2P port for this network.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Network::Mainnet => 8333,
+            Network::Testnet => 18333,
+            Network::Signet => 38333,
+            Network::Regtest => 18444,
+        }
+    }
+
+    /// The well-known DNS seeds used for peer discovery on this network. Regtest has none.
+    pub fn dns_seeds(&self) -> &'static [&'static str] {
+        match self {
+            Network::Mainnet => &[
+                "seed.bitcoin.sipa.be",
+                "dnsseed.bluematt.me",
+                "seed.bitcoinstats.com",
+                "seed.bitcoin.jonasschnelli.ch",
+                "seed.btc.petertodd.net",
+                "seed.bitcoin.sprovoost.nl",
+                "dnsseed.emzy.de",
+                "seed.bitcoin.wiz.biz",
+            ],
+            Network::Testnet => &[
+                "testnet-seed.bitcoin.jonasschnelli.ch",
+                "seed.tbtc.petertodd.net",
+                "seed.testnet.bitcoin.sprovoost.nl",
+                "testnet-seed.bluematt.me",
+            ],
+            Network::Signet => &["seed.signet.bitcoin.sprovoost.nl"],
+            Network::Regtest => &[],
+        }
+    }
+}
+
+/// Determine which network an address string belongs to, by inspecting its bech32 HRP or its
+/// base58check version byte. Returns `None` if the string is not a valid address on any known
+/// network.
+///
+/// # Note:
+///
+/// Regtest shares testnet's base58check version bytes, so base58check addresses on either are
+/// reported as `Network::Testnet`. Only regtest bech32 addresses are detected as regtest.
+pub fn detect_network(addr: &str) -> Option<Network> {
+    if let Some(pos) = addr.rfind('1') {
+        let hrp = addr[..pos].to_lowercase();
+        if let Some(network) = Network::ALL.iter().find(|n| n.hrp() == hrp) {
+            return decode_bech32(network.hrp(), addr)
+                .and_then(|(variant, program)| check_bech32_variant(variant, &program))
+                .ok()
+                .map(|_| *network);
+        }
+    }
+    Network::ALL.iter().copied().find(|n| {
+        decode_base58(n.pkh_version(), addr).is_ok() || decode_base58(n.sh_version(), addr).is_ok()
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::types::txin::BitcoinOutpoint;
-    use coins_core::{builder::TxBuilder, ser::ByteFormat};
+    use coins_core::{builder::TxBuilder, nets::Network as _, ser::ByteFormat};
 
     #[test]
     fn it_has_sensible_syntax() {
@@ -122,4 +276,93 @@ mod test {
         let u = BitcoinMainnet::decode_address(&address);
         assert_eq!(&address, &BitcoinMainnet::encode_address(&u).unwrap())
     }
+
+    #[test]
+    fn it_maps_networks_to_magic() {
+        let cases = [
+            (Network::Mainnet, "f9beb4d9"),
+            (Network::Testnet, "0b110907"),
+            (Network::Signet, "0a03cf40"),
+            (Network::Regtest, "fabfb5da"),
+        ];
+        for (network, magic) in cases.iter() {
+            let mut expected = [0u8; 4];
+            expected.copy_from_slice(&hex::decode(magic).unwrap());
+            assert_eq!(network.magic(), expected);
+            assert_eq!(Network::from_magic(&expected), Some(*network));
+        }
+        assert_eq!(Network::from_magic(&[0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn it_exposes_ports_and_seeds() {
+        assert_eq!(Network::Mainnet.default_port(), 8333);
+        assert_eq!(Network::Testnet.default_port(), 18333);
+        assert_eq!(Network::Signet.default_port(), 38333);
+        assert_eq!(Network::Regtest.default_port(), 18444);
+
+        assert!(!Network::Mainnet.dns_seeds().is_empty());
+        assert!(Network::Regtest.dns_seeds().is_empty());
+    }
+
+    #[test]
+    fn it_ties_network_metadata_together() {
+        let network = Network::Mainnet;
+        assert_eq!(network.name(), "main");
+        assert_eq!(network.hrp(), "bc");
+        assert_eq!(network.hrp(), Main::HRP);
+        assert_eq!(network.pkh_version(), 0x00);
+        assert_eq!(network.sh_version(), 0x05);
+        assert_eq!(hex::encode(network.magic()), "f9beb4d9");
+        assert_eq!(network.default_port(), 8333);
+
+        assert_eq!(Network::Testnet.hrp(), "tb");
+        assert_eq!(Network::Signet.hrp(), "sb");
+        assert_eq!(Network::Regtest.hrp(), "bcrt");
+
+        assert_eq!(network.wif_network().version(), 0x80);
+        assert_eq!(Network::Regtest.wif_network(), WifNetwork::Testnet);
+    }
+
+    #[test]
+    fn it_detects_address_networks() {
+        let cases = [
+            ("1BiYr44pgRG3Z2T5dgMpcAH4gpPWV4KdGA", Some(Network::Mainnet)),
+            ("3CQZmbZGEKaReC9Wkn2R2ndzqLgE5QaPYZ", Some(Network::Mainnet)),
+            (
+                "bc1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnhtq88f",
+                Some(Network::Mainnet),
+            ),
+            (
+                "BC1QWKXW25PCPKTYQ5GGV7VDV4RTA0W2Y7NNHTQ88F",
+                Some(Network::Mainnet),
+            ),
+            ("mrEW979oVShJL8vhMFLCS5VPYozDM1fqqi", Some(Network::Testnet)),
+            (
+                "2N3xmqLVHqn5mqyn4RueHejdG3gtPsCCXND",
+                Some(Network::Testnet),
+            ),
+            (
+                "tb1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnadm5u6",
+                Some(Network::Testnet),
+            ),
+            (
+                "bcrt1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnlyzetn",
+                Some(Network::Regtest),
+            ),
+            // bad checksums
+            ("bc1qwkxw25pcpktyq5ggv7vdv4rta0w2y7nnhtq88g", None),
+            ("1BiYr44pgRG3Z2T5dgMpcAH4gpPWV4KdGB", None),
+            ("not an address", None),
+        ];
+        for (addr, network) in cases.iter() {
+            assert_eq!(detect_network(addr), *network, "{}", addr);
+        }
+
+        let script = BitcoinMainnet::decode_address(&Address::Pkh(
+            "1BiYr44pgRG3Z2T5dgMpcAH4gpPWV4KdGA".to_owned(),
+        ));
+        let signet = SignetEncoder::encode_address(&script).unwrap();
+        assert_eq!(detect_network(signet.as_ref()), Some(Network::Signet));
+    }
 }
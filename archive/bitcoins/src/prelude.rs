@@ -1,6 +1,7 @@
 pub use crate::{
     builder::*,
     enc::*,
+    fees::*,
     hashes::{BlockHash, TXID, WTXID},
     types::*,
 };
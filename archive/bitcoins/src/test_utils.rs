@@ -0,0 +1,120 @@
+//! Helpers for building realistic signed transactions in tests. Available in this crate's tests,
+//! and to downstream crates via the `test-utils` feature.
+//!
+//! Signing is deterministic (RFC6979), so transactions produced here are identical across runs,
+//! and their sizes, weights, and txids may be asserted exactly.
+
+use coins_bip32::{
+    k256::ecdsa::SigningKey,
+    primitives::{ChainCode, Hint, KeyFingerprint, XKeyInfo},
+    xkeys::XPriv,
+};
+use coins_core::types::tx::Transaction;
+
+use crate::{
+    enc::Address,
+    types::{
+        script::ScriptPubkey,
+        txin::{BitcoinOutpoint, BitcoinTxIn},
+        txout::TxOut,
+        utxo::{SpendScript, Utxo},
+        witness::WitnessTx,
+    },
+};
+
+/// The secret key held by `TestWallet::new()`.
+pub const TEST_SECRET: [u8; 32] = [1u8; 32];
+
+/// The txid of the synthetic transaction that created every `TestWallet` UTXO.
+pub const TEST_FUNDING_TXID: [u8; 32] = [0x11; 32];
+
+/// A mainnet P2WPKH address that no `TestWallet` controls, for paying outputs in tests.
+pub fn test_address() -> Address {
+    Address::Wpkh("bc1qvyyvsdcd0t9863stt7u9rf37wx443lzasg0usy".to_owned())
+}
+
+/// A wallet holding a single fixed private key, which receives to and spends from a P2WPKH
+/// script pubkey.
+#[derive(Debug, Clone)]
+pub struct TestWallet {
+    key: XPriv,
+}
+
+impl Default for TestWallet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestWallet {
+    /// Instantiate a wallet holding `TEST_SECRET`.
+    pub fn new() -> Self {
+        Self::from_secret(TEST_SECRET)
+    }
+
+    /// Instantiate a wallet holding `secret`. Panics if the secret is not a valid private key.
+    pub fn from_secret(secret: [u8; 32]) -> Self {
+        let key = XPriv::new(
+            SigningKey::from_bytes(&secret.into()).expect("valid secret key"),
+            XKeyInfo {
+                depth: 0,
+                parent: KeyFingerprint([0u8; 4]),
+                index: 0,
+                chain_code: ChainCode([0u8; 32]),
+                hint: Hint::SegWit,
+            },
+        );
+        Self { key }
+    }
+
+    /// The wallet's private key.
+    pub fn key(&self) -> &XPriv {
+        &self.key
+    }
+
+    /// The wallet's P2WPKH script pubkey.
+    pub fn script_pubkey(&self) -> ScriptPubkey {
+        ScriptPubkey::p2wpkh(&self.key.verify_key())
+    }
+
+    /// A synthetic UTXO of `value` paying the wallet, at output `idx` of the transaction
+    /// `TEST_FUNDING_TXID`.
+    pub fn utxo(&self, idx: u32, value: u64) -> Utxo {
+        Utxo::new(
+            BitcoinOutpoint::new(TEST_FUNDING_TXID.into(), idx),
+            value,
+            self.script_pubkey(),
+            SpendScript::None,
+        )
+    }
+
+    /// Sign every input of `tx` spending one of `utxos`, using `SIGHASH_ALL`. `utxos` must
+    /// contain the UTXO spent by each input, in input order. Panics otherwise.
+    pub fn sign(&self, tx: &mut WitnessTx, utxos: &[Utxo]) {
+        let prevouts = utxos
+            .iter()
+            .map(|utxo| TxOut::new(utxo.value, utxo.script_pubkey.clone()))
+            .collect::<Vec<_>>();
+        let spk = self.script_pubkey();
+        tx.sign_all(&prevouts, |script_pubkey| {
+            if script_pubkey == &spk {
+                Some(&self.key)
+            } else {
+                None
+            }
+        })
+        .expect("prevouts match inputs");
+    }
+
+    /// A fully signed version 2 transaction with one input and one output. It spends a
+    /// synthetic UTXO of `value` at output 0 of `TEST_FUNDING_TXID`, and pays `value - fee` back
+    /// to the wallet. The input signals RBF.
+    pub fn signed_tx(&self, value: u64, fee: u64) -> WitnessTx {
+        let utxo = self.utxo(0, value);
+        let vin = vec![BitcoinTxIn::new(utxo.outpoint, vec![], 0xffff_fffd)];
+        let vout = vec![TxOut::new(value - fee, self.script_pubkey())];
+        let mut tx = <WitnessTx as Transaction>::new(2, vin, vout, 0).expect("valid tx");
+        self.sign(&mut tx, &[utxo]);
+        tx
+    }
+}
@@ -0,0 +1,420 @@
+//! Block headers, merkle roots, and helpers for compact block relay.
+
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+};
+
+use coins_core::{
+    hashes::{Digest, Hash256, Hash256Digest, MarkedDigest, MarkedDigestOutput, Sha256},
+    ser::{self, ByteFormat, SerError, SerResult},
+};
+
+use crate::hashes::BlockHash;
+
+/// An 80-byte Bitcoin block header.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BlockHeader {
+    /// The block version
+    pub version: u32,
+    /// The hash of the previous block header
+    pub prev_block: BlockHash,
+    /// The merkle root of the block's transactions
+    pub merkle_root: Hash256Digest,
+    /// The block timestamp
+    pub time: u32,
+    /// The compact proof of work target
+    pub bits: u32,
+    /// The proof of work nonce
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    /// The big-endian proof of work target encoded by `bits`. `None` if the encoding is invalid.
+    /// See `compact_to_target`.
+    pub fn target(&self) -> Option<[u8; 32]> {
+        compact_to_target(self.bits)
+    }
+
+    /// The difficulty of the header's target. See `difficulty`.
+    pub fn difficulty(&self) -> f64 {
+        difficulty(self.bits)
+    }
+
+    /// Calculate the block hash. This is the double-sha256 of the serialized header.
+    pub fn block_hash(&self) -> BlockHash {
+        let mut w = Hash256::default();
+        self.write_to(&mut w)
+            .expect("No IOError from hash functions");
+        w.finalize_marked()
+    }
+}
+
+impl ByteFormat for BlockHeader {
+    type Error = SerError;
+
+    fn serialized_length(&self) -> usize {
+        80
+    }
+
+    fn read_from<T>(reader: &mut T) -> SerResult<Self>
+    where
+        T: Read,
+        Self: std::marker::Sized,
+    {
+        Ok(BlockHeader {
+            version: ser::read_u32_le(reader)?,
+            prev_block: BlockHash::read_from(reader)?,
+            merkle_root: Hash256Digest::read_from(reader)?,
+            time: ser::read_u32_le(reader)?,
+            bits: ser::read_u32_le(reader)?,
+            nonce: ser::read_u32_le(reader)?,
+        })
+    }
+
+    fn write_to<T>(&self, writer: &mut T) -> SerResult<usize>
+    where
+        T: Write,
+    {
+        let mut len = ser::write_u32_le(writer, self.version)?;
+        len += self.prev_block.write_to(writer)?;
+        len += self.merkle_root.write_to(writer)?;
+        len += ser::write_u32_le(writer, self.time)?;
+        len += ser::write_u32_le(writer, self.bits)?;
+        len += ser::write_u32_le(writer, self.nonce)?;
+        Ok(len)
+    }
+}
+
+/// Expand a compact `nBits` encoding into a big-endian 256-bit target. The top byte of `bits`
+/// is the target's length in bytes, and the low 3 bytes are its most significant bytes. This
+/// matches Bitcoin Core's `arith_uint256::SetCompact`.
+///
+/// Returns `None` if the encoding sets the sign bit (`0x0080_0000`) with a non-zero mantissa,
+/// or if the target does not fit in 256 bits. A zero mantissa encodes the zero target, which no
+/// block hash can meet.
+pub fn compact_to_target(bits: u32) -> Option<[u8; 32]> {
+    let size = (bits >> 24) as isize;
+    let mantissa = bits & 0x007f_ffff;
+    if mantissa != 0 && bits & 0x0080_0000 != 0 {
+        return None;
+    }
+
+    let mut target = [0u8; 32];
+    for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+        let position = 32 - size + i as isize;
+        if position < 0 {
+            if *byte != 0 {
+                return None;
+            }
+        } else if position < 32 {
+            target[position as usize] = *byte;
+        }
+    }
+    Some(target)
+}
+
+/// Encode a big-endian 256-bit target in the compact `nBits` format. This matches Bitcoin
+/// Core's `arith_uint256::GetCompact`. Precision beyond the 3 most significant bytes is lost.
+pub fn target_to_compact(target: &[u8; 32]) -> u32 {
+    let start = match target.iter().position(|byte| *byte != 0) {
+        Some(start) => start,
+        None => return 0,
+    };
+    let mut size = (32 - start) as u32;
+    let mut mantissa = (0..3)
+        .map(|i| *target.get(start + i).unwrap_or(&0) as u32)
+        .fold(0, |acc, byte| (acc << 8) | byte);
+    // The mantissa is signed. If its high bit is set, shift it into the next byte.
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    (size << 24) | mantissa
+}
+
+/// The compact encoding of the mainnet genesis target, which has difficulty 1.
+pub const GENESIS_BITS: u32 = 0x1d00_ffff;
+
+/// The difficulty of `bits`: the genesis target divided by the target `bits` encodes. Invalid
+/// encodings, and the zero target, can't be met by any block hash, so their difficulty is
+/// infinite.
+pub fn difficulty(bits: u32) -> f64 {
+    let as_f64 = |target: [u8; 32]| {
+        target
+            .iter()
+            .fold(0f64, |acc, byte| acc * 256.0 + *byte as f64)
+    };
+    let max_target = as_f64(compact_to_target(GENESIS_BITS).expect("valid genesis bits"));
+    match compact_to_target(bits).map(as_f64) {
+        Some(target) if target > 0.0 => max_target / target,
+        _ => f64::INFINITY,
+    }
+}
+
+/// Calculate the Bitcoin merkle root of `leaves`. Each level pairs adjacent nodes and hashes
+/// them with double-sha256, duplicating the last node of levels with an odd number of nodes.
+/// Returns the all-zero digest if there are no leaves.
+pub fn merkle_root(leaves: &[Hash256Digest]) -> Hash256Digest {
+    if leaves.is_empty() {
+        return Hash256Digest::default();
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut preimage = pair[0].as_slice().to_vec();
+                preimage.extend(pair.get(1).unwrap_or(&pair[0]).as_slice());
+                Hash256::digest_marked(&preimage)
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Calculate the BIP141 witness merkle root of a block from the wtxids of its transactions, in
+/// block order. The coinbase wtxid is replaced with the all-zero digest, so the first element of
+/// `wtxids` is ignored.
+pub fn witness_merkle_root(wtxids: &[Hash256Digest]) -> Hash256Digest {
+    let mut leaves = wtxids.to_vec();
+    if let Some(coinbase) = leaves.first_mut() {
+        *coinbase = Hash256Digest::default();
+    }
+    merkle_root(&leaves)
+}
+
+/// Calculate the BIP141 witness commitment included in the coinbase. This is
+/// `sha256d(witness_root || witness_reserved_value)`, where the reserved value is the single item
+/// in the coinbase input's witness.
+pub fn witness_commitment(
+    witness_root: &Hash256Digest,
+    witness_reserved_value: &[u8; 32],
+) -> Hash256Digest {
+    let mut preimage = witness_root.as_slice().to_vec();
+    preimage.extend(witness_reserved_value);
+    Hash256::digest_marked(&preimage)
+}
+
+/// SipHash-2-4 of `data` under the key `(k0, k1)`.
+fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+
+    let chunks = data.chunks_exact(8);
+    let mut last = [0u8; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+
+    for chunk in chunks.map(|c| c.try_into().unwrap()).chain(Some(last)) {
+        let m = u64::from_le_bytes(chunk);
+        v[3] ^= m;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= m;
+    }
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+/// Calculate the BIP152 short transaction ID of `wtxid` for a compact block with `header` and
+/// `nonce`. The SipHash keys are the first two little-endian u64s of
+/// `sha256(header || nonce)`, and the short ID is the low 6 bytes of the SipHash-2-4 of the
+/// wtxid.
+pub fn short_txid(wtxid: &Hash256Digest, header: &BlockHeader, nonce: u64) -> [u8; 6] {
+    let mut preimage = Vec::with_capacity(88);
+    header
+        .write_to(&mut preimage)
+        .expect("No error on heap write");
+    preimage.extend(nonce.to_le_bytes());
+    let key = Sha256::digest(&preimage);
+
+    let mut k0 = [0u8; 8];
+    let mut k1 = [0u8; 8];
+    k0.copy_from_slice(&key[..8]);
+    k1.copy_from_slice(&key[8..16]);
+
+    let hash = siphash24(
+        u64::from_le_bytes(k0),
+        u64::from_le_bytes(k1),
+        wtxid.as_ref(),
+    );
+    let mut short_id = [0u8; 6];
+    short_id.copy_from_slice(&hash.to_le_bytes()[..6]);
+    short_id
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{BitcoinTransaction, WitnessTransaction, WitnessTx};
+    use coins_core::types::tx::Transaction;
+    use std::convert::TryInto;
+
+    // The mainnet genesis block header
+    static GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    #[test]
+    fn it_serializes_and_hashes_headers() {
+        let header = BlockHeader::deserialize_hex(GENESIS).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.bits, 0x1d00ffff);
+        assert_eq!(header.nonce, 2083236893);
+        assert_eq!(header.serialize_hex(), GENESIS);
+        assert_eq!(
+            header.block_hash().to_be_hex(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+
+    #[test]
+    fn it_calculates_short_txids() {
+        // SipHash-2-4 reference vectors, from the SipHash paper
+        let key = (0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908);
+        assert_eq!(siphash24(key.0, key.1, &[]), 0x726f_db47_dd0e_0e31);
+        let data = (0u8..15).collect::<Vec<_>>();
+        assert_eq!(siphash24(key.0, key.1, &data), 0xa129_ca61_49be_45e5);
+
+        // BIP152 keys SipHash with the first 16 bytes of sha256(header || nonce), and keeps
+        // the low 6 bytes of the hash
+        let header = BlockHeader::deserialize_hex(GENESIS).unwrap();
+        let wtxid = header.merkle_root;
+        let nonce = 0x1122_3344_5566_7788u64;
+        let mut preimage = hex::decode(GENESIS).unwrap();
+        preimage.extend(nonce.to_le_bytes());
+        let key = Sha256::digest(&preimage);
+        let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+        let k1 = u64::from_le_bytes(key[8..16].try_into().unwrap());
+        let expected = siphash24(k0, k1, wtxid.as_slice()).to_le_bytes();
+        assert_eq!(short_txid(&wtxid, &header, nonce)[..], expected[..6]);
+        assert_eq!(
+            hex::encode(short_txid(&wtxid, &header, nonce)),
+            "a8eb7d72754a"
+        );
+    }
+
+    #[test]
+    fn it_calculates_witness_commitments() {
+        // The transactions of regtest block
+        // 38c34a52ad8b8a937ca861a2991372eb9b6a0c9b43a71ba4acba3959f570f816, from the BIP152
+        // compact block vector that rust-bitcoin tests against the Elements implementation
+        let coinbase = WitnessTx::deserialize_hex("020000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff04016b0101ffffffff020006062a0100000001510000000000000000266a24aa21a9ed4a3d9f3343dafcc0d6f6d4310f2ee5ce273ed34edca6c75db3a73e7f368734200120000000000000000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let spend = WitnessTx::deserialize_hex("020000000001021fc20ba2bd745507b8e00679e3b362558f9457db374ca28ffa5243f4c23a4d5f00000000171600147c9dea14ffbcaec4b575e03f05ceb7a81cd3fcbffdffffff915d689be87b43337f42e26033df59807b768223368f189a023d0242d837768900000000171600147c9dea14ffbcaec4b575e03f05ceb7a81cd3fcbffdffffff0200cdf5050000000017a9146803c72d9154a6a20f404bed6d3dcee07986235a8700e1f5050000000017a9144e6a4c7cb5b5562904843bdf816342f4db9f5797870247304402205e9bf6e70eb0e4b495bf483fd8e6e02da64900f290ef8aaa64bb32600d973c450220670896f5d0e5f33473e5f399ab680cc1d25c2d2afd15abd722f04978f28be887012103e4e4d9312b2261af508b367d8ba9be4f01b61d6d6e78bec499845b4f410bcf2702473044022045ac80596a6ac9c8c572f94708709adaf106677221122e08daf8b9741a04f66a022003ccd52a3b78f8fd08058fc04fc0cffa5f4c196c84eae9e37e2a85babe731b57012103e4e4d9312b2261af508b367d8ba9be4f01b61d6d6e78bec499845b4f410bcf276a000000").unwrap();
+        let wtxids = [coinbase.wtxid(), spend.wtxid()]
+            .iter()
+            .map(|wtxid| Hash256Digest::from(wtxid.to_internal()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            wtxids[1].to_be_hex(),
+            "3c8d7eff789992c75db9542a53d19b6ede7d844dea42c2a0782c3e3694c7bace"
+        );
+
+        // The coinbase wtxid is replaced with zeros
+        let root = witness_merkle_root(&wtxids);
+        let mut preimage = vec![0u8; 32];
+        preimage.extend(wtxids[1].as_slice());
+        assert_eq!(root, Hash256::digest_marked(&preimage));
+
+        // The reserved value is the coinbase witness, and the commitment is pushed by the
+        // coinbase's `6a24aa21a9ed` output
+        let reserved_value: [u8; 32] = coinbase.witnesses()[0][0].items().try_into().unwrap();
+        let commitment = witness_commitment(&root, &reserved_value);
+        let mut script_pubkey = hex::decode("6a24aa21a9ed").unwrap();
+        script_pubkey.extend(commitment.as_slice());
+        assert_eq!(
+            coinbase.outputs()[1].script_pubkey.items(),
+            &script_pubkey[..]
+        );
+
+        // The genesis block's merkle root is its only txid
+        let header = BlockHeader::deserialize_hex(GENESIS).unwrap();
+        assert_eq!(merkle_root(&[header.merkle_root]), header.merkle_root);
+        assert_eq!(merkle_root(&[]), Hash256Digest::default());
+    }
+
+    /// A big-endian target from the hex of its significant bytes
+    fn target(significant: &str) -> [u8; 32] {
+        let bytes = hex::decode(significant).unwrap();
+        let mut target = [0u8; 32];
+        target[32 - bytes.len()..].copy_from_slice(&bytes);
+        target
+    }
+
+    #[test]
+    fn it_converts_compact_targets() {
+        let header = BlockHeader::deserialize_hex(GENESIS).unwrap();
+        let genesis_target = target(&format!("ffff{}", "00".repeat(26)));
+        assert_eq!(header.target(), Some(genesis_target));
+        assert_eq!(target_to_compact(&genesis_target), 0x1d00ffff);
+
+        // Edge cases from Bitcoin Core's arith_uint256 tests. Each case is the bits, the target,
+        // and the re-encoded bits.
+        let cases = [
+            (0x0000_0000, "", 0),
+            (0x0112_3456, "12", 0x0112_0000),
+            (0x0200_8000, "80", 0x0200_8000),
+            (0x0500_9234, "92340000", 0x0500_9234),
+            (
+                0x2012_3456,
+                &format!("123456{}", "00".repeat(29)),
+                0x2012_3456,
+            ),
+            // a small size truncates the mantissa to the zero target
+            (0x0100_3456, "", 0),
+            // a zero mantissa is the zero target, even with the sign bit set
+            (0x0180_0000, "", 0),
+        ];
+        for (bits, significant, compact) in cases.iter() {
+            assert_eq!(compact_to_target(*bits), Some(target(significant)));
+            assert_eq!(target_to_compact(&target(significant)), *compact);
+        }
+
+        // the sign bit is set
+        assert_eq!(compact_to_target(0x0492_3456), None);
+        assert_eq!(compact_to_target(0x01fe_dcba), None);
+        // the target overflows 256 bits
+        assert_eq!(compact_to_target(0xff12_3456), None);
+        assert_eq!(compact_to_target(0x2112_3456), None);
+        // but not if the overflowing bytes are 0
+        assert_eq!(
+            compact_to_target(0x2100_3456),
+            Some(target(&format!("3456{}", "00".repeat(30))))
+        );
+    }
+
+    #[test]
+    fn it_calculates_difficulty() {
+        let header = BlockHeader::deserialize_hex(GENESIS).unwrap();
+        assert_eq!(header.difficulty(), 1.0);
+
+        // mainnet block 100,000
+        let expected = 14_484.162_361_225_399;
+        assert!((difficulty(0x1b04_864c) - expected).abs() < 1e-6);
+
+        // halving the target doubles the difficulty
+        assert!((difficulty(0x1c7f_ff80) - 2.0).abs() < 1e-12);
+
+        assert_eq!(difficulty(0x1d00_0000), f64::INFINITY);
+        assert_eq!(difficulty(0x1d80_ffff), f64::INFINITY);
+    }
+}
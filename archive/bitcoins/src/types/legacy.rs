@@ -8,11 +8,11 @@ use coins_core::{
 };
 
 use crate::{
-    hashes::TXID,
+    hashes::{TXID, WTXID},
     types::{
-        script::{Script, ScriptSig, Witness},
+        script::{strip_code_separators, Script, ScriptSig, Witness},
         tx::*,
-        txin::{BitcoinTxIn, Vin},
+        txin::{BitcoinOutpoint, BitcoinTxIn, Vin},
         txout::{TxOut, Vout},
         witness::*,
     },
@@ -86,11 +86,31 @@ pub struct LegacyTx {
 }
 
 impl LegacyTx {
+    /// The witness txid of the transaction. Legacy transactions have no witnesses, so this is
+    /// equal to the txid.
+    pub fn wtxid(&self) -> WTXID {
+        let mut w = Hash256::default();
+        self.write_to(&mut w).expect("No IOError from SHA2");
+        w.finalize_marked()
+    }
+
+    /// Repoint the input at `index` to spend `outpoint`. Errors if the input carries a script
+    /// sig, as changing the outpoint would invalidate any signature it contains.
+    pub fn set_input_outpoint(&mut self, index: usize, outpoint: BitcoinOutpoint) -> TxResult<()> {
+        let input = self.vin.get_mut(index).ok_or(TxError::NoSuchInput(index))?;
+        if !input.script_sig.is_empty() {
+            return Err(TxError::InputAlreadySigned(index));
+        }
+        input.outpoint = outpoint;
+        Ok(())
+    }
+
     /// Performs steps 6, 7, and 8 of the sighash setup described here:
     /// https://en.bitcoin.it/wiki/OP_CHECKSIG#How_it_works
     /// https://bitcoin.stackexchange.com/questions/3374/how-to-redeem-a-basic-tx
     ///
-    /// OP_CODESEPARATOR functionality is NOT provided here.
+    /// `OP_CODESEPARATOR` opcodes are removed from the script code. Selecting the subset of the
+    /// script following the last executed `OP_CODESEPARATOR` is left to the caller.
     ///
     /// TODO: memoize
     fn legacy_sighash_prep(&self, index: usize, prevout_script: &Script) -> Self {
@@ -98,7 +118,7 @@ impl LegacyTx {
 
         for i in 0..copy_tx.vin.len() {
             copy_tx.vin[i].script_sig = if i == index {
-                ScriptSig::from(prevout_script.items())
+                ScriptSig::from(strip_code_separators(prevout_script.items()))
             } else {
                 ScriptSig::null()
             };
@@ -129,6 +149,21 @@ impl LegacyTx {
         copy_tx.vin = vin;
     }
 
+    /// Modifies copy_tx according to legacy SIGHASH_NONE semantics. No outputs are committed to,
+    /// and other inputs' sequences are zeroed so that they may be replaced.
+    ///
+    /// For Legacy sighash documentation, see here:
+    ///
+    /// - https://en.bitcoin.it/wiki/OP_CHECKSIG#Hashtype_SIGHASH_ALL_.28default.29
+    fn legacy_sighash_none(copy_tx: &mut Self, index: usize) {
+        copy_tx.vout = vec![];
+        for (i, txin) in copy_tx.vin.iter_mut().enumerate() {
+            if i != index {
+                txin.sequence = 0;
+            }
+        }
+    }
+
     /// Modifies copy_tx according to legacy SIGHASH_ANYONECANPAY semantics.
     ///
     /// For Legacy sighash documentation, see here:
@@ -188,16 +223,22 @@ impl Transaction for LegacyTx {
         self.locktime
     }
 
+    /// Writes the legacy sighash preimage. Errors with `SighashSingleBug` if the flag is
+    /// SIGHASH_SINGLE and the input has no matching output, as there is no preimage. In that
+    /// case, `sighash` returns the digest that consensus requires.
     fn write_sighash_preimage<W: Write>(
         &self,
         writer: &mut W,
         args: &LegacySighashArgs,
     ) -> TxResult<()> {
-        if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
-            return Err(TxError::NoneUnsupported);
+        if args.index >= self.inputs().len() {
+            return Err(TxError::NoSuchInput(args.index));
         }
 
         let mut copy_tx: Self = self.legacy_sighash_prep(args.index, &args.prevout_script);
+        if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
+            Self::legacy_sighash_none(&mut copy_tx, args.index);
+        }
         if args.sighash_flag == Sighash::Single || args.sighash_flag == Sighash::SingleAcp {
             if args.index >= self.outputs().len() {
                 return Err(TxError::SighashSingleBug);
@@ -214,6 +255,23 @@ impl Transaction for LegacyTx {
 
         Ok(())
     }
+
+    /// Calculates the legacy sighash. If the flag is SIGHASH_SINGLE and the input has no matching
+    /// output, this reproduces Satoshi's sighash single bug, and returns the digest
+    /// `0x01 00..00`, which consensus requires signatures to commit to.
+    fn sighash(&self, args: &LegacySighashArgs) -> TxResult<DigestOutput<Hash256>> {
+        if (args.sighash_flag == Sighash::Single || args.sighash_flag == Sighash::SingleAcp)
+            && args.index < self.inputs().len()
+            && args.index >= self.outputs().len()
+        {
+            let mut digest = DigestOutput::<Hash256>::default();
+            digest[0] = 1;
+            return Ok(digest);
+        }
+        let mut w = Hash256::default();
+        self.write_sighash_preimage(&mut w, args)?;
+        Ok(w.finalize())
+    }
 }
 
 impl BitcoinTransaction for LegacyTx {
@@ -277,8 +335,8 @@ impl ByteFormat for LegacyTx {
         W: Write,
     {
         let mut len = coins_core::ser::write_u32_le(writer, self.version())?;
-        ser::write_prefix_vec(writer, &self.vin)?;
-        ser::write_prefix_vec(writer, &self.vout)?;
+        len += ser::write_prefix_vec(writer, &self.vin)?;
+        len += ser::write_prefix_vec(writer, &self.vout)?;
         len += coins_core::ser::write_u32_le(writer, self.locktime())?;
         Ok(len)
     }
@@ -2,16 +2,22 @@
 //! Extends the `Transaction` trait to maintain a type distinction between Legacy and Witness
 //! transactions (and allow conversion from one to the other).
 
+pub mod block;
 pub mod legacy;
+pub mod record;
 pub mod script;
+pub mod taproot;
 pub mod tx;
 pub mod txin;
 pub mod txout;
 pub mod utxo;
 pub mod witness;
 
+pub use block::*;
 pub use legacy::*;
+pub use record::*;
 pub use script::*;
+pub use taproot::*;
 pub use tx::*;
 pub use txin::*;
 pub use txout::*;
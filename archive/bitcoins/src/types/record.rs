@@ -0,0 +1,105 @@
+//! Wallet records, attaching metadata to a transaction without modifying it.
+
+use coins_core::types::tx::Transaction;
+
+use crate::{
+    hashes::TXID,
+    types::{
+        tx::{BitcoinTransaction, BitcoinTx},
+        txout::TxOut,
+    },
+};
+
+/// A transaction, and wallet metadata about it. The metadata is not part of the transaction, and
+/// is not committed to by its txid.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct TxRecord {
+    /// The transaction
+    pub tx: BitcoinTx,
+    /// A user-assigned label
+    pub label: Option<String>,
+    /// The height of the block confirming the transaction, if any
+    pub height: Option<u32>,
+    /// A unix timestamp, e.g. when the transaction was first seen
+    pub timestamp: Option<u64>,
+}
+
+impl From<BitcoinTx> for TxRecord {
+    fn from(tx: BitcoinTx) -> Self {
+        Self {
+            tx,
+            label: None,
+            height: None,
+            timestamp: None,
+        }
+    }
+}
+
+impl TxRecord {
+    /// Return a copy of the record with the label set to `label`.
+    pub fn labeled(mut self, label: &str) -> Self {
+        self.label = Some(label.to_owned());
+        self
+    }
+
+    /// `true` if the record has a confirmation height.
+    pub fn is_confirmed(&self) -> bool {
+        self.height.is_some()
+    }
+
+    /// The txid of the inner transaction.
+    pub fn txid(&self) -> TXID {
+        self.tx.txid()
+    }
+
+    /// The fee paid by the inner transaction. `prevouts` must contain the output spent by each
+    /// input, in input order. Returns `None` if the number of prevouts does not match the number
+    /// of inputs, or if the outputs exceed the inputs.
+    pub fn fee(&self, prevouts: &[TxOut]) -> Option<u64> {
+        if prevouts.len() != self.tx.inputs().len() {
+            return None;
+        }
+        let input_value = prevouts.iter().map(|p| p.value).sum::<u64>();
+        let output_value = self.tx.outputs().iter().map(|o| o.value).sum::<u64>();
+        input_value.checked_sub(output_value)
+    }
+
+    /// The virtual size of the inner transaction, per BIP141.
+    pub fn vsize(&self) -> usize {
+        self.tx.vsize()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::ScriptPubkey;
+    use coins_core::{hashes::MarkedDigestOutput, ser::ByteFormat};
+
+    #[test]
+    fn it_serializes_records() {
+        // A mainnet segwit transaction
+        let tx_hex = "02000000000101ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f187024730440220645d5b09a7a00581bad644d36521fa1dc6cd12763ca45f10893cb2e80c1d4d720220505f1f55b9fe2aeba40f68c52c39dcb38cf4be41ec99273a23bdfe58119f7d30012103ba797be9acc7bde9ed4aef2ff6409e4c44559dd127f3e0c03ee2a1da14d9f6b100000000";
+        let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+        let txid = tx.txid();
+
+        let mut record = TxRecord::from(tx).labeled("rent");
+        record.height = Some(650_000);
+
+        let json = serde_json::to_string(&record).unwrap();
+        let parsed: TxRecord = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, record);
+        assert_eq!(parsed.txid(), txid);
+        assert_eq!(parsed.label.as_deref(), Some("rent"));
+        assert!(parsed.is_confirmed());
+        assert_eq!(
+            parsed.txid().to_be_hex(),
+            "b7820d360ee55358dc828f7b540981240906b33db6ae598c5d4e507928df865e"
+        );
+        assert_eq!(parsed.vsize(), 143);
+
+        let prevout = TxOut::new(240_000, ScriptPubkey::null());
+        assert_eq!(parsed.fee(&[prevout]), Some(666));
+        assert_eq!(parsed.fee(&[]), None);
+    }
+}
@@ -23,9 +23,13 @@
 use coins_core::{
     hashes::{Digest, Hash160, Hash160Digest, Hash256Digest, MarkedDigestOutput, Sha256},
     impl_hex_serde, impl_script_conversion,
+    ser::ByteFormat,
     types::tx::RecipientIdentifier,
     wrap_prefixed_byte_vector,
 };
+use thiserror::Error;
+
+use crate::types::taproot::XOnlyPubkey;
 
 /// A wrapped script.
 pub trait BitcoinScript {}
@@ -61,6 +65,9 @@ wrap_prefixed_byte_vector!(
     ///
     /// `ScriptPubkey::null()` and `ScriptPubkey::default()` return the empty byte vector with a 0
     /// prefix, which represents numerical 0, boolean `false`, or null bytestring.
+    ///
+    /// ScriptPubkeys are ordered lexicographically by their raw bytes, as required for the
+    /// BIP69 output tiebreak.
     ScriptPubkey
 );
 
@@ -91,13 +98,44 @@ pub type Witness = Vec<WitnessStackItem>;
 /// A TxWitness is the UNPREFIXED vector of witnesses
 pub type TxWitness = Vec<Witness>;
 
+/// Functions for sizing a witness for weight calculations.
+pub trait WitnessSize {
+    /// The serialized size of the witness in bytes: the compact-size item count, followed by
+    /// each item's length prefix and data. An empty witness serializes to 1 byte.
+    fn serialized_size(&self) -> usize;
+}
+
+impl WitnessSize for Witness {
+    fn serialized_size(&self) -> usize {
+        coins_core::ser::prefix_byte_len(self.len() as u64) as usize
+            + self
+                .iter()
+                .map(|item| item.serialized_length())
+                .sum::<usize>()
+    }
+}
+
 impl ScriptPubkey {
     /// Instantiate a standard p2pkh script pubkey from a pubkey.
     pub fn p2pkh<K>(key: &K) -> Self
     where
         K: AsRef<coins_bip32::k256::ecdsa::VerifyingKey>,
     {
-        let digest = Hash160::digest(&key.as_ref().to_sec1_bytes());
+        let digest = Hash160::digest(key.as_ref().to_sec1_bytes());
+
+        let mut v: Vec<u8> = vec![0x76, 0xa9, 0x14]; // DUP, HASH160, PUSH_20
+        v.extend(&digest);
+        v.extend(&[0x88, 0xac]); // EQUALVERIFY, CHECKSIG
+        v.into()
+    }
+
+    /// Instantiate a p2pkh script pubkey committing to the uncompressed serialization of a
+    /// pubkey. Legacy wallets produced these before compressed keys were standard.
+    pub fn p2pkh_uncompressed<K>(key: &K) -> Self
+    where
+        K: AsRef<coins_bip32::k256::ecdsa::VerifyingKey>,
+    {
+        let digest = Hash160::digest(key.as_ref().to_encoded_point(false).as_bytes());
 
         let mut v: Vec<u8> = vec![0x76, 0xa9, 0x14]; // DUP, HASH160, PUSH_20
         v.extend(&digest);
@@ -110,17 +148,55 @@ impl ScriptPubkey {
     where
         K: AsRef<coins_bip32::k256::ecdsa::VerifyingKey>,
     {
-        let digest = Hash160::digest(&key.as_ref().to_sec1_bytes());
+        let digest = Hash160::digest(key.as_ref().to_sec1_bytes());
 
         let mut v: Vec<u8> = vec![0x00, 0x14]; // OP_0, PUSH_20
         v.extend(&digest);
         v.into()
     }
 
+    /// Instantiate a standard p2tr script pubkey from an output key. Consensus does not require
+    /// the key to be a valid point, but an output to an invalid key is unspendable. If
+    /// `validate`, returns `InvalidTaprootKey` if the key is not a valid x-only point.
+    pub fn p2tr(key: &XOnlyPubkey, validate: bool) -> Result<Self, ScriptError> {
+        if validate && !key.is_valid_point() {
+            return Err(ScriptError::InvalidTaprootKey);
+        }
+        let mut v: Vec<u8> = vec![0x51, 0x20]; // OP_1, PUSH_32
+        v.extend(key.as_ref());
+        Ok(v.into())
+    }
+
+    /// Instantiate a `<locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey> OP_CHECKSIG` script.
+    /// The locktime is minimally encoded as a script number.
+    pub fn cltv_p2pk<K>(locktime: u32, key: &K) -> Self
+    where
+        K: AsRef<coins_bip32::k256::ecdsa::VerifyingKey>,
+    {
+        timelock_p2pk(
+            locktime,
+            OP_CHECKLOCKTIMEVERIFY,
+            &key.as_ref().to_sec1_bytes(),
+        )
+    }
+
+    /// Instantiate a `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey> OP_CHECKSIG` script.
+    /// The sequence is minimally encoded as a script number.
+    pub fn csv_p2pk<K>(sequence: u32, key: &K) -> Self
+    where
+        K: AsRef<coins_bip32::k256::ecdsa::VerifyingKey>,
+    {
+        timelock_p2pk(
+            sequence,
+            OP_CHECKSEQUENCEVERIFY,
+            &key.as_ref().to_sec1_bytes(),
+        )
+    }
+
     /// Instantiate a standard p2sh script pubkey from a script.
     pub fn p2sh(script: &Script) -> Self {
         let mut v: Vec<u8> = vec![0xa9, 0x14]; // HASH160, PUSH_20
-        v.extend(Hash160::digest(script.as_ref()).as_slice());
+        v.extend(Hash160::digest(script.as_ref()));
         v.extend(&[0x87]); // EQUAL
         v.into()
     }
@@ -144,12 +220,59 @@ pub enum ScriptType {
     Wpkh(Hash160Digest),
     /// Pay to Witness Scripthash.
     Wsh(Hash256Digest),
+    /// Pay to Taproot. Contains the x-only output key.
+    Tr(XOnlyPubkey),
     /// OP_RETURN
     OpReturn(Vec<u8>),
+    /// `<locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey> OP_CHECKSIG`. Contains the locktime and
+    /// the compressed pubkey.
+    CltvP2pk(u32, [u8; 33]),
+    /// `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey> OP_CHECKSIG`. Contains the relative
+    /// locktime sequence and the compressed pubkey.
+    CsvP2pk(u32, [u8; 33]),
     /// Nonstandard or unknown `Script` type. May be a newer witness version.
     NonStandard,
 }
 
+/// A standard output script, typed by its committed payload. Unlike `ScriptType`, this
+/// recognizes Taproot outputs, and OP_RETURN outputs with any number of data pushes.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum StandardOutput {
+    /// Pay to Pubkeyhash.
+    P2pkh(Hash160Digest),
+    /// Pay to Scripthash.
+    P2sh(Hash160Digest),
+    /// Pay to Witness Pubkeyhash.
+    P2wpkh(Hash160Digest),
+    /// Pay to Witness Scripthash. The payload is the single sha256 of the witness script.
+    P2wsh(Hash256Digest),
+    /// Pay to Taproot.
+    P2tr(XOnlyPubkey),
+    /// OP_RETURN followed by data pushes.
+    OpReturn(Vec<Vec<u8>>),
+}
+
+impl std::convert::TryFrom<&ScriptPubkey> for StandardOutput {
+    type Error = ScriptError;
+
+    fn try_from(script: &ScriptPubkey) -> Result<Self, ScriptError> {
+        let items = script.items();
+        if items.first() == Some(&0x6a) {
+            return parse_pushes(&items[1..])
+                .map(StandardOutput::OpReturn)
+                .ok_or(ScriptError::NonStandard);
+        }
+        match script.standard_type() {
+            ScriptType::Pkh(hash) => Ok(StandardOutput::P2pkh(hash)),
+            ScriptType::Sh(hash) => Ok(StandardOutput::P2sh(hash)),
+            ScriptType::Wpkh(hash) => Ok(StandardOutput::P2wpkh(hash)),
+            ScriptType::Wsh(hash) => Ok(StandardOutput::P2wsh(hash)),
+            ScriptType::Tr(key) => Ok(StandardOutput::P2tr(key)),
+            _ => Err(ScriptError::NonStandard),
+        }
+    }
+}
+
 impl ScriptPubkey {
     /// Extract the op return payload. None if not an op return. Does not extract OP_RETURN blobs
     /// larger than 75 bytes.
@@ -165,11 +288,41 @@ impl ScriptPubkey {
         None
     }
 
+    /// True if the script is a witness program of any version, as defined in BIP141. I.e. a
+    /// version opcode followed by a single 2-40 byte push.
+    pub fn is_witness_program(&self) -> bool {
+        let len = self.len();
+        if !(4..=42).contains(&len) {
+            return false;
+        }
+        (self[0] == 0x00 || (0x51..=0x60).contains(&self[0])) && self[1] as usize == len - 2
+    }
+
+    /// Return the commitment of a standard output script: the hash160 of a P2PKH, P2SH or
+    /// P2WPKH output, the sha256 of a P2WSH output, or the output key of a P2TR output. `None`
+    /// for all other scripts.
+    pub fn committed_hash(&self) -> Option<Vec<u8>> {
+        match self.standard_type() {
+            ScriptType::Pkh(hash) | ScriptType::Sh(hash) | ScriptType::Wpkh(hash) => {
+                Some(hash.as_slice().to_vec())
+            }
+            ScriptType::Wsh(hash) => Some(hash.as_slice().to_vec()),
+            ScriptType::Tr(key) => Some(key.as_ref().to_vec()),
+            _ => None,
+        }
+    }
+
     /// Inspect the `Script` to determine its type.
     pub fn standard_type(&self) -> ScriptType {
         if let Some(data) = self.extract_op_return_data() {
             return ScriptType::OpReturn(data);
         }
+        if let Some((value, opcode, pubkey)) = parse_timelock_p2pk(self.items()) {
+            return match opcode {
+                OP_CHECKLOCKTIMEVERIFY => ScriptType::CltvP2pk(value, pubkey),
+                _ => ScriptType::CsvP2pk(value, pubkey),
+            };
+        }
 
         let items = &self.0;
         match self.0.len() {
@@ -203,6 +356,11 @@ impl ScriptPubkey {
                     buf.as_mut_slice().copy_from_slice(&items[2..34]);
                     return ScriptType::Wsh(buf);
                 }
+                if items[0..2] == [0x51, 0x20] {
+                    let mut key = [0u8; 32];
+                    key.copy_from_slice(&items[2..34]);
+                    return ScriptType::Tr(key.into());
+                }
             }
             _ => return ScriptType::NonStandard,
         }
@@ -211,11 +369,350 @@ impl ScriptPubkey {
     }
 }
 
+impl Script {
+    /// If the script is a standard `OP_M <pubkey>... OP_N OP_CHECKMULTISIG` script, return `m`
+    /// and `n`.
+    pub fn multisig_params(&self) -> Option<(usize, usize)> {
+        let items = self.items();
+        if items.len() < 3 || items[items.len() - 1] != 0xae {
+            return None;
+        }
+        let m = small_int(items[0])?;
+        let n = small_int(items[items.len() - 2])?;
+        let keys = parse_pushes(&items[1..items.len() - 2])?;
+        if m == 0 || m > n || keys.len() != n || keys.iter().any(|k| k.len() != 33 && k.len() != 65)
+        {
+            return None;
+        }
+        Some((m, n))
+    }
+}
+
+/// Decode an `OP_1` through `OP_16` opcode.
+fn small_int(opcode: u8) -> Option<usize> {
+    match opcode {
+        0x51..=0x60 => Some((opcode - 0x50) as usize),
+        _ => None,
+    }
+}
+
+impl ScriptSig {
+    /// If the script sig matches the P2PKH `<sig> <pubkey>` template, return the signature,
+    /// including its trailing sighash flag, and the pubkey.
+    pub fn parse_p2pkh(&self) -> Option<(Vec<u8>, coins_bip32::k256::ecdsa::VerifyingKey)> {
+        let mut pushes = parse_pushes(self.items())?;
+        if pushes.len() != 2 || !is_signature(&pushes[0]) {
+            return None;
+        }
+        let pubkey = coins_bip32::k256::ecdsa::VerifyingKey::from_sec1_bytes(&pushes[1]).ok()?;
+        Some((pushes.swap_remove(0), pubkey))
+    }
+}
+
+/// An Error type for script manipulation
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    /// A data push at this offset runs past the end of the script
+    #[error("Data push at offset {0} runs past the end of the script")]
+    TruncatedPush(usize),
+
+    /// The script is not one of the standard output types
+    #[error("Nonstandard script pubkey")]
+    NonStandard,
+
+    /// The Taproot output key is not the x coordinate of a point on the curve
+    #[error("Taproot output key is not a valid x-only point")]
+    InvalidTaprootKey,
+}
+
+/// Read the data push opcode at `cursor`. Returns `Some((header_length, data_length))` if the
+/// opcode is a data push, and `None` otherwise. The data may run past the end of the script.
+fn push_at(script: &[u8], cursor: usize) -> Option<Result<(usize, usize), ScriptError>> {
+    let truncated = Err(ScriptError::TruncatedPush(cursor));
+    let header = match script[cursor] {
+        opcode @ 0x00..=0x4b => return Some(Ok((1, opcode as usize))),
+        0x4c => script.get(cursor + 1..cursor + 2),
+        0x4d => script.get(cursor + 1..cursor + 3),
+        0x4e => script.get(cursor + 1..cursor + 5),
+        _ => return None,
+    };
+    let header = match header {
+        Some(header) => header,
+        None => return Some(truncated),
+    };
+    let mut buf = [0u8; 4];
+    buf[..header.len()].copy_from_slice(header);
+    Some(Ok((1 + header.len(), u32::from_le_bytes(buf) as usize)))
+}
+
+/// Append `data` to `script` using the minimal push encoding, as required by the
+/// `SCRIPT_VERIFY_MINIMALDATA` policy rule.
+pub(crate) fn push_minimal(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        0 => return script.push(0x00),
+        1 if (1..=16).contains(&data[0]) => return script.push(0x50 + data[0]),
+        1 if data[0] == 0x81 => return script.push(0x4f),
+        len @ 1..=0x4b => script.push(len as u8),
+        len @ 0x4c..=0xff => script.extend([0x4c, len as u8]),
+        len @ 0x100..=0xffff => {
+            script.push(0x4d);
+            script.extend((len as u16).to_le_bytes());
+        }
+        len => {
+            script.push(0x4e);
+            script.extend((len as u32).to_le_bytes());
+        }
+    }
+    script.extend(data);
+}
+
+const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+const OP_CHECKSEQUENCEVERIFY: u8 = 0xb2;
+
+/// Encode a non-negative number as a minimal script number.
+fn script_num(number: u32) -> Vec<u8> {
+    let mut bytes = number.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    // a set high bit would make the number negative
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// Build a `<value> <opcode> OP_DROP <pubkey> OP_CHECKSIG` script.
+fn timelock_p2pk(value: u32, opcode: u8, pubkey: &[u8]) -> ScriptPubkey {
+    let mut v = vec![];
+    push_minimal(&mut v, &script_num(value));
+    v.extend([opcode, 0x75, 0x21]); // CLTV or CSV, DROP, PUSH_33
+    v.extend(pubkey);
+    v.push(0xac); // CHECKSIG
+    v.into()
+}
+
+/// Parse a `<value> <opcode> OP_DROP <pubkey> OP_CHECKSIG` script, where the opcode is CLTV or
+/// CSV. Returns `None` unless the value is a minimally-encoded non-negative u32, and the pubkey
+/// is 33 bytes.
+fn parse_timelock_p2pk(script: &[u8]) -> Option<(u32, u8, [u8; 33])> {
+    let value = match *script.first()? {
+        0x00 => 0,
+        opcode @ 0x51..=0x60 => (opcode - 0x50) as u32,
+        len @ 0x01..=0x05 => {
+            let data = script.get(1..1 + len as usize)?;
+            if data[data.len() - 1] & 0x80 != 0 {
+                return None;
+            }
+            let mut buf = [0u8; 8];
+            buf[..data.len()].copy_from_slice(data);
+            std::convert::TryFrom::try_from(u64::from_le_bytes(buf)).ok()?
+        }
+        _ => return None,
+    };
+
+    let rest = &script[script.len().checked_sub(37)?..];
+    let opcode = rest[0];
+    if !(opcode == OP_CHECKLOCKTIMEVERIFY || opcode == OP_CHECKSEQUENCEVERIFY)
+        || rest[1..3] != [0x75, 0x21]
+        || rest[36] != 0xac
+    {
+        return None;
+    }
+    let mut pubkey = [0u8; 33];
+    pubkey.copy_from_slice(&rest[3..36]);
+
+    // Rebuilding rejects non-minimal encodings and trailing bytes after the number
+    if timelock_p2pk(value, opcode, &pubkey).items() != script {
+        return None;
+    }
+    Some((value, opcode, pubkey))
+}
+
+/// True if the item is plausibly a DER signature with a trailing sighash flag.
+pub(crate) fn is_signature(item: &[u8]) -> bool {
+    (9..=73).contains(&item.len()) && item[0] == 0x30
+}
+
+/// Remove all `OP_CODESEPARATOR` opcodes from a script. Push data is copied unchanged. If a push
+/// runs past the end of the script, the remainder is copied unchanged.
+pub(crate) fn strip_code_separators(script: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(script.len());
+    let mut cursor = 0;
+    while cursor < script.len() {
+        match push_at(script, cursor) {
+            Some(Ok((header, len))) if cursor + header + len <= script.len() => {
+                stripped.extend(&script[cursor..cursor + header + len]);
+                cursor += header + len;
+            }
+            Some(_) => {
+                stripped.extend(&script[cursor..]);
+                break;
+            }
+            None => {
+                if script[cursor] != 0xab {
+                    stripped.push(script[cursor]);
+                }
+                cursor += 1;
+            }
+        }
+    }
+    stripped
+}
+
+/// Split a push-only script into its data pushes. Returns `None` if the script contains any
+/// opcode other than a data push, or if a push runs past the end of the script.
+pub(crate) fn parse_pushes(script: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut pushes = vec![];
+    let mut cursor = 0;
+    while cursor < script.len() {
+        let (header, len) = push_at(script, cursor)?.ok()?;
+        cursor += header;
+        pushes.push(script.get(cursor..cursor + len)?.to_vec());
+        cursor += len;
+    }
+    Some(pushes)
+}
+
+impl ScriptPubkey {
+    /// Rewrite every data push in the script to its minimal encoding. Non-push opcodes are
+    /// copied unchanged, so the script's semantics are preserved. Errors if a push runs past the
+    /// end of the script, as there is then no way to tell where the data ends.
+    pub fn normalize_pushes(&self) -> Result<ScriptPubkey, ScriptError> {
+        let script = self.items();
+        let mut normalized = Vec::with_capacity(script.len());
+        let mut cursor = 0;
+        while cursor < script.len() {
+            match push_at(script, cursor) {
+                Some(push) => {
+                    let (header, len) = push?;
+                    let data = script
+                        .get(cursor + header..cursor + header + len)
+                        .ok_or(ScriptError::TruncatedPush(cursor))?;
+                    push_minimal(&mut normalized, data);
+                    cursor += header + len;
+                }
+                None => {
+                    normalized.push(script[cursor]);
+                    cursor += 1;
+                }
+            }
+        }
+        Ok(normalized.into())
+    }
+
+    /// Return a copy of the script with all `OP_CODESEPARATOR` opcodes removed, as required for
+    /// the script code in legacy sighash computation. Bytes inside data pushes are unaffected.
+    pub fn without_code_separators(&self) -> ScriptPubkey {
+        strip_code_separators(self.items()).into()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use coins_core::ser::ByteFormat;
 
+    #[test]
+    fn it_copies_script_bytes() {
+        let bytes = hex::decode("0014758ce550380d964051086798d6546bebdca27a73").unwrap();
+        let script_pubkey = ScriptPubkey::new(bytes.clone());
+        let copy = script_pubkey.to_bytes();
+        assert_eq!(copy, bytes);
+        assert_eq!(copy, script_pubkey.items());
+        // the copy outlives the script
+        drop(script_pubkey);
+        assert_eq!(copy.len(), 22);
+    }
+
+    #[test]
+    fn it_sizes_witnesses() {
+        // signed p2wpkh: a 72-byte sig and a 33-byte pubkey
+        let witness: Witness = vec![
+            WitnessStackItem::new(vec![0x30; 72]),
+            WitnessStackItem::new(vec![0x02; 33]),
+        ];
+        assert_eq!(witness.serialized_size(), 1 + (1 + 72) + (1 + 33));
+        assert_eq!(Witness::default().serialized_size(), 1);
+
+        let mut serialized = vec![];
+        coins_core::ser::write_prefix_vec(&mut serialized, &witness).unwrap();
+        assert_eq!(witness.serialized_size(), serialized.len());
+    }
+
+    #[test]
+    fn it_parses_pushes_and_multisig_scripts() {
+        assert_eq!(
+            parse_pushes(&hex::decode("00020102").unwrap()),
+            Some(vec![vec![], vec![1, 2]])
+        );
+        assert_eq!(
+            parse_pushes(&hex::decode("4c020102").unwrap()),
+            Some(vec![vec![1, 2]])
+        );
+        assert_eq!(parse_pushes(&hex::decode("030102").unwrap()), None);
+        assert_eq!(parse_pushes(&hex::decode("0102ac").unwrap()), None);
+        assert_eq!(parse_pushes(&hex::decode("4d01").unwrap()), None);
+
+        let multisig = Script::new(hex::decode("52210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c2102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f552ae").unwrap());
+        assert_eq!(multisig.multisig_params(), Some((2, 2)));
+        let not_multisig =
+            Script::new(hex::decode("0014758ce550380d964051086798d6546bebdca27a73").unwrap());
+        assert_eq!(not_multisig.multisig_params(), None);
+    }
+
+    #[test]
+    fn it_parses_p2pkh_script_sigs() {
+        let sig = "3045022100cfd18ee918d6729134adbc61212142cf71fcf186dfc3123cfca8f7062e0fad5a0220703cc467d9857349ddb6e148bd1663f5050f3f6b9d788d64349c357f14eb4a5f01";
+        let pubkey = "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa";
+        let script_sig = ScriptSig::new(hex::decode(format!("48{}21{}", sig, pubkey)).unwrap());
+
+        let (parsed_sig, parsed_key) = script_sig.parse_p2pkh().unwrap();
+        assert_eq!(hex::encode(parsed_sig), sig);
+        assert_eq!(hex::encode(parsed_key.to_sec1_bytes()), pubkey);
+
+        // A pubkey without a signature, and a signature with an invalid pubkey
+        let key_only = ScriptSig::new(hex::decode(format!("21{}", pubkey)).unwrap());
+        assert!(key_only.parse_p2pkh().is_none());
+        let bad_key = ScriptSig::new(hex::decode(format!("48{}0102", sig)).unwrap());
+        assert!(bad_key.parse_p2pkh().is_none());
+    }
+
+    #[test]
+    fn it_strips_code_separators() {
+        // OP_CODESEPARATOR, a push containing 0xab, OP_CODESEPARATOR, OP_CHECKSIG
+        let script = ScriptPubkey::new(hex::decode("ab02abababac").unwrap());
+        let stripped = script.without_code_separators();
+        assert_eq!(stripped.items(), &[0x02, 0xab, 0xab, 0xac]);
+        assert_eq!(stripped.len(), script.len() - 2);
+
+        let no_separators = ScriptPubkey::new(
+            hex::decode("76a914758ce550380d964051086798d6546bebdca27a7388ac").unwrap(),
+        );
+        assert_eq!(no_separators.without_code_separators(), no_separators);
+    }
+
+    #[test]
+    fn it_normalizes_pushes() {
+        // OP_PUSHDATA1 of 5 bytes, OP_DROP, then a 1-byte push of 0x05
+        let script = ScriptPubkey::new(hex::decode("4c050102030405750105").unwrap());
+        assert_eq!(
+            script.normalize_pushes().unwrap(),
+            ScriptPubkey::new(hex::decode("0501020304057555").unwrap())
+        );
+
+        let minimal = ScriptPubkey::new(
+            hex::decode("76a914758ce550380d964051086798d6546bebdca27a7388ac").unwrap(),
+        );
+        assert_eq!(minimal.normalize_pushes().unwrap(), minimal);
+
+        let truncated = ScriptPubkey::new(hex::decode("4c0501020304").unwrap());
+        match truncated.normalize_pushes() {
+            Err(ScriptError::TruncatedPush(0)) => {}
+            e => panic!("expected truncated push error, got {:?}", e),
+        }
+    }
+
     #[test]
     fn it_serializes_and_derializes_scripts() {
         let cases = [
@@ -266,7 +763,6 @@ mod test {
         }
     }
 
-    #[allow(unused_must_use)]
     #[test]
     #[allow(unused_must_use)]
     fn it_converts_between_bitcoin_script_types() {
@@ -318,4 +814,166 @@ mod test {
             assert_eq!(script.standard_type(), *t);
         }
     }
+
+    #[test]
+    fn it_converts_standard_scripts_to_typed_outputs() {
+        use std::convert::TryFrom;
+
+        let hash160 = <Hash160Digest as MarkedDigestOutput>::from_slice(&[0x11; 20]).unwrap();
+        let hash256 = <Hash256Digest as MarkedDigestOutput>::from_slice(&[0x22; 32]).unwrap();
+        let cases = [
+            (
+                format!("76a914{}88ac", "11".repeat(20)),
+                StandardOutput::P2pkh(hash160),
+            ),
+            (
+                format!("a914{}87", "11".repeat(20)),
+                StandardOutput::P2sh(hash160),
+            ),
+            (
+                format!("0014{}", "11".repeat(20)),
+                StandardOutput::P2wpkh(hash160),
+            ),
+            (
+                format!("0020{}", "22".repeat(32)),
+                StandardOutput::P2wsh(hash256),
+            ),
+            (
+                format!("5120{}", "33".repeat(32)),
+                StandardOutput::P2tr(XOnlyPubkey([0x33; 32])),
+            ),
+            (
+                "6a0201020103".to_owned(),
+                StandardOutput::OpReturn(vec![vec![1, 2], vec![3]]),
+            ),
+            ("6a".to_owned(), StandardOutput::OpReturn(vec![])),
+        ];
+        for (script, expected) in cases.iter() {
+            let script = ScriptPubkey::new(hex::decode(script).unwrap());
+            assert_eq!(&StandardOutput::try_from(&script).unwrap(), expected);
+        }
+
+        let nonstandard = [
+            // bare multisig
+            "51210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c51ae",
+            // OP_RETURN followed by a non-push opcode
+            "6a0102ac",
+            // a v1 witness program of the wrong length
+            "5114111111111111111111111111111111111111111111",
+        ];
+        for script in nonstandard.iter() {
+            let script = ScriptPubkey::new(hex::decode(script).unwrap());
+            match StandardOutput::try_from(&script) {
+                Err(ScriptError::NonStandard) => {}
+                e => panic!("expected nonstandard error, got {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn it_extracts_committed_hashes() {
+        let cases = [
+            (
+                format!("76a914{}88ac", "11".repeat(20)),
+                Some([0x11; 20].to_vec()),
+            ),
+            (
+                format!("a914{}87", "11".repeat(20)),
+                Some([0x11; 20].to_vec()),
+            ),
+            (
+                format!("0014{}", "11".repeat(20)),
+                Some([0x11; 20].to_vec()),
+            ),
+            (
+                format!("0020{}", "22".repeat(32)),
+                Some([0x22; 32].to_vec()),
+            ),
+            (
+                format!("5120{}", "33".repeat(32)),
+                Some([0x33; 32].to_vec()),
+            ),
+            ("6a0201020103".to_owned(), None),
+            (
+                "51210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c51ae"
+                    .to_owned(),
+                None,
+            ),
+            (format!("5214{}", "11".repeat(20)), None),
+        ];
+        for (script, expected) in cases.iter() {
+            let script = ScriptPubkey::new(hex::decode(script).unwrap());
+            assert_eq!(&script.committed_hash(), expected);
+        }
+    }
+
+    #[test]
+    fn it_optionally_validates_taproot_keys() {
+        // the generator's x coordinate is valid. x = 5 is not on the curve.
+        let mut valid = XOnlyPubkey([0u8; 32]);
+        valid.0.copy_from_slice(
+            &hex::decode("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                .unwrap(),
+        );
+        let mut invalid = XOnlyPubkey([0u8; 32]);
+        invalid.0[31] = 5;
+
+        let spk = ScriptPubkey::p2tr(&valid, true).unwrap();
+        assert_eq!(spk, ScriptPubkey::p2tr(&valid, false).unwrap());
+        assert_eq!(spk.standard_type(), ScriptType::Tr(valid));
+
+        match ScriptPubkey::p2tr(&invalid, true) {
+            Err(ScriptError::InvalidTaprootKey) => {}
+            e => panic!("expected invalid taproot key, got {:?}", e),
+        }
+        let spk = ScriptPubkey::p2tr(&invalid, false).unwrap();
+        assert_eq!(spk.standard_type(), ScriptType::Tr(invalid));
+    }
+
+    #[test]
+    fn it_builds_and_detects_timelock_scripts() {
+        use coins_bip32::enc::{MainnetEncoder, XKeyEncoder};
+
+        let key = MainnetEncoder::xpub_from_base58("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8").unwrap();
+        let pubkey_bytes = key.to_sec1_bytes();
+        let pubkey = hex::encode(pubkey_bytes);
+
+        // (value, minimally-encoded script number)
+        let cases = [
+            (0, "00"),
+            (16, "60"),
+            (17, "0111"),
+            (0x80, "028000"),
+            (500_000, "0320a107"),
+            (0xffff_ffff, "05ffffffff00"),
+        ];
+        for (value, encoded) in cases.iter() {
+            let cltv = ScriptPubkey::cltv_p2pk(*value, &key);
+            assert_eq!(
+                hex::encode(cltv.items()),
+                format!("{}b17521{}ac", encoded, pubkey)
+            );
+            assert_eq!(
+                cltv.standard_type(),
+                ScriptType::CltvP2pk(*value, pubkey_bytes)
+            );
+
+            let csv = ScriptPubkey::csv_p2pk(*value, &key);
+            assert_eq!(
+                hex::encode(csv.items()),
+                format!("{}b27521{}ac", encoded, pubkey)
+            );
+            assert_eq!(
+                csv.standard_type(),
+                ScriptType::CsvP2pk(*value, pubkey_bytes)
+            );
+        }
+
+        // non-minimal and negative numbers, and an unexpected opcode
+        for prefix in ["0110b1", "020100b1", "0181b1", "00b0"].iter() {
+            let script =
+                ScriptPubkey::new(hex::decode(format!("{}7521{}ac", prefix, pubkey)).unwrap());
+            assert_eq!(script.standard_type(), ScriptType::NonStandard);
+        }
+    }
 }
@@ -0,0 +1,465 @@
+//! Taproot-specific helpers for analyzing witnesses and building signature messages.
+
+use coins_bip32::{k256::ecdsa::VerifyingKey, primitives::Parity, xkeys::tap_tweak_pubkey};
+use coins_core::{hashes::tagged_hash, ser::ByteFormat};
+use thiserror::Error;
+
+use crate::types::{
+    script::{Script, Witness},
+    tx::{Sighash, TxError},
+};
+
+/// The BIP341 annex prefix. An annex is the last witness item, if there are at least 2 items and
+/// it begins with this byte.
+pub const ANNEX_TAG: u8 = 0x50;
+
+/// A 32-byte x-only public key, as committed to by a Taproot output. See BIP340. This does not
+/// check that the bytes encode a valid curve point.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct XOnlyPubkey(pub [u8; 32]);
+
+impl From<[u8; 32]> for XOnlyPubkey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl XOnlyPubkey {
+    /// True if the bytes are the x coordinate of a point on the curve.
+    pub fn is_valid_point(&self) -> bool {
+        let mut sec1 = [0x02u8; 33];
+        sec1[1..].copy_from_slice(&self.0);
+        VerifyingKey::from_sec1_bytes(&sec1).is_ok()
+    }
+}
+
+impl AsRef<[u8]> for XOnlyPubkey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Compute the x-only BIP341 output key for the internal key `P`. See
+/// `coins_bip32::xkeys::tap_tweak_pubkey`. Without a `merkle_root`, this is the BIP86
+/// key-path-only output key.
+///
+/// # Panics
+///
+/// If the tweak exceeds the curve order, or `Q` is the point at infinity. Both happen with
+/// negligible probability.
+pub fn taproot_output_key<K>(internal_key: &K, merkle_root: Option<[u8; 32]>) -> XOnlyPubkey
+where
+    K: AsRef<VerifyingKey>,
+{
+    let (output, _) =
+        tap_tweak_pubkey(internal_key.as_ref(), merkle_root).expect("negligible probability");
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&output.to_sec1_bytes()[1..]);
+    XOnlyPubkey(key)
+}
+
+/// Errors when parsing or constructing Taproot script path data
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TaprootError {
+    /// Leaf versions must be even, and may not be the annex tag `0x50`
+    #[error("Invalid tapleaf version: {0:#04x}")]
+    InvalidLeafVersion(u8),
+
+    /// Control blocks are 33 bytes, plus 32 bytes for each of up to 128 merkle branch nodes
+    #[error("Invalid control block length: {0}")]
+    InvalidControlBlockLength(usize),
+}
+
+/// The most merkle branch nodes a control block may hold. This bounds the tree depth.
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+/// A BIP341 tapleaf version. The leaf version is stored in the high 7 bits of the first control
+/// block byte, so it must be even. `0x50` is also forbidden, as its control block could be
+/// mistaken for an annex. Versions other than `0xc0` are unassigned, but are accepted for forward
+/// compatibility.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LeafVersion(u8);
+
+impl LeafVersion {
+    /// The BIP342 tapscript leaf version, `0xc0`.
+    pub const TAPSCRIPT: LeafVersion = LeafVersion(0xc0);
+
+    /// Instantiate a leaf version, checking that it is even and is not the annex tag.
+    pub fn new(version: u8) -> Result<Self, TaprootError> {
+        if version & 1 == 1 || version == ANNEX_TAG {
+            return Err(TaprootError::InvalidLeafVersion(version));
+        }
+        Ok(Self(version))
+    }
+
+    /// The leaf version byte.
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+}
+
+impl Default for LeafVersion {
+    fn default() -> Self {
+        Self::TAPSCRIPT
+    }
+}
+
+/// Compute the BIP341 tapleaf hash of `script`. The hash commits to the leaf version.
+pub fn tapleaf_hash(version: LeafVersion, script: &Script) -> [u8; 32] {
+    let mut data = vec![version.to_u8()];
+    script.write_to(&mut data).expect("No error on heap write");
+    tagged_hash("TapLeaf", &data)
+}
+
+/// A BIP341 control block, the last witness item of a script path spend (before any annex). It
+/// proves that the executed leaf is committed to by the output key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlBlock {
+    /// The version of the executed leaf
+    pub leaf_version: LeafVersion,
+    /// The parity of the y coordinate of the tweaked output key
+    pub output_key_parity: Parity,
+    /// The x-only internal key
+    pub internal_key: XOnlyPubkey,
+    /// The merkle branch from the leaf to the root, leaf end first
+    pub merkle_branch: Vec<[u8; 32]>,
+}
+
+impl ControlBlock {
+    /// Parse a serialized control block, validating its length and leaf version.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TaprootError> {
+        let len = data.len();
+        if len < 33
+            || !(len - 33).is_multiple_of(32)
+            || (len - 33) / 32 > TAPROOT_CONTROL_MAX_NODE_COUNT
+        {
+            return Err(TaprootError::InvalidControlBlockLength(len));
+        }
+        let leaf_version = LeafVersion::new(data[0] & 0xfe)?;
+        let output_key_parity = if data[0] & 1 == 1 {
+            Parity::Odd
+        } else {
+            Parity::Even
+        };
+        let mut internal_key = [0u8; 32];
+        internal_key.copy_from_slice(&data[1..33]);
+        let merkle_branch = data[33..]
+            .chunks(32)
+            .map(|chunk| {
+                let mut node = [0u8; 32];
+                node.copy_from_slice(chunk);
+                node
+            })
+            .collect();
+        Ok(Self {
+            leaf_version,
+            output_key_parity,
+            internal_key: internal_key.into(),
+            merkle_branch,
+        })
+    }
+
+    /// Serialize the control block.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![self.leaf_version.to_u8() | self.output_key_parity.to_u8()];
+        data.extend(self.internal_key.as_ref());
+        for node in self.merkle_branch.iter() {
+            data.extend(node);
+        }
+        data
+    }
+}
+
+/// The BIP341 sighash epoch. It is prepended to the Taproot signature message, so that future
+/// sighash schemes can be distinguished from this one.
+pub const TAPROOT_SIGHASH_EPOCH: u8 = 0x00;
+
+/// A BIP341 sighash type. These are the legacy sighash flags, plus `Default`, which commits to
+/// the same data as `All` but is omitted from the signature, saving a byte.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TapSighashType {
+    /// Sign ALL inputs and ALL outputs. The signature carries no sighash byte
+    Default = 0x00,
+    /// Sign ALL inputs and ALL outputs
+    All = 0x01,
+    /// Sign ALL inputs and NO outputs
+    None = 0x02,
+    /// Sign ALL inputs and ONE output
+    Single = 0x03,
+    /// Sign ONE inputs and ALL outputs
+    AllAcp = 0x81,
+    /// Sign ONE inputs and NO outputs
+    NoneAcp = 0x82,
+    /// Sign ONE inputs and ONE output
+    SingleAcp = 0x83,
+}
+
+impl TapSighashType {
+    /// Convert the sighash type into its u8 representation.
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Convert a u8 into a sighash type or an error.
+    pub fn from_u8(flag: u8) -> Result<Self, TxError> {
+        match flag {
+            0x00 => Ok(TapSighashType::Default),
+            0x80 => Err(TxError::UnknownSighash(flag)),
+            _ => Sighash::from_u8(flag).map(Into::into),
+        }
+    }
+
+    /// True if the sighash type commits to only the input being signed.
+    pub fn is_anyone_can_pay(self) -> bool {
+        self as u8 & 0x80 == 0x80
+    }
+
+    /// True if the sighash type commits to all outputs.
+    pub fn commits_to_all_outputs(self) -> bool {
+        matches!(self as u8 & 0x03, 0x00 | 0x01)
+    }
+}
+
+impl From<Sighash> for TapSighashType {
+    fn from(flag: Sighash) -> Self {
+        match flag {
+            Sighash::All => TapSighashType::All,
+            Sighash::None => TapSighashType::None,
+            Sighash::Single => TapSighashType::Single,
+            Sighash::AllAcp => TapSighashType::AllAcp,
+            Sighash::NoneAcp => TapSighashType::NoneAcp,
+            Sighash::SingleAcp => TapSighashType::SingleAcp,
+        }
+    }
+}
+
+/// Compute the BIP341 `spend_type` byte of the Taproot signature message. This is
+/// `(ext_flag << 1) | annex_present`, where `ext_flag` is 1 for BIP342 script path spends and 0
+/// for key path spends.
+pub fn taproot_spend_type_byte(has_annex: bool, is_script_path: bool) -> u8 {
+    ((is_script_path as u8) << 1) | has_annex as u8
+}
+
+/// The way a Taproot output is spent, as determined from the input witness.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TaprootSpendType {
+    /// A key path spend. The witness is a single signature.
+    KeyPath,
+    /// A key path spend, with an annex
+    KeyPathWithAnnex,
+    /// A script path spend. The witness is the script inputs, the script, and a control block.
+    ScriptPath,
+    /// A script path spend, with an annex
+    ScriptPathWithAnnex,
+    /// An empty witness, which can't spend a Taproot output
+    Invalid,
+}
+
+impl TaprootSpendType {
+    /// True if the witness includes an annex.
+    pub fn has_annex(&self) -> bool {
+        matches!(
+            self,
+            TaprootSpendType::KeyPathWithAnnex | TaprootSpendType::ScriptPathWithAnnex
+        )
+    }
+
+    /// True if this is a script path spend.
+    pub fn is_script_path(&self) -> bool {
+        matches!(
+            self,
+            TaprootSpendType::ScriptPath | TaprootSpendType::ScriptPathWithAnnex
+        )
+    }
+}
+
+/// Functions for interpreting a witness as spending a Taproot output.
+pub trait TaprootWitness {
+    /// Return the annex, including its `0x50` prefix, if the witness has one.
+    fn annex(&self) -> Option<&[u8]>;
+
+    /// Determine whether the witness is a key path or script path spend, and whether it has an
+    /// annex.
+    fn spend_type(&self) -> TaprootSpendType;
+}
+
+impl TaprootWitness for Witness {
+    fn annex(&self) -> Option<&[u8]> {
+        if self.len() < 2 {
+            return None;
+        }
+        let last = self.last()?.items();
+        if last.first() == Some(&ANNEX_TAG) {
+            Some(last)
+        } else {
+            None
+        }
+    }
+
+    fn spend_type(&self) -> TaprootSpendType {
+        let has_annex = self.annex().is_some();
+        match (self.len() - has_annex as usize, has_annex) {
+            (0, _) => TaprootSpendType::Invalid,
+            (1, false) => TaprootSpendType::KeyPath,
+            (1, true) => TaprootSpendType::KeyPathWithAnnex,
+            (_, false) => TaprootSpendType::ScriptPath,
+            (_, true) => TaprootSpendType::ScriptPathWithAnnex,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::script::WitnessStackItem;
+
+    #[test]
+    fn it_validates_leaf_versions() {
+        assert_eq!(LeafVersion::new(0xc0).unwrap(), LeafVersion::TAPSCRIPT);
+        assert_eq!(LeafVersion::default(), LeafVersion::TAPSCRIPT);
+        assert_eq!(LeafVersion::new(0xc2).unwrap().to_u8(), 0xc2);
+        for version in [0xc1u8, 0xff, 0x51, ANNEX_TAG].iter() {
+            assert_eq!(
+                LeafVersion::new(*version),
+                Err(TaprootError::InvalidLeafVersion(*version))
+            );
+        }
+
+        // BIP341 wallet vector leaf. The leaf hash commits to the leaf version, then the
+        // length-prefixed script
+        let script = Script::new(
+            hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")
+                .unwrap(),
+        );
+        assert_eq!(
+            hex::encode(tapleaf_hash(LeafVersion::TAPSCRIPT, &script)),
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"
+        );
+        let mut preimage = vec![0xc2, 0x22];
+        preimage.extend(script.items());
+        assert_eq!(
+            tapleaf_hash(LeafVersion::new(0xc2).unwrap(), &script)[..],
+            tagged_hash("TapLeaf", &preimage)[..]
+        );
+    }
+
+    #[test]
+    fn it_round_trips_control_blocks() {
+        let mut data = vec![0xc1];
+        data.extend([0x11u8; 32]);
+        data.extend([0x22u8; 32]);
+        data.extend([0x33u8; 32]);
+
+        let control_block = ControlBlock::from_bytes(&data).unwrap();
+        assert_eq!(control_block.leaf_version, LeafVersion::TAPSCRIPT);
+        assert_eq!(control_block.output_key_parity, Parity::Odd);
+        assert_eq!(control_block.internal_key, XOnlyPubkey([0x11; 32]));
+        assert_eq!(control_block.merkle_branch, vec![[0x22; 32], [0x33; 32]]);
+        assert_eq!(control_block.to_bytes(), data);
+
+        // an unassigned, even leaf version is accepted
+        data[0] = 0xc2;
+        let control_block = ControlBlock::from_bytes(&data).unwrap();
+        assert_eq!(control_block.leaf_version.to_u8(), 0xc2);
+        assert_eq!(control_block.output_key_parity, Parity::Even);
+
+        // the annex tag is not a leaf version, with either parity
+        data[0] = 0x51;
+        assert_eq!(
+            ControlBlock::from_bytes(&data),
+            Err(TaprootError::InvalidLeafVersion(ANNEX_TAG))
+        );
+
+        for len in [0usize, 32, 34, 66, 33 + 32 * 129].iter() {
+            let data = vec![0xc0; *len];
+            assert_eq!(
+                ControlBlock::from_bytes(&data),
+                Err(TaprootError::InvalidControlBlockLength(*len))
+            );
+        }
+    }
+
+    fn witness(items: &[&[u8]]) -> Witness {
+        items
+            .iter()
+            .map(|item| WitnessStackItem::new(item.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn it_detects_annexes_and_spend_types() {
+        let sig = [0x11u8; 64];
+        let script = [0x20u8, 0x22, 0xac];
+        let control_block = [0xc0u8; 33];
+        let annex = [ANNEX_TAG, 0x01];
+
+        // (witness, annex, spend type)
+        let cases = [
+            (witness(&[&sig]), None, TaprootSpendType::KeyPath),
+            (
+                witness(&[&sig, &annex]),
+                Some(&annex[..]),
+                TaprootSpendType::KeyPathWithAnnex,
+            ),
+            (
+                witness(&[&sig, &script, &control_block]),
+                None,
+                TaprootSpendType::ScriptPath,
+            ),
+            (
+                witness(&[&sig, &script, &control_block, &annex]),
+                Some(&annex[..]),
+                TaprootSpendType::ScriptPathWithAnnex,
+            ),
+            // a lone item starting with 0x50 is not an annex
+            (witness(&[&annex]), None, TaprootSpendType::KeyPath),
+            (witness(&[]), None, TaprootSpendType::Invalid),
+        ];
+        for (witness, annex, spend_type) in cases.iter() {
+            assert_eq!(witness.annex(), *annex);
+            assert_eq!(witness.spend_type(), *spend_type);
+        }
+        assert!(TaprootSpendType::ScriptPathWithAnnex.has_annex());
+        assert!(TaprootSpendType::ScriptPathWithAnnex.is_script_path());
+        assert!(!TaprootSpendType::KeyPathWithAnnex.is_script_path());
+    }
+
+    #[test]
+    fn it_gets_tap_sighash_types_from_u8s() {
+        for flag in [0x00u8, 0x01, 0x02, 0x03, 0x81, 0x82, 0x83].iter() {
+            assert_eq!(TapSighashType::from_u8(*flag).unwrap().to_u8(), *flag);
+        }
+        for flag in [0x04u8, 0x80, 0x84, 0xff].iter() {
+            match TapSighashType::from_u8(*flag) {
+                Err(TxError::UnknownSighash(f)) => assert_eq!(f, *flag),
+                e => panic!("expected unknown sighash, got {:?}", e),
+            }
+        }
+        assert_eq!(
+            TapSighashType::from(Sighash::SingleAcp),
+            TapSighashType::SingleAcp
+        );
+        assert!(TapSighashType::Default.commits_to_all_outputs());
+        assert!(!TapSighashType::NoneAcp.commits_to_all_outputs());
+        assert!(TapSighashType::NoneAcp.is_anyone_can_pay());
+    }
+
+    #[test]
+    fn it_calculates_spend_type_bytes() {
+        // (has_annex, is_script_path, spend_type)
+        let cases = [
+            (false, false, 0x00),
+            (true, false, 0x01),
+            (false, true, 0x02),
+            (true, true, 0x03),
+        ];
+        for (has_annex, is_script_path, expected) in cases.iter() {
+            assert_eq!(
+                taproot_spend_type_byte(*has_annex, *is_script_path),
+                *expected
+            );
+        }
+        assert_eq!(TAPROOT_SIGHASH_EPOCH, 0);
+    }
+}
@@ -9,10 +9,13 @@ use coins_core::{
 };
 
 use crate::{
-    hashes::TXID,
+    hashes::{TXID, WTXID},
     types::{
         legacy::*,
-        script::Witness,
+        script::{
+            is_signature, parse_pushes, Script, ScriptPubkey, ScriptSig, ScriptType, Witness,
+            WitnessStackItem,
+        },
         txin::{BitcoinOutpoint, BitcoinTxIn},
         txout::TxOut,
         witness::*,
@@ -22,6 +25,11 @@ use crate::{
 /// Wrapper enum for returning values that may be EITHER a Witness OR a Legacy tx and the type is
 /// not known in advance. While a few transaction methods have been implemented for convenience,
 /// This wrapper must be explicitly unwrapped before the tx object can be signed.
+///
+/// Equality compares the full transaction, including witnesses, and is consistent with
+/// `ByteFormat::serialized_eq`. Two transactions may share a txid without being equal, as the
+/// txid does not commit to the witnesses. To compare transactions ignoring witnesses, compare
+/// their txids.
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
 pub enum BitcoinTx {
     /// Witness
@@ -73,6 +81,121 @@ impl BitcoinTx {
     pub fn is_legacy(&self) -> bool {
         matches!(self, BitcoinTx::Legacy(_))
     }
+
+    /// The witness txid of the transaction, per BIP141: the double-sha256 of its full
+    /// serialization, including witnesses. It is equal to the txid if the transaction has no
+    /// witness data.
+    ///
+    /// Like the txid, it is in internal byte order. Use `to_be_hex` to get the order displayed
+    /// by Bitcoin Core's RPC and block explorers.
+    pub fn wtxid(&self) -> WTXID {
+        match self {
+            BitcoinTx::Witness(tx) => tx.wtxid(),
+            BitcoinTx::Legacy(tx) => tx.wtxid(),
+        }
+    }
+
+    /// Calculates the sighash of the input at `index`, as described by `spend`. See
+    /// `WitnessTx::spend_sighash`. Legacy transactions may still sign witness spends, as the
+    /// BIP143 sighash does not commit to witnesses.
+    pub fn spend_sighash(
+        &self,
+        index: usize,
+        flag: Sighash,
+        prevout_value: u64,
+        spend: &SpendInfo,
+    ) -> TxResult<Hash256Digest> {
+        match self {
+            BitcoinTx::Witness(tx) => tx.spend_sighash(index, flag, prevout_value, spend),
+            BitcoinTx::Legacy(tx) => {
+                WitnessTx::from_legacy(tx.clone()).spend_sighash(index, flag, prevout_value, spend)
+            }
+        }
+    }
+
+    /// Check that `hex` is a valid serialized transaction, and report where it fails if not.
+    pub fn validate_hex(hex: &str) -> Result<(), TxHexError> {
+        let bytes = hex::decode(hex).map_err(|e| match e {
+            hex::FromHexError::InvalidHexCharacter { c, index } => TxHexError::InvalidCharacter {
+                character: c,
+                index,
+            },
+            _ => TxHexError::OddLength,
+        })?;
+
+        let mut reader = CountingReader {
+            inner: &bytes[..],
+            count: 0,
+        };
+        match BitcoinTx::read_from(&mut reader) {
+            Ok(_) if reader.count < bytes.len() => Err(TxHexError::TrailingBytes {
+                offset: reader.count,
+            }),
+            Ok(_) => Ok(()),
+            Err(_) if reader.count == bytes.len() => Err(TxHexError::Truncated {
+                offset: reader.count,
+            }),
+            Err(e) => Err(TxHexError::Invalid {
+                offset: reader.count,
+                source: e,
+            }),
+        }
+    }
+}
+
+/// A reader that counts the bytes read from it.
+struct CountingReader<R> {
+    inner: R,
+    count: usize,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read;
+        Ok(read)
+    }
+}
+
+/// Errors describing why a hex string is not a valid serialized transaction.
+#[derive(Debug, Error)]
+pub enum TxHexError {
+    /// The string contains a non-hex character.
+    #[error("Invalid hex character {character:?} at position {index}")]
+    InvalidCharacter {
+        /// The invalid character
+        character: char,
+        /// The position of the character in the string
+        index: usize,
+    },
+
+    /// The string has an odd number of characters.
+    #[error("Hex string has odd length")]
+    OddLength,
+
+    /// The transaction ended early. More bytes were expected at the offset.
+    #[error("Transaction truncated. Expected more bytes at offset {offset}")]
+    Truncated {
+        /// The byte offset at which more bytes were expected
+        offset: usize,
+    },
+
+    /// A valid transaction was followed by extra bytes, starting at the offset.
+    #[error("Unexpected trailing bytes at offset {offset}")]
+    TrailingBytes {
+        /// The byte offset of the first trailing byte
+        offset: usize,
+    },
+
+    /// The transaction failed to deserialize. The offset is the number of bytes read before the
+    /// failure.
+    #[error("Invalid transaction at offset {offset}: {source}")]
+    Invalid {
+        /// The number of bytes read before the failure
+        offset: usize,
+        /// The deserialization error
+        source: TxError,
+    },
 }
 
 impl ByteFormat for BitcoinTx {
@@ -103,6 +226,17 @@ impl ByteFormat for BitcoinTx {
         }
     }
 
+    /// Deserialize a tx from a slice. Witness txs must consume the whole slice. See
+    /// `WitnessTx::deserialize_slice`.
+    fn deserialize_slice(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let tx = Self::read_from(&mut cursor)?;
+        if let BitcoinTx::Witness(witness_tx) = &tx {
+            check_consumed(&cursor, witness_tx.inputs().len())?;
+        }
+        Ok(tx)
+    }
+
     fn write_to<W>(&self, writer: &mut W) -> Result<usize, <Self as ByteFormat>::Error>
     where
         W: Write,
@@ -169,7 +303,9 @@ impl Transaction for BitcoinTx {
         }
     }
 
-    /// Return the TXID of the transaction
+    /// Return the TXID of the transaction: the double-sha256 of its legacy serialization, which
+    /// excludes witnesses. It is in internal byte order. Use `to_be_hex` to get the order
+    /// displayed by Bitcoin Core's RPC and block explorers.
     fn txid(&self) -> TXID {
         match self {
             BitcoinTx::Witness(tx) => tx.txid(),
@@ -188,6 +324,14 @@ impl Transaction for BitcoinTx {
             BitcoinTx::Legacy(tx) => tx.write_sighash_preimage(writer, &args.into()),
         }
     }
+
+    /// Return the sighash of the underlying tx. Legacy txs use the legacy sighash.
+    fn sighash(&self, args: &Self::SighashArgs) -> TxResult<DigestOutput<Hash256>> {
+        match self {
+            BitcoinTx::Witness(tx) => tx.sighash(args),
+            BitcoinTx::Legacy(tx) => tx.sighash(&args.into()),
+        }
+    }
 }
 
 /// An Error type for transaction objects
@@ -205,7 +349,8 @@ pub enum TxError {
     #[error("SIGHASH_NONE is unsupported")]
     NoneUnsupported,
 
-    /// Satoshi's sighash single bug. Throws an error here.
+    /// Satoshi's sighash single bug. The legacy preimage does not exist, but
+    /// `Transaction::sighash` returns the digest consensus requires.
     #[error("SIGHASH_SINGLE bug is unsupported")]
     SighashSingleBug,
 
@@ -229,6 +374,31 @@ pub enum TxError {
     /// No inputs in vin
     #[error("Vin may not be empty")]
     EmptyVin,
+
+    /// The number of prevouts provided does not match the number of inputs
+    #[error("Expected {inputs} prevouts, got {prevouts}")]
+    PrevoutCountMismatch {
+        /// The number of inputs in the transaction
+        inputs: usize,
+        /// The number of prevouts provided
+        prevouts: usize,
+    },
+
+    /// No input exists at this index
+    #[error("No input at index {0}")]
+    NoSuchInput(usize),
+
+    /// The input at this index carries a script sig or witness, which would be invalidated
+    #[error("Input {0} is signed. Modifying it would invalidate its signature")]
+    InputAlreadySigned(usize),
+
+    /// A BIP341 SIGHASH_SINGLE input has no output at the same index
+    #[error("Input {0} has no matching output for SIGHASH_SINGLE")]
+    NoMatchingOutput(usize),
+
+    /// Bubbled up from the signing key
+    #[error("Signing failed: {0}")]
+    SigningError(coins_bip32::k256::ecdsa::Error),
 }
 
 /// Type alias for result with TxError
@@ -262,6 +432,49 @@ pub trait BitcoinTransaction:
     /// For witness txns, this will ALWAYS be the same length as the input vector.
     fn witnesses(&self) -> &[Witness];
 
+    /// Split the transaction into its legacy serialization and its witness section. The legacy
+    /// segment is the serialization hashed to produce the txid. The witness segment is the
+    /// concatenated, prefixed witness stacks, and is empty for legacy transactions.
+    ///
+    /// The full witness serialization is the legacy segment with the `0001` marker and flag
+    /// inserted after the version, and the witness segment inserted before the locktime.
+    fn serialize_segments(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut legacy = vec![];
+        self.as_legacy()
+            .write_to(&mut legacy)
+            .expect("No error on heap write");
+        let mut witness = vec![];
+        for wit in self.witnesses().iter() {
+            coins_core::ser::write_prefix_vec(&mut witness, wit).expect("No error on heap write");
+        }
+        (legacy, witness)
+    }
+
+    /// The total serialized length of the transaction in bytes, including any witnesses.
+    fn size(&self) -> usize {
+        self.serialized_length()
+    }
+
+    /// The weight of the transaction, per BIP141. Bytes of the legacy serialization count 4
+    /// weight units each, and the segwit marker, flag and witnesses count 1 weight unit per byte.
+    fn weight(&self) -> usize {
+        3 * self.as_legacy().serialized_length() + self.size()
+    }
+
+    /// The virtual size of the transaction, per BIP141. This is the weight divided by 4, rounded
+    /// up. For legacy transactions it is equal to the size.
+    fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    /// A key for deduplicating transactions, e.g. in a cache. This is the txid, which commits to
+    /// the version, inputs, outputs, and locktime, but not to the witnesses. For transactions
+    /// whose inputs all spend segwit outputs, it can't be changed by a third party. Legacy
+    /// script_sigs are malleable, and change the txid.
+    fn dedup_key(&self) -> TXID {
+        self.txid()
+    }
+
     /// Get a reference to the output by
     fn txout_from_outpoint(&self, outpoint: &BitcoinOutpoint) -> Option<&TxOut> {
         if outpoint.txid == self.txid() && (outpoint.idx as usize) < self.outputs().len() {
@@ -270,6 +483,178 @@ pub trait BitcoinTransaction:
             None
         }
     }
+
+    /// Check that every input carries a structurally complete satisfaction for the output it
+    /// spends. `prevouts` must hold the spent outputs, in input order. Signatures are not
+    /// verified.
+    ///
+    /// PKH and WPKH inputs must have a signature and a pubkey. SH and WSH inputs with a multisig
+    /// redeem or witness script must have at least `m` signatures. Inputs spending other scripts
+    /// are considered signed if they carry any script sig or witness.
+    fn is_fully_signed(&self, prevouts: &[TxOut]) -> bool {
+        if prevouts.len() != self.inputs().len() {
+            return false;
+        }
+        self.inputs()
+            .iter()
+            .zip(prevouts.iter())
+            .enumerate()
+            .all(|(i, (input, prevout))| {
+                let witness = self.witnesses().get(i).map(Vec::as_slice).unwrap_or(&[]);
+                input_is_signed(&input.script_sig, witness, &prevout.script_pubkey)
+            })
+    }
+
+    /// List the prevout data an external signer must be given to sign each input, in input
+    /// order. Witness sighashes commit to the prevout value, so witness inputs need both the
+    /// value and the script pubkey. Legacy inputs need only the script pubkey.
+    ///
+    /// # Note:
+    ///
+    /// Unsigned native witness inputs can't be told apart from unsigned legacy inputs. In
+    /// witness transactions, inputs with an empty script sig and witness are assumed to need the
+    /// value, as supplying it needlessly is harmless. Inputs of legacy transactions never need
+    /// it.
+    fn prevout_requirements(&self) -> Vec<PrevoutRequirement> {
+        let is_witness_tx = !self.witnesses().is_empty();
+        self.inputs()
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let witness = self.witnesses().get(i).map(Vec::as_slice).unwrap_or(&[]);
+                PrevoutRequirement {
+                    outpoint: input.outpoint,
+                    needs_value: is_witness_tx
+                        && (!witness.is_empty() || spends_witness_program(&input.script_sig)),
+                }
+            })
+            .collect()
+    }
+
+    /// Return an outpoint referencing this transaction's output at `vout`. `None` if the
+    /// transaction has no output at that index.
+    fn outpoint_at(&self, vout: u32) -> Option<BitcoinOutpoint> {
+        if (vout as usize) < self.outputs().len() {
+            Some(BitcoinOutpoint::new(self.txid(), vout))
+        } else {
+            None
+        }
+    }
+
+    /// True if the transaction may be included in a block at `height` whose median time past is
+    /// `mtp`. This matches Bitcoin Core's `IsFinalTx`. The transaction is final if its locktime
+    /// is 0, if its locktime is below `height` (or `mtp`, for timestamp locktimes), or if every
+    /// input's sequence is `0xffff_ffff`.
+    fn is_final(&self, height: u32, mtp: u32) -> bool {
+        let locktime = self.locktime();
+        if locktime == 0 {
+            return true;
+        }
+        let cutoff = if locktime < LOCKTIME_THRESHOLD {
+            height
+        } else {
+            mtp
+        };
+        locktime < cutoff
+            || self
+                .inputs()
+                .iter()
+                .all(|input| input.sequence == SEQUENCE_FINAL)
+    }
+}
+
+/// Locktimes below this value are block heights. Locktimes at or above it are unix timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// An input sequence that disables locktime for its transaction, if set on every input.
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// The prevout data needed to sign an input. See `BitcoinTransaction::prevout_requirements`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrevoutRequirement {
+    /// The outpoint of the prevout to look up
+    pub outpoint: BitcoinOutpoint,
+    /// True if the prevout value is needed, as well as the prevout script pubkey
+    pub needs_value: bool,
+}
+
+/// True if the script sig is empty, as for native witness inputs, or pushes only a witness
+/// program, as for nested witness inputs.
+fn spends_witness_program(script_sig: &ScriptSig) -> bool {
+    if script_sig.is_empty() {
+        return true;
+    }
+    match parse_pushes(script_sig.items()).as_deref() {
+        Some([program]) => matches!(
+            ScriptPubkey::from(program.clone()).standard_type(),
+            ScriptType::Wpkh(_) | ScriptType::Wsh(_)
+        ),
+        _ => false,
+    }
+}
+
+/// True if the items are a signature followed by a pubkey.
+fn is_sig_and_pubkey<T: AsRef<[u8]>>(items: &[T]) -> bool {
+    items.len() == 2
+        && is_signature(items[0].as_ref())
+        && [33, 65].contains(&items[1].as_ref().len())
+}
+
+/// True if `items` satisfy `script`. For multisig scripts we expect the leading dummy element,
+/// followed by at least `m` signatures.
+fn script_is_satisfied<T: AsRef<[u8]>>(items: &[T], script: &Script) -> bool {
+    match script.multisig_params() {
+        Some((m, _)) => {
+            !items.is_empty()
+                && items[0].as_ref().is_empty()
+                && items[1..]
+                    .iter()
+                    .filter(|i| is_signature(i.as_ref()))
+                    .count()
+                    >= m
+        }
+        None => !items.is_empty(),
+    }
+}
+
+/// True if the witness satisfies a WSH output. The last item is the witness script.
+fn wsh_is_satisfied(witness: &[WitnessStackItem]) -> bool {
+    match witness.split_last() {
+        Some((script, items)) => script_is_satisfied(items, &script.into()),
+        None => false,
+    }
+}
+
+/// Structurally check the satisfaction of a single input.
+fn input_is_signed(
+    script_sig: &ScriptSig,
+    witness: &[WitnessStackItem],
+    script_pubkey: &ScriptPubkey,
+) -> bool {
+    match script_pubkey.standard_type() {
+        ScriptType::Pkh(_) => parse_pushes(script_sig.items())
+            .map(|pushes| is_sig_and_pubkey(&pushes))
+            .unwrap_or(false),
+        ScriptType::Wpkh(_) => script_sig.is_empty() && is_sig_and_pubkey(witness),
+        ScriptType::Wsh(_) => script_sig.is_empty() && wsh_is_satisfied(witness),
+        ScriptType::Sh(_) => {
+            let pushes = match parse_pushes(script_sig.items()) {
+                Some(pushes) => pushes,
+                None => return false,
+            };
+            let (redeem_script, items) = match pushes.split_last() {
+                Some((redeem_script, items)) => (Script::from(redeem_script.clone()), items),
+                None => return false,
+            };
+            // nested witness programs carry their satisfaction in the witness
+            match ScriptPubkey::from(&redeem_script).standard_type() {
+                ScriptType::Wpkh(_) => items.is_empty() && is_sig_and_pubkey(witness),
+                ScriptType::Wsh(_) => items.is_empty() && wsh_is_satisfied(witness),
+                _ => script_is_satisfied(items, &redeem_script),
+            }
+        }
+        _ => !script_sig.is_empty() || !witness.is_empty(),
+    }
 }
 
 impl BitcoinTransaction for BitcoinTx {
@@ -308,20 +693,20 @@ impl BitcoinTransaction for BitcoinTx {
 pub enum Sighash {
     /// Sign ALL inputs and ALL outputs
     All = 0x01,
-    /// Sign ALL inputs and NO outputs (unsupported)
+    /// Sign ALL inputs and NO outputs (unsupported in witness sighashes)
     None = 0x02,
     /// Sign ALL inputs and ONE output
     Single = 0x3,
     /// Sign ONE inputs and ALL outputs
     AllAcp = 0x81,
-    /// Sign ONE inputs and NO outputs (unsupported)
+    /// Sign ONE inputs and NO outputs (unsupported in witness sighashes)
     NoneAcp = 0x82,
     /// Sign ONE inputs and ONE output
     SingleAcp = 0x83,
 }
 
 impl Sighash {
-    ///
+    /// Convert the Sighash flag into its u8 representation.
     pub fn to_u8(self) -> u8 {
         self as u8
     }
@@ -343,7 +728,11 @@ impl Sighash {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::prelude::*;
+    use crate::{
+        prelude::*,
+        test_utils::{test_address, TestWallet},
+        BitcoinMainnet,
+    };
 
     #[test]
     fn it_calculates_legacy_sighashes_and_txids() {
@@ -392,9 +781,9 @@ mod tests {
         args.sighash_flag = Sighash::AllAcp;
         assert_eq!(tx.sighash(&args).unwrap(), all_anyonecanpay);
         args.sighash_flag = Sighash::Single;
-        assert_eq!(tx.sighash(&args).unwrap(), single);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single[..]);
         args.sighash_flag = Sighash::SingleAcp;
-        assert_eq!(tx.sighash(&args).unwrap(), single_anyonecanpay);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single_anyonecanpay[..]);
     }
 
     #[test]
@@ -402,7 +791,9 @@ mod tests {
         // pulled from riemann-py helpers
         let tx_hex = "02000000000101ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18700cafd0700";
         let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
-        assert_eq!(tx.serialized_length(), tx_hex.len() / 2);
+        // the tx has no witness data, so it re-serializes in the legacy format
+        assert_eq!(tx.serialized_length(), tx.as_legacy().serialized_length());
+        assert_eq!(tx.serialize_hex(), tx.as_legacy().serialize_hex());
 
         let prevout_script_hex = "160014758ce550380d964051086798d6546bebdca27a73";
         let prevout_script = Script::deserialize_hex(prevout_script_hex).unwrap();
@@ -447,10 +838,10 @@ mod tests {
         assert_eq!(tx.sighash(&args).unwrap(), all_anyonecanpay);
 
         args.sighash_flag = Sighash::Single;
-        assert_eq!(tx.sighash(&args).unwrap(), single);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single[..]);
 
         args.sighash_flag = Sighash::SingleAcp;
-        assert_eq!(tx.sighash(&args).unwrap(), single_anyonecanpay);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single_anyonecanpay[..]);
     }
 
     #[test]
@@ -458,7 +849,9 @@ mod tests {
         // from riemann-py
         let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
         let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
-        assert_eq!(tx.serialized_length(), tx_hex.len() / 2);
+        // the tx has no witness data, so it re-serializes in the legacy format
+        assert_eq!(tx.serialized_length(), tx.as_legacy().serialized_length());
+        assert_eq!(tx.serialize_hex(), tx.as_legacy().serialize_hex());
 
         let prevout_script_hex = "160014758ce550380d964051086798d6546bebdca27a73";
         let prevout_script = Script::deserialize_hex(prevout_script_hex).unwrap();
@@ -505,11 +898,11 @@ mod tests {
         assert_eq!(tx.witness_sighash(&args).unwrap(), all_anyonecanpay);
 
         args.sighash_flag = Sighash::Single;
-        assert_eq!(tx.sighash(&args).unwrap(), single);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single[..]);
         assert_eq!(tx.witness_sighash(&args).unwrap(), single);
 
         args.sighash_flag = Sighash::SingleAcp;
-        assert_eq!(tx.sighash(&args).unwrap(), single_anyonecanpay);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single_anyonecanpay[..]);
         assert_eq!(tx.witness_sighash(&args).unwrap(), single_anyonecanpay);
     }
 
@@ -562,10 +955,10 @@ mod tests {
         assert_eq!(tx.sighash(&args).unwrap(), all_anyonecanpay);
 
         args.sighash_flag = Sighash::Single;
-        assert_eq!(tx.sighash(&args).unwrap(), single);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single[..]);
 
         args.sighash_flag = Sighash::SingleAcp;
-        assert_eq!(tx.sighash(&args).unwrap(), single_anyonecanpay);
+        assert_eq!(tx.sighash(&args).unwrap()[..], single_anyonecanpay[..]);
     }
 
     #[test]
@@ -582,6 +975,131 @@ mod tests {
         assert_eq!(tx.wtxid(), wtxid);
     }
 
+    #[test]
+    fn it_calculates_txids_and_wtxids() {
+        // from mainnet: 3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a
+        let tx_hex = "01000000000101b77bebb3ac480e99c0d95a4c812137b116e65e2f3b3a66a36d0e252928d460180100000000ffffffff03982457000000000017a91417b8e0f150215cc70bf2fb58070041d655b162dd8740e133000000000017a9142535e444f7d55f0500c1f86609d6cfc289576b698747abfb0100000000220020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d040047304402205c6a889efa26955bef7ce2b08792e63e25eac9859080f0d83912b0ea833d7eb402205f859f4640f1600db5012b467ec05bb4ae1779640c1b5fadc8908960740e52b30147304402201c239ea25cfeadfa9493a1b0d136d70f50f821385972b7188c4329c2bf2d23a302201ee790e4b6794af6567f85a226a387d5b0222c3dc90d2fc558d09e08062b8271016952210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae00000000";
+        let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+        assert_eq!(
+            tx.txid().to_be_hex(),
+            "3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a"
+        );
+        assert_eq!(
+            tx.wtxid().to_be_hex(),
+            "4935e6dcfc4b254be1bab70539a47a12b9d06e9a371fb12b078e722ce85cd884"
+        );
+        // internal byte order is the reverse of the display order
+        assert_eq!(
+            tx.wtxid().serialize_hex(),
+            "84d85ce82c728e072bb11f379a6ed0b9127aa43905b7bae14b254bfcdce63549"
+        );
+
+        let legacy = BitcoinTx::Legacy(tx.as_legacy().clone());
+        assert_eq!(legacy.txid(), tx.txid());
+        assert_eq!(legacy.wtxid().as_slice(), legacy.txid().as_slice());
+    }
+
+    #[test]
+    fn it_serializes_legacy_and_witness_segments() {
+        // from mainnet: 3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a
+        let tx_hex = "01000000000101b77bebb3ac480e99c0d95a4c812137b116e65e2f3b3a66a36d0e252928d460180100000000ffffffff03982457000000000017a91417b8e0f150215cc70bf2fb58070041d655b162dd8740e133000000000017a9142535e444f7d55f0500c1f86609d6cfc289576b698747abfb0100000000220020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d040047304402205c6a889efa26955bef7ce2b08792e63e25eac9859080f0d83912b0ea833d7eb402205f859f4640f1600db5012b467ec05bb4ae1779640c1b5fadc8908960740e52b30147304402201c239ea25cfeadfa9493a1b0d136d70f50f821385972b7188c4329c2bf2d23a302201ee790e4b6794af6567f85a226a387d5b0222c3dc90d2fc558d09e08062b8271016952210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae00000000";
+        let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
+        let (legacy, witness) = tx.serialize_segments();
+
+        let txid: TXID = Hash256::digest_marked(&legacy);
+        assert_eq!(txid, tx.txid());
+        assert_eq!(
+            txid.to_be_hex(),
+            "3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a"
+        );
+
+        let locktime = legacy.len() - 4;
+        let mut full = legacy[..4].to_vec();
+        full.extend([0x00, 0x01]);
+        full.extend(&legacy[4..locktime]);
+        full.extend(&witness);
+        full.extend(&legacy[locktime..]);
+        assert_eq!(hex::encode(full), tx_hex);
+
+        let (legacy_only, no_witness) = tx.as_legacy().serialize_segments();
+        assert_eq!(legacy_only, legacy);
+        assert!(no_witness.is_empty());
+    }
+
+    #[test]
+    fn it_calculates_weight_and_vsize() {
+        // from mainnet: 3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a
+        let tx_hex = "01000000000101b77bebb3ac480e99c0d95a4c812137b116e65e2f3b3a66a36d0e252928d460180100000000ffffffff03982457000000000017a91417b8e0f150215cc70bf2fb58070041d655b162dd8740e133000000000017a9142535e444f7d55f0500c1f86609d6cfc289576b698747abfb0100000000220020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d040047304402205c6a889efa26955bef7ce2b08792e63e25eac9859080f0d83912b0ea833d7eb402205f859f4640f1600db5012b467ec05bb4ae1779640c1b5fadc8908960740e52b30147304402201c239ea25cfeadfa9493a1b0d136d70f50f821385972b7188c4329c2bf2d23a302201ee790e4b6794af6567f85a226a387d5b0222c3dc90d2fc558d09e08062b8271016952210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae00000000";
+        let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+        assert_eq!(tx.size(), 412);
+        assert_eq!(tx.weight(), 886);
+        assert_eq!(tx.vsize(), 222);
+
+        let legacy = tx.as_legacy();
+        assert_eq!(legacy.size(), 158);
+        assert_eq!(legacy.weight(), 4 * 158);
+        assert_eq!(legacy.vsize(), legacy.size());
+
+        // 1-input 1-output P2WPKH: 82 legacy bytes, plus the marker, flag and a 108-byte witness
+        let tx = TestWallet::new().signed_tx(100_000, 1_000);
+        assert_eq!(tx.as_legacy().size(), 82);
+        assert_eq!(tx.size(), 192);
+        assert_eq!(tx.weight(), 438);
+        assert_eq!(tx.vsize(), 110);
+    }
+
+    #[test]
+    fn it_calculates_legacy_sighash_none_and_the_single_bug() {
+        // mainnet tx 452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03
+        let tx_hex = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+        let tx = LegacyTx::deserialize_hex(tx_hex).unwrap();
+        let prevout_script =
+            Script::deserialize_hex("17a91424d6008f143af0cca57344069c46661aa4fcea2387").unwrap();
+
+        let none = hex::decode("d2e915d936dce4f920b764ac584b892724ccfee115e2ff2c8ad21effaf5f4c9b")
+            .unwrap();
+        let none_anyonecanpay =
+            hex::decode("70e0a019d46e16b74abdad32c6084bab38d077c6bc91d57fc3e1e957745289d7")
+                .unwrap();
+
+        let mut args = LegacySighashArgs {
+            index: 0,
+            sighash_flag: Sighash::None,
+            prevout_script,
+        };
+        assert_eq!(tx.sighash(&args).unwrap()[..], none[..]);
+
+        args.sighash_flag = Sighash::NoneAcp;
+        assert_eq!(tx.sighash(&args).unwrap()[..], none_anyonecanpay[..]);
+
+        // 2 inputs and 1 output. Signing input 1 with SIGHASH_SINGLE hits the bug
+        let tx = LegacyTx::deserialize_hex("0200000002ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f187cafd0700").unwrap();
+        let mut one = [0u8; 32];
+        one[0] = 1;
+
+        args.index = 1;
+        for flag in [Sighash::Single, Sighash::SingleAcp].iter() {
+            args.sighash_flag = *flag;
+            assert_eq!(tx.sighash(&args).unwrap()[..], one[..]);
+            assert_eq!(
+                WitnessTx::from_legacy(tx.clone())
+                    .legacy_sighash(&args)
+                    .unwrap()[..],
+                one[..]
+            );
+            match tx.write_sighash_preimage(&mut vec![], &args) {
+                Err(TxError::SighashSingleBug) => {}
+                e => panic!("expected sighash single bug, got {:?}", e),
+            }
+        }
+
+        args.index = 2;
+        match tx.sighash(&args) {
+            Err(TxError::NoSuchInput(2)) => {}
+            e => panic!("expected no such input, got {:?}", e),
+        }
+    }
+
     #[test]
     fn it_rejects_sighash_none() {
         let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
@@ -601,20 +1119,33 @@ mod tests {
     }
 
     #[test]
-    fn it_rejects_sighash_single_bug() {
+    fn it_commits_to_no_output_for_out_of_range_witness_sighash_single() {
         let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
         let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
 
-        let args = WitnessSighashArgs {
+        // BIP143 hashes a zero `hash_outputs`
+        let single =
+            hex::decode("c18576a80e0a583ae8b643b2a99e8dde8a742edb1573aa4eccbd176adc64f8fa")
+                .unwrap();
+        let single_anyonecanpay =
+            hex::decode("21963908061e647bed2189a5158d42e0fbe67929e4fd2132448e7e8a686140a2")
+                .unwrap();
+
+        let mut args = WitnessSighashArgs {
             index: 1,
             sighash_flag: Sighash::Single,
             prevout_script: vec![].into(),
             prevout_value: 120000,
         };
+        assert_eq!(tx.sighash(&args).unwrap()[..], single[..]);
+
+        args.sighash_flag = Sighash::SingleAcp;
+        assert_eq!(tx.sighash(&args).unwrap()[..], single_anyonecanpay[..]);
 
+        args.index = 2;
         match tx.sighash(&args) {
-            Err(TxError::SighashSingleBug) => {}
-            _ => panic!("expected sighash single bug unsupported"),
+            Err(TxError::NoSuchInput(2)) => {}
+            e => panic!("expected no such input, got {:?}", e),
         }
     }
 
@@ -624,7 +1155,9 @@ mod tests {
         let tx_hex = "01000000000101813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac0019430600";
         let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
         assert_eq!(tx.as_legacy().clone().into_witness(), tx);
-        assert_eq!(tx.serialized_length(), tx_hex.len() / 2);
+        // the tx has no witness data, so it re-serializes in the legacy format
+        assert_eq!(tx.serialized_length(), tx.as_legacy().serialized_length());
+        assert_eq!(tx.serialize_hex(), tx.as_legacy().serialize_hex());
 
         let prevout_script_hex = "17a91424d6008f143af0cca57344069c46661aa4fcea2387";
         let prevout_script = Script::deserialize_hex(prevout_script_hex).unwrap();
@@ -671,6 +1204,230 @@ mod tests {
         assert_eq!(tx.legacy_sighash(&args).unwrap(), single_anyonecanpay);
     }
 
+    #[test]
+    fn it_chains_outpoints_into_spending_txns() {
+        let address = test_address();
+        let funding_tx = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend(BitcoinOutpoint::default(), 0xffff_fffd)
+            .pay(100_000, &address)
+            .pay(50_000, &address)
+            .build()
+            .unwrap();
+
+        assert_eq!(funding_tx.outpoint_at(2), None);
+
+        let outpoint = funding_tx.outpoint_at(1).unwrap();
+        assert_eq!(outpoint.txid, funding_tx.txid());
+        assert_eq!(outpoint.idx, 1);
+
+        let spend_tx = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend(outpoint, 0xffff_fffd)
+            .pay(40_000, &address)
+            .build()
+            .unwrap();
+
+        let prevout = funding_tx
+            .txout_from_outpoint(&spend_tx.inputs()[0].outpoint)
+            .unwrap();
+        assert_eq!(prevout.value, 50_000);
+    }
+
+    #[test]
+    fn it_checks_for_complete_signatures() {
+        let sig = WitnessStackItem::new(hex::decode("3045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01").unwrap());
+        let pubkey = WitnessStackItem::new(
+            hex::decode("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a")
+                .unwrap(),
+        );
+        let wpkh_prevout = TxOut::new(
+            100_000,
+            hex::decode("0014758ce550380d964051086798d6546bebdca27a73").unwrap(),
+        );
+
+        let multisig_script = Script::new(hex::decode("52210205c8897fd0ff5644adba4545a84020cd6aa94d90e1e0a56bb4b8eb7522e3ef8c2102756de182c5dd4b717ea87e693006da62dbb3cddaa4a5cad2ed1f5bbab755f0f52102d27a781fd1b3ec5ba5017ca55b9b900fde598459a0204597b37e6c66a0e35c9853ae").unwrap());
+        let wsh_prevout = TxOut::new(100_000, ScriptPubkey::p2wsh(&multisig_script));
+        let witness_script = WitnessStackItem::from(&multisig_script);
+
+        let unsigned = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend(BitcoinOutpoint::default(), 0xffff_fffd)
+            .pay_script_pubkey(90_000, wpkh_prevout.script_pubkey.clone());
+
+        let cases = [
+            (vec![vec![sig.clone(), pubkey.clone()]], &wpkh_prevout, true),
+            (vec![vec![sig.clone()]], &wpkh_prevout, false),
+            (vec![vec![]], &wpkh_prevout, false),
+            (
+                vec![vec![
+                    WitnessStackItem::null(),
+                    sig.clone(),
+                    sig.clone(),
+                    witness_script.clone(),
+                ]],
+                &wsh_prevout,
+                true,
+            ),
+            (
+                vec![vec![
+                    WitnessStackItem::null(),
+                    sig.clone(),
+                    witness_script.clone(),
+                ]],
+                &wsh_prevout,
+                false,
+            ),
+            (
+                vec![vec![
+                    WitnessStackItem::null(),
+                    sig.clone(),
+                    WitnessStackItem::null(),
+                    witness_script.clone(),
+                ]],
+                &wsh_prevout,
+                false,
+            ),
+        ];
+        for case in cases.iter() {
+            let tx = unsigned
+                .clone()
+                .extend_witnesses(case.0.clone())
+                .build()
+                .unwrap();
+            assert_eq!(tx.is_fully_signed(std::slice::from_ref(case.1)), case.2);
+        }
+
+        // missing witnesses and mismatched prevouts are never signed
+        let tx = unsigned.build().unwrap();
+        assert!(!tx.is_fully_signed(std::slice::from_ref(&wpkh_prevout)));
+        assert!(!tx.is_fully_signed(&[]));
+    }
+
+    #[test]
+    fn it_checks_transaction_finality() {
+        let outpoint = BitcoinOutpoint::new([1; 32].into(), 0);
+        let tx = |locktime: u32, sequence: u32| {
+            LegacyTx::new(
+                2,
+                vec![BitcoinTxIn::new(outpoint, vec![], sequence)],
+                vec![TxOut::default()],
+                locktime,
+            )
+            .unwrap()
+        };
+        let height = 800_000;
+        let mtp = 1_700_000_000;
+
+        // unlocked
+        assert!(tx(0, 0xffff_fffd).is_final(height, mtp));
+        // locked to a future height, or to the next block's height
+        assert!(!tx(800_001, 0xffff_fffd).is_final(height, mtp));
+        assert!(!tx(height, 0xffff_fffd).is_final(height, mtp));
+        // locked to a past height
+        assert!(tx(799_999, 0xffff_fffd).is_final(height, mtp));
+        // locked to a future or past time
+        assert!(!tx(mtp + 1, 0xffff_fffd).is_final(height, mtp));
+        assert!(tx(mtp - 1, 0xffff_fffd).is_final(height, mtp));
+        // future locktime, but every sequence is final
+        assert!(tx(800_001, SEQUENCE_FINAL).is_final(height, mtp));
+        assert!(tx(mtp + 1, SEQUENCE_FINAL).is_final(height, mtp));
+    }
+
+    #[test]
+    fn it_lists_prevout_requirements() {
+        let sig = vec![0x30; 72];
+        let pubkey = vec![0x02; 33];
+        let mut legacy_script_sig = vec![72];
+        legacy_script_sig.extend(&sig);
+        legacy_script_sig.push(33);
+        legacy_script_sig.extend(&pubkey);
+        let mut nested_script_sig = vec![22, 0x00, 0x14];
+        nested_script_sig.extend(&[0x11; 20]);
+
+        let outpoints = (0..4u8)
+            .map(|i| BitcoinOutpoint::new([i; 32].into(), i as u32))
+            .collect::<Vec<_>>();
+        let vin = vec![
+            // signed legacy
+            BitcoinTxIn::new(outpoints[0], legacy_script_sig, 0xffff_ffff),
+            // signed native witness
+            BitcoinTxIn::new(outpoints[1], vec![], 0xffff_ffff),
+            // unsigned nested witness
+            BitcoinTxIn::new(outpoints[2], nested_script_sig, 0xffff_ffff),
+            // unsigned, unknown
+            BitcoinTxIn::new(outpoints[3], vec![], 0xffff_ffff),
+        ];
+        let vout = vec![TxOut::new(90_000, vec![0x51])];
+        let mut tx = <WitnessTx as Transaction>::new(2, vin.clone(), vout.clone(), 0).unwrap();
+        tx.witnesses[1] = vec![WitnessStackItem::new(sig), WitnessStackItem::new(pubkey)];
+
+        let requirements = BitcoinTx::from(tx).prevout_requirements();
+        let needs_value = requirements
+            .iter()
+            .map(|r| r.needs_value)
+            .collect::<Vec<_>>();
+        assert_eq!(needs_value, vec![false, true, true, true]);
+        assert_eq!(
+            requirements.iter().map(|r| r.outpoint).collect::<Vec<_>>(),
+            outpoints
+        );
+
+        let legacy = <LegacyTx as Transaction>::new(2, vin, vout, 0).unwrap();
+        assert!(legacy.prevout_requirements().iter().all(|r| !r.needs_value));
+    }
+
+    #[test]
+    fn it_distinguishes_full_equality_from_txid_equality() {
+        let address = test_address();
+        let builder = BitcoinMainnet::tx_builder()
+            .version(2)
+            .spend(BitcoinOutpoint::default(), 0xffff_fffd)
+            .pay(100_000, &address);
+
+        let a = builder
+            .clone()
+            .extend_witnesses(vec![vec![WitnessStackItem::new(vec![1])]])
+            .build()
+            .unwrap();
+        let b = builder
+            .extend_witnesses(vec![vec![WitnessStackItem::new(vec![2])]])
+            .build()
+            .unwrap();
+
+        assert_eq!(a.txid(), b.txid());
+        assert_ne!(a, b);
+        assert!(!a.serialized_eq(&b));
+        assert!(a.serialized_eq(&a.clone()));
+    }
+
+    #[test]
+    fn it_validates_tx_hex() {
+        let tx_hex = "02000000000101ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f187024730440220645d5b09a7a00581bad644d36521fa1dc6cd12763ca45f10893cb2e80c1d4d720220505f1f55b9fe2aeba40f68c52c39dcb38cf4be41ec99273a23bdfe58119f7d30012103ba797be9acc7bde9ed4aef2ff6409e4c44559dd127f3e0c03ee2a1da14d9f6b100000000";
+        let len = tx_hex.len() / 2;
+        BitcoinTx::validate_hex(tx_hex).unwrap();
+
+        match BitcoinTx::validate_hex(&format!("{}zz", &tx_hex[..10])) {
+            Err(TxHexError::InvalidCharacter {
+                character: 'z',
+                index: 10,
+            }) => {}
+            e => panic!("expected invalid character error, got {:?}", e),
+        }
+        match BitcoinTx::validate_hex(&tx_hex[1..]) {
+            Err(TxHexError::OddLength) => {}
+            e => panic!("expected odd length error, got {:?}", e),
+        }
+        match BitcoinTx::validate_hex(&tx_hex[..tx_hex.len() - 4]) {
+            Err(TxHexError::Truncated { offset }) => assert_eq!(offset, len - 2),
+            e => panic!("expected truncation error, got {:?}", e),
+        }
+        match BitcoinTx::validate_hex(&format!("{}0000", tx_hex)) {
+            Err(TxHexError::TrailingBytes { offset }) => assert_eq!(offset, len),
+            e => panic!("expected trailing bytes error, got {:?}", e),
+        }
+    }
+
     #[test]
     fn it_gets_sighash_flags_from_u8s() {
         let cases = [
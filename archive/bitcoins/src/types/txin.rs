@@ -8,7 +8,10 @@ use coins_core::{
     types::tx::{Input, TxoIdentifier},
 };
 
-use crate::{hashes::TXID, types::script::ScriptSig};
+use crate::{
+    hashes::TXID,
+    types::script::{ScriptSig, WitnessSize},
+};
 /// An Outpoint. This is a unique identifier for a UTXO, and is composed of a transaction ID (in
 /// Bitcoin-style LE format), and the index of the output being spent within that transactions
 /// output vectour (vout).
@@ -102,6 +105,50 @@ where
     }
 }
 
+/// If set in an input's sequence, BIP68 relative locktime semantics are disabled.
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+
+/// If set in an input's sequence, its BIP68 relative locktime is measured in units of 512
+/// seconds. Otherwise it is measured in blocks.
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// The bits of an input's sequence that encode its BIP68 relative locktime value.
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+
+/// A BIP68 relative locktime, as encoded in an input's sequence number. It is enforced for
+/// inputs of version 2+ transactions, and by `OP_CHECKSEQUENCEVERIFY`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum RelativeLockTime {
+    /// The prevout must be buried under this many blocks.
+    Blocks(u16),
+    /// The prevout must be at least this many 512-second intervals old.
+    Time(u16),
+}
+
+impl RelativeLockTime {
+    /// Encode the relative locktime as a sequence number.
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            RelativeLockTime::Blocks(blocks) => blocks as u32,
+            RelativeLockTime::Time(intervals) => SEQUENCE_LOCKTIME_TYPE_FLAG | intervals as u32,
+        }
+    }
+
+    /// Decode the relative locktime from a sequence number. `None` if the sequence has the
+    /// disable flag set.
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (sequence & SEQUENCE_LOCKTIME_MASK) as u16;
+        if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLockTime::Time(value))
+        } else {
+            Some(RelativeLockTime::Blocks(value))
+        }
+    }
+}
+
 /// An TxInput. This data structure contains an outpoint referencing an existing UTXO, a
 /// `script_sig`, which will contain spend authorization information (when spending a Legacy or
 /// Witness-via-P2SH prevout), and a sequence number which may encode relative locktim semantics
@@ -147,10 +194,48 @@ where
         }
     }
 
+    /// Instantiate a new TxInput spending `outpoint`, with an empty script sig and a final
+    /// sequence number of `0xffff_ffff`.
+    pub fn from_outpoint(outpoint: Outpoint<M>) -> Self {
+        Self::new(outpoint, vec![], 0xffff_ffff)
+    }
+
+    /// Instantiate a new TxInput spending `outpoint`, with an empty script sig and the sequence
+    /// number `0xffff_fffd`, which signals replaceability per BIP125.
+    pub fn from_outpoint_rbf(outpoint: Outpoint<M>) -> Self {
+        Self::new(outpoint, vec![], 0xffff_fffd)
+    }
+
+    /// True if the input signals replaceability per BIP125, i.e. its sequence number is less
+    /// than `0xffff_fffe`.
+    pub fn signals_rbf(&self) -> bool {
+        self.sequence < 0xffff_fffe
+    }
+
+    /// The BIP68 relative locktime encoded in the input's sequence number. `None` if the
+    /// sequence disables relative locktime semantics, as final sequences do.
+    pub fn relative_locktime(&self) -> Option<RelativeLockTime> {
+        RelativeLockTime::from_sequence(self.sequence)
+    }
+
     /// Copy the input, stripping the scriptsig information.
     pub fn unsigned(&self) -> TxInput<M> {
         Self::new(self.outpoint, vec![], self.sequence)
     }
+
+    /// The weight of the input in a transaction. The outpoint, script sig and sequence count 4
+    /// weight units per byte. `witness_size` is the serialized size of the input's witness
+    /// (including its item count), which counts 1 weight unit per byte. For unsigned inputs,
+    /// callers may pass the predicted witness size. Pass 0 for inputs without a witness. To
+    /// measure a known witness, use `weight_with_witness`.
+    pub fn weight(&self, witness_size: usize) -> usize {
+        4 * self.serialized_length() + witness_size
+    }
+
+    /// The weight of the input in a transaction, with `witness` as its witness. See `weight`.
+    pub fn weight_with_witness<W: WitnessSize>(&self, witness: &W) -> usize {
+        self.weight(witness.serialized_size())
+    }
 }
 
 impl<M> ByteFormat for TxInput<M>
@@ -202,6 +287,7 @@ pub type Vin = Vec<BitcoinTxIn>;
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::script::Witness;
     use coins_core::ser::ByteFormat;
 
     static NULL_OUTPOINT: &str =
@@ -249,4 +335,64 @@ mod test {
             assert_eq!(BitcoinTxIn::deserialize_hex(&case.1).unwrap(), case.0);
         }
     }
+
+    #[test]
+    fn it_instantiates_inputs_from_outpoints() {
+        let outpoint = Outpoint::new(Default::default(), 3);
+
+        let input = BitcoinTxIn::from_outpoint(outpoint);
+        assert_eq!(input.outpoint, outpoint);
+        assert!(input.script_sig.is_empty());
+        assert_eq!(input.sequence, 0xffff_ffff);
+        assert!(!input.signals_rbf());
+
+        let input = BitcoinTxIn::from_outpoint_rbf(outpoint);
+        assert_eq!(input.outpoint, outpoint);
+        assert!(input.script_sig.is_empty());
+        assert!(input.signals_rbf());
+    }
+
+    #[test]
+    fn it_decodes_relative_locktimes() {
+        let input = BitcoinTxIn::new(Outpoint::null(), vec![], 144);
+        assert_eq!(
+            input.relative_locktime(),
+            Some(RelativeLockTime::Blocks(144))
+        );
+
+        // the type flag, and 0x1234 512-second intervals
+        let input = BitcoinTxIn::new(Outpoint::null(), vec![], 0x0040_1234);
+        assert_eq!(
+            input.relative_locktime(),
+            Some(RelativeLockTime::Time(0x1234))
+        );
+
+        for sequence in [0xffff_ffff, 0xffff_fffd, 0x8000_0090].iter() {
+            let input = BitcoinTxIn::new(Outpoint::null(), vec![], *sequence);
+            assert_eq!(input.relative_locktime(), None);
+        }
+
+        for locktime in [RelativeLockTime::Blocks(6), RelativeLockTime::Time(300)].iter() {
+            assert_eq!(
+                RelativeLockTime::from_sequence(locktime.to_sequence()),
+                Some(*locktime)
+            );
+        }
+    }
+
+    #[test]
+    fn it_calculates_input_weights() {
+        // signed p2wpkh. witness is 2 items: a 72-byte sig and a 33-byte pubkey
+        let input = BitcoinTxIn::new(Outpoint::null(), vec![], 0xffff_fffd);
+        let witness_size = 1 + (1 + 72) + (1 + 33);
+        assert_eq!(input.weight(witness_size), 272);
+        assert_eq!(input.weight(0), 164);
+        let witness: Witness = vec![vec![0u8; 72].into(), vec![0u8; 33].into()];
+        assert_eq!(input.weight_with_witness(&witness), 272);
+        assert_eq!(input.weight_with_witness(&Witness::default()), 165);
+
+        // signed p2pkh. script_sig is the same sig and pubkey
+        let input = BitcoinTxIn::new(Outpoint::null(), vec![0u8; 107], 0xffff_fffd);
+        assert_eq!(input.weight(0), 592);
+    }
 }
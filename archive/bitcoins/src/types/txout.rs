@@ -15,7 +15,10 @@ use crate::types::script::{ScriptPubkey, ScriptType};
 /// `TxOut::null()` and `TxOut::default()` return the "null" TxOut, which has a value of
 /// 0xffff_ffff_ffff_ffff, and an empty `script_pubkey`. This null output is used within legacy
 /// sighash calculations.
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
+///
+/// TxOuts are ordered according to BIP69: ascending by value, then lexicographically by
+/// `script_pubkey` bytes.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub struct TxOut {
     /// The value of the output in satoshis
     pub value: u64,
@@ -76,6 +79,34 @@ impl TxOut {
     pub fn extract_op_return_data(&self) -> Option<Vec<u8>> {
         self.script_pubkey.extract_op_return_data()
     }
+
+    /// The weight of the output in a transaction: `4 * (8 + compact_size_len(script_len) +
+    /// script_len)`. Outputs are never witness-discounted.
+    pub fn weight(&self) -> usize {
+        4 * self.serialized_length()
+    }
+
+    /// The minimum value for this output to be relayed under Bitcoin Core's default dust relay
+    /// fee of 3 sat/vbyte. This is the fee to create and later spend the output. OP_RETURN
+    /// outputs are unspendable, and have no dust threshold.
+    pub fn dust_threshold(&self) -> u64 {
+        if !self.script_pubkey.is_empty() && self.script_pubkey[0] == 0x6a {
+            return 0;
+        }
+        // outpoint, script_sig length, sequence, and a 107-byte signature and pubkey. Witness
+        // spends discount the signature and pubkey.
+        let spend_size = if self.script_pubkey.is_witness_program() {
+            32 + 4 + 1 + 107 / 4 + 4
+        } else {
+            32 + 4 + 1 + 107 + 4
+        };
+        3 * (self.serialized_length() + spend_size) as u64
+    }
+
+    /// True if the output's value is below its dust threshold.
+    pub fn is_dust(&self) -> bool {
+        self.value < self.dust_threshold()
+    }
 }
 
 impl ByteFormat for TxOut {
@@ -130,4 +161,86 @@ mod test {
             assert_eq!(TxOut::deserialize_hex(case.1).unwrap(), case.0);
         }
     }
+
+    #[test]
+    fn it_calculates_output_weights() {
+        let cases = [
+            // p2wpkh
+            ("160014758ce550380d964051086798d6546bebdca27a73", 124),
+            // p2pkh
+            ("1976a91424d6008f143af0cca57344069c46661aa4fcea2388ac", 136),
+            // p2wsh
+            (
+                "2200200c27bbbaec5619320554ca7e733024266783807e12de9957cdb7e4f59838ae5e",
+                172,
+            ),
+        ];
+        for case in cases.iter() {
+            let output = TxOut::new(1000, ScriptPubkey::deserialize_hex(case.0).unwrap());
+            assert_eq!(output.weight(), case.1);
+        }
+    }
+
+    #[test]
+    fn it_calculates_dust_thresholds() {
+        let cases = [
+            // p2pkh
+            ("1976a91424d6008f143af0cca57344069c46661aa4fcea2388ac", 546),
+            // p2wpkh
+            ("160014758ce550380d964051086798d6546bebdca27a73", 294),
+            // p2wsh
+            (
+                "2200200c27bbbaec5619320554ca7e733024266783807e12de9957cdb7e4f59838ae5e",
+                330,
+            ),
+            // op_return
+            ("036a0100", 0),
+        ];
+        for case in cases.iter() {
+            let script_pubkey = ScriptPubkey::deserialize_hex(case.0).unwrap();
+            let output = TxOut::new(case.1, script_pubkey.clone());
+            assert_eq!(output.dust_threshold(), case.1);
+            assert!(!output.is_dust());
+            if case.1 > 0 {
+                assert!(TxOut::new(case.1 - 1, script_pubkey).is_dust());
+            }
+        }
+    }
+
+    #[test]
+    fn it_sorts_outputs_by_bip69() {
+        let p2pkh_a = ScriptPubkey::new(
+            hex::decode("76a9144a5fba237213a062f6f57978f796390bdcf8d01588ac").unwrap(),
+        );
+        let p2pkh_b = ScriptPubkey::new(
+            hex::decode("76a9145be32612930b8323add2212a4ec03c1562084f8488ac").unwrap(),
+        );
+        let p2wpkh =
+            ScriptPubkey::new(hex::decode("0014758ce550380d964051086798d6546bebdca27a73").unwrap());
+
+        // scripts compare by byte content, with prefixes first
+        assert!(p2wpkh < p2pkh_a);
+        assert!(p2pkh_a < p2pkh_b);
+        assert!(ScriptPubkey::new(vec![0x76]) < p2pkh_a);
+
+        // The outputs of BIP69 test transaction
+        // 28204cad1d7fc1d199e8ef4fa22f182de6258a3eaafe1bbe56ebdcacd3069a5f, with equal-valued
+        // outputs added to exercise the script tiebreak
+        let mut outputs = vec![
+            TxOut::new(40_000_000_000, p2pkh_b.clone()),
+            TxOut::new(400_057_456, p2pkh_b.clone()),
+            TxOut::new(400_057_456, p2pkh_a.clone()),
+            TxOut::new(400_057_456, p2wpkh.clone()),
+        ];
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            vec![
+                TxOut::new(400_057_456, p2wpkh),
+                TxOut::new(400_057_456, p2pkh_a),
+                TxOut::new(400_057_456, p2pkh_b.clone()),
+                TxOut::new(40_000_000_000, p2pkh_b),
+            ]
+        );
+    }
 }
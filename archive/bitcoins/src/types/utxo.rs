@@ -9,8 +9,8 @@
 //! This functionality does NOT currently support nested witness-via-p2sh prevouts. If you' like
 //! to use those, you'll need a processing step in your tx signer.
 use crate::types::{
-    BitcoinOutpoint, BitcoinTransaction, LegacySighashArgs, Script, ScriptPubkey, ScriptType,
-    Sighash, TxOut, WitnessSighashArgs,
+    BitcoinOutpoint, BitcoinTransaction, BitcoinTxIn, LegacySighashArgs, Script, ScriptPubkey,
+    ScriptType, Sighash, TxOut, WitnessSighashArgs,
 };
 use coins_core::hashes::{Digest, Hash160, MarkedDigest, MarkedDigestOutput, Sha256};
 use serde::{Deserialize, Serialize};
@@ -37,6 +37,38 @@ impl SpendScript {
     }
 }
 
+/// The kind of a standard single-key input, used to predict its signed weight.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum InputKind {
+    /// Spends a legacy Pay to Pubkeyhash output
+    Pkh,
+    /// Spends a Pay to Witness Pubkeyhash output nested in Pay to Scripthash
+    ShWpkh,
+    /// Spends a Pay to Witness Pubkeyhash output
+    Wpkh,
+}
+
+impl InputKind {
+    /// True if the input requires a witness.
+    pub fn is_witness(&self) -> bool {
+        !matches!(self, InputKind::Pkh)
+    }
+
+    /// Predict the weight of a signed input of this kind, assuming 72-byte signatures and
+    /// compressed pubkeys. The weight includes the witness, but not the segwit marker and flag.
+    pub fn predicted_weight(&self) -> usize {
+        let outpoint = BitcoinOutpoint::default();
+        match self {
+            // script_sig: (PUSH_72 <sig> PUSH_33 <pubkey>)
+            InputKind::Pkh => BitcoinTxIn::new(outpoint, vec![0u8; 107], 0).weight(0),
+            // script_sig: (PUSH_22 <wpkh script>). witness: (2 <sig> <pubkey>)
+            InputKind::ShWpkh => BitcoinTxIn::new(outpoint, vec![0u8; 23], 0).weight(1 + 73 + 34),
+            // empty script_sig. witness: (2 <sig> <pubkey>)
+            InputKind::Wpkh => BitcoinTxIn::new(outpoint, vec![], 0).weight(1 + 73 + 34),
+        }
+    }
+}
+
 /// Information necessary to spend an output.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Utxo {
@@ -135,6 +167,35 @@ impl Utxo {
         self.script_pubkey.standard_type()
     }
 
+    /// True if spending this UTXO requires a witness.
+    pub fn is_witness(&self) -> bool {
+        matches!(
+            self.standard_type(),
+            ScriptType::Wpkh(_) | ScriptType::Wsh(_)
+        )
+    }
+
+    /// Predict the weight of a signed input spending this UTXO, assuming 72-byte signatures and
+    /// compressed pubkeys. The weight includes the witness, but not the segwit marker and flag.
+    /// Returns `None` if the spend can't be predicted. At the moment, only PKH and WPKH are
+    /// supported.
+    pub fn predicted_input_weight(&self) -> Option<usize> {
+        match self.standard_type() {
+            ScriptType::Pkh(_) => Some(InputKind::Pkh.predicted_weight()),
+            ScriptType::Wpkh(_) => Some(InputKind::Wpkh.predicted_weight()),
+            _ => None,
+        }
+    }
+
+    /// The value of this UTXO less the fee to spend it at `fee_rate` sat/vbyte, using its
+    /// predicted input weight. This is negative if spending the UTXO costs more than it is worth.
+    /// Returns `None` if the input weight can't be predicted.
+    pub fn effective_value(&self, fee_rate: u64) -> Option<i64> {
+        let input_vsize = self.predicted_input_weight()?.div_ceil(4) as i128;
+        let effective_value = self.value as i128 - input_vsize * fee_rate as i128;
+        Some(effective_value.clamp(i64::MIN.into(), i64::MAX.into()) as i64)
+    }
+
     /// Attempts to set the script. Returns true if succesful, false otherwise. Before setting, we
     /// check that the provided script's hash matches the payload of the script pubkey. As such,
     /// this will always fail for UTXOs with PKH or WPKH script pubkeys.
@@ -147,7 +208,7 @@ impl Utxo {
                 }
             }
             ScriptType::Wsh(data) => {
-                if data.as_slice() == Sha256::digest(script.as_ref()).as_slice() {
+                if data.as_slice() == &Sha256::digest(script.as_ref())[..] {
                     self.spend_script = SpendScript::Known(script);
                     return true;
                 }
@@ -2,9 +2,13 @@
 
 use std::io::{Read, Write};
 
+use coins_bip32::k256::ecdsa::{signature::DigestSigner, Signature, SigningKey};
 use coins_core::{
-    hashes::{Digest, DigestOutput, Hash256, Hash256Digest, MarkedDigest, MarkedDigestOutput},
-    ser::{self, ByteFormat},
+    hashes::{
+        Digest, DigestOutput, Hash160, Hash160Digest, Hash256, Hash256Digest, MarkedDigest,
+        MarkedDigestOutput, Sha256, TaggedHash,
+    },
+    ser::{self, ByteFormat, SerError},
     types::tx::Transaction,
 };
 
@@ -12,9 +16,13 @@ use crate::{
     hashes::{TXID, WTXID},
     types::{
         legacy::*,
-        script::{Script, Witness},
+        script::{
+            push_minimal, Script, ScriptPubkey, ScriptSig, ScriptType, Witness, WitnessSize,
+            WitnessStackItem,
+        },
+        taproot::{taproot_spend_type_byte, TapSighashType, TAPROOT_SIGHASH_EPOCH},
         tx::*,
-        txin::BitcoinTxIn,
+        txin::{BitcoinOutpoint, BitcoinTxIn, Vin},
         txout::TxOut,
     },
 };
@@ -106,7 +114,8 @@ pub trait WitnessTransaction: BitcoinTransaction {
 ///
 /// SIGHASH_SINGLE commits specifically the the output at the same index as the input being
 /// signed. If there is no output at that index, (because, e.g. the input vector is longer than
-/// the output vector) it behaves insecurely, and we do not implement that protocol bug.
+/// the output vector) BIP143 commits to no outputs, and anyone may choose the outputs. Unlike
+/// the legacy sighash, the signature still commits to the inputs.
 ///
 /// SIGHASH_SINGLE + ANYONECANPAY commits to ONE input and ONE output. It indicates that anyone
 /// may add additional value to the transaction, and route value to any other location. The
@@ -135,6 +144,42 @@ pub struct WitnessSighashArgs {
     pub prevout_value: u64,
 }
 
+/// How an input spends its prevout, carrying exactly the scripts needed to compute its sighash.
+/// Passed to `WitnessTx::spend_sighash` so that the caller declares the spend type, rather than
+/// having it guessed from the prevout.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SpendInfo {
+    /// A bare script pubkey, e.g. P2PKH or bare multisig. The script pubkey is signed with the
+    /// legacy sighash.
+    Bare(Script),
+    /// A P2SH spend. The redeem script is signed with the legacy sighash.
+    P2sh(Script),
+    /// A native P2WSH spend. The witness script is signed with the BIP143 sighash.
+    P2wsh(Script),
+    /// A P2WSH spend nested in P2SH. The witness script is signed with the BIP143 sighash.
+    P2shP2wsh(Script),
+    /// A Taproot spend. `prevouts` must contain the output spent by each input, in input order.
+    /// `leaf_hash` is the tapleaf hash for script path spends, and `None` for key path spends.
+    ///
+    /// # Note
+    ///
+    /// `spend_sighash` signs this with the explicit sighash flag it is given, and without an
+    /// annex. For `TapSighashType::Default`, or to commit to an annex, use `taproot_sighash`.
+    Taproot {
+        /// The outputs spent by each input of the transaction
+        prevouts: Vec<TxOut>,
+        /// The tapleaf hash of the script being executed, if any
+        leaf_hash: Option<[u8; 32]>,
+    },
+}
+
+impl SpendInfo {
+    /// True if the spend is signed with the BIP143 sighash. False otherwise.
+    pub fn is_witness(&self) -> bool {
+        matches!(self, SpendInfo::P2wsh(_) | SpendInfo::P2shP2wsh(_))
+    }
+}
+
 /// A witness transaction. Any transaction that contains 1 or more witnesses.
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Default)]
 pub struct WitnessTx {
@@ -197,15 +242,199 @@ impl WitnessTx {
                 }
                 Ok(w.finalize_marked())
             }
-            Sighash::Single | Sighash::SingleAcp => {
-                let mut w = Hash256::default();
-                self.legacy_tx.vout[index].write_to(&mut w)?;
-                Ok(w.finalize_marked())
-            }
+            // BIP143 commits to no output if the input has no matching output. Unlike the legacy
+            // sighash, this is not a bug, and the sighash still commits to the rest of the tx.
+            Sighash::Single | Sighash::SingleAcp => match self.legacy_tx.vout.get(index) {
+                Some(output) => {
+                    let mut w = Hash256::default();
+                    output.write_to(&mut w)?;
+                    Ok(w.finalize_marked())
+                }
+                None => Ok(Hash256Digest::default()),
+            },
             _ => Ok(Hash256Digest::default()),
         }
     }
 
+    /// Write the BIP143 sighash preimage to `writer`. If a `cache` is provided, its precomputed
+    /// hashes are used wherever the sighash flag commits to them.
+    fn write_bip143_preimage<W: Write>(
+        &self,
+        writer: &mut W,
+        args: &WitnessSighashArgs,
+        cache: Option<&SighashCache>,
+    ) -> TxResult<()> {
+        if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
+            return Err(TxError::NoneUnsupported);
+        }
+
+        let input = self
+            .legacy_tx
+            .vin
+            .get(args.index)
+            .ok_or(TxError::NoSuchInput(args.index))?;
+
+        let hash_prevouts = match cache {
+            Some(cache) if args.sighash_flag as u8 & 0x80 == 0 => cache.hash_prevouts,
+            _ => self.hash_prevouts(args.sighash_flag)?,
+        };
+        let hash_sequence = match cache {
+            Some(cache) if args.sighash_flag == Sighash::All => cache.hash_sequence,
+            _ => self.hash_sequence(args.sighash_flag)?,
+        };
+        let hash_outputs = match (cache, args.sighash_flag) {
+            (Some(cache), Sighash::All | Sighash::AllAcp) => cache.hash_outputs,
+            _ => self.hash_outputs(args.index, args.sighash_flag)?,
+        };
+
+        ser::write_u32_le(writer, self.legacy_tx.version)?;
+        hash_prevouts.write_to(writer)?;
+        hash_sequence.write_to(writer)?;
+        input.outpoint.write_to(writer)?;
+        args.prevout_script.write_to(writer)?;
+        ser::write_u64_le(writer, args.prevout_value)?;
+        ser::write_u32_le(writer, input.sequence)?;
+        hash_outputs.write_to(writer)?;
+        ser::write_u32_le(writer, self.legacy_tx.locktime)?;
+        ser::write_u32_le(writer, args.sighash_flag as u32)?;
+        Ok(())
+    }
+
+    /// Calculates the sighash of the input at `index`, which spends a prevout worth
+    /// `prevout_value` as described by `spend`. Legacy spends use the legacy sighash, and ignore
+    /// `prevout_value`. Witness spends use the BIP143 sighash, with the witness script as the
+    /// script code. Taproot spends use the BIP341 sighash, and read values from their prevouts.
+    pub fn spend_sighash(
+        &self,
+        index: usize,
+        flag: Sighash,
+        prevout_value: u64,
+        spend: &SpendInfo,
+    ) -> TxResult<Hash256Digest> {
+        let mut w = Hash256::default();
+        match spend {
+            SpendInfo::Bare(script) | SpendInfo::P2sh(script) => {
+                let args = LegacySighashArgs {
+                    index,
+                    sighash_flag: flag,
+                    prevout_script: script.clone(),
+                };
+                return Ok(self.legacy_sighash(&args)?.into());
+            }
+            SpendInfo::P2wsh(script) | SpendInfo::P2shP2wsh(script) => {
+                let args = WitnessSighashArgs {
+                    index,
+                    sighash_flag: flag,
+                    prevout_script: script.clone(),
+                    prevout_value,
+                };
+                self.write_witness_sighash_preimage(&mut w, &args)?;
+            }
+            SpendInfo::Taproot {
+                prevouts,
+                leaf_hash,
+            } => {
+                return Ok(self
+                    .taproot_sighash(index, prevouts, flag.into(), *leaf_hash, None)?
+                    .into());
+            }
+        }
+        Ok(w.finalize_marked())
+    }
+
+    /// Calculates the BIP341 sighash of the input at `index`. `prevouts` must contain the output
+    /// spent by each input, in input order. `leaf_hash` is the tapleaf hash of the executed script
+    /// for BIP342 script path spends, and `None` for key path spends. `annex` includes its `0x50`
+    /// prefix. See `TaprootWitness::annex`.
+    ///
+    /// Script path spends are assumed to have executed no `OP_CODESEPARATOR`.
+    ///
+    /// For BIP341 sighash documentation, see here:
+    ///
+    /// - https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki#common-signature-message
+    pub fn taproot_sighash(
+        &self,
+        index: usize,
+        prevouts: &[TxOut],
+        sighash_type: TapSighashType,
+        leaf_hash: Option<[u8; 32]>,
+        annex: Option<&[u8]>,
+    ) -> TxResult<[u8; 32]> {
+        let vin = &self.legacy_tx.vin;
+        let vout = &self.legacy_tx.vout;
+        if prevouts.len() != vin.len() {
+            return Err(TxError::PrevoutCountMismatch {
+                inputs: vin.len(),
+                prevouts: prevouts.len(),
+            });
+        }
+        let input = vin.get(index).ok_or(TxError::NoSuchInput(index))?;
+        let single_output = match sighash_type {
+            TapSighashType::Single | TapSighashType::SingleAcp => {
+                Some(vout.get(index).ok_or(TxError::NoMatchingOutput(index))?)
+            }
+            _ => None,
+        };
+
+        let mut w = TaggedHash::new("TapSighash");
+        w.write_all(&[TAPROOT_SIGHASH_EPOCH, sighash_type.to_u8()])?;
+        ser::write_u32_le(&mut w, self.legacy_tx.version)?;
+        ser::write_u32_le(&mut w, self.legacy_tx.locktime)?;
+
+        if !sighash_type.is_anyone_can_pay() {
+            let (mut outpoints, mut amounts, mut script_pubkeys, mut sequences) =
+                (vec![], vec![], vec![], vec![]);
+            for (input, prevout) in vin.iter().zip(prevouts.iter()) {
+                input.outpoint.write_to(&mut outpoints)?;
+                ser::write_u64_le(&mut amounts, prevout.value)?;
+                prevout.script_pubkey.write_to(&mut script_pubkeys)?;
+                ser::write_u32_le(&mut sequences, input.sequence)?;
+            }
+            for data in [outpoints, amounts, script_pubkeys, sequences].iter() {
+                w.write_all(&Sha256::digest(data))?;
+            }
+        }
+        if sighash_type.commits_to_all_outputs() {
+            let mut outputs = vec![];
+            for output in vout.iter() {
+                output.write_to(&mut outputs)?;
+            }
+            w.write_all(&Sha256::digest(&outputs))?;
+        }
+
+        w.write_all(&[taproot_spend_type_byte(
+            annex.is_some(),
+            leaf_hash.is_some(),
+        )])?;
+        if sighash_type.is_anyone_can_pay() {
+            input.outpoint.write_to(&mut w)?;
+            ser::write_u64_le(&mut w, prevouts[index].value)?;
+            prevouts[index].script_pubkey.write_to(&mut w)?;
+            ser::write_u32_le(&mut w, input.sequence)?;
+        } else {
+            ser::write_u32_le(&mut w, index as u32)?;
+        }
+        if let Some(annex) = annex {
+            let mut data = vec![];
+            ser::write_compact_int(&mut data, annex.len() as u64)?;
+            data.extend(annex);
+            w.write_all(&Sha256::digest(&data))?;
+        }
+        if let Some(output) = single_output {
+            let mut data = vec![];
+            output.write_to(&mut data)?;
+            w.write_all(&Sha256::digest(&data))?;
+        }
+
+        if let Some(leaf_hash) = leaf_hash {
+            w.write_all(&leaf_hash)?;
+            // key_version 0, and no executed OP_CODESEPARATOR
+            w.write_all(&[0x00])?;
+            ser::write_u32_le(&mut w, 0xffff_ffff)?;
+        }
+        Ok(w.finish())
+    }
+
     /// Consumes a `LegacyTx` and instantiates a new `WitnessTx` with empty witnesses
     pub fn from_legacy(legacy_tx: LegacyTx) -> Self {
         let witnesses = (0..legacy_tx.inputs().len())
@@ -216,6 +445,107 @@ impl WitnessTx {
             witnesses,
         }
     }
+
+    /// True if any input has a non-empty witness. Per BIP144, transactions without witness data
+    /// are serialized in the legacy format, without the segwit marker and flag.
+    pub fn has_witness(&self) -> bool {
+        self.witnesses.iter().any(|w| !w.is_empty())
+    }
+
+    /// Repoint the input at `index` to spend `outpoint`. Errors if the input carries a script
+    /// sig or witness, as changing the outpoint would invalidate any signature it contains.
+    pub fn set_input_outpoint(&mut self, index: usize, outpoint: BitcoinOutpoint) -> TxResult<()> {
+        if self.witnesses.get(index).is_some_and(|w| !w.is_empty()) {
+            return Err(TxError::InputAlreadySigned(index));
+        }
+        self.legacy_tx.set_input_outpoint(index, outpoint)
+    }
+
+    /// Sign each input spending a P2PKH, P2SH-P2WPKH or P2WPKH prevout for which `key_for`
+    /// returns the matching key, using `SIGHASH_ALL`, and write its script sig and witness.
+    /// `prevouts` must contain the output spent by each input, in input order.
+    ///
+    /// Inputs with no matching key, and inputs spending other script types, are left untouched.
+    pub fn sign_all<'a, K, F>(&mut self, prevouts: &[TxOut], key_for: F) -> TxResult<()>
+    where
+        K: AsRef<SigningKey> + 'a,
+        F: Fn(&ScriptPubkey) -> Option<&'a K>,
+    {
+        if prevouts.len() != self.inputs().len() {
+            return Err(TxError::PrevoutCountMismatch {
+                inputs: self.inputs().len(),
+                prevouts: prevouts.len(),
+            });
+        }
+
+        for (index, prevout) in prevouts.iter().enumerate() {
+            let key = match key_for(&prevout.script_pubkey) {
+                Some(key) => key.as_ref(),
+                None => continue,
+            };
+            let pubkey = key.verifying_key().to_sec1_bytes();
+            let pubkey_hash: Hash160Digest = Hash160::digest_marked(&pubkey);
+
+            let mut wpkh_script = vec![0x00, 0x14]; // OP_0, PUSH_20
+            wpkh_script.extend(pubkey_hash.as_slice());
+
+            let (is_witness, script_sig) = match prevout.script_pubkey.standard_type() {
+                ScriptType::Pkh(payload) if payload == pubkey_hash => (false, ScriptSig::null()),
+                ScriptType::Wpkh(payload) if payload == pubkey_hash => (true, ScriptSig::null()),
+                ScriptType::Sh(payload) if payload == Hash160::digest_marked(&wpkh_script) => {
+                    let mut script_sig = vec![];
+                    push_minimal(&mut script_sig, &wpkh_script);
+                    (true, script_sig.into())
+                }
+                _ => continue,
+            };
+
+            let mut hasher = Hash256::default();
+            if is_witness {
+                let mut script_code = vec![0x76, 0xa9, 0x14]; // DUP, HASH160, PUSH_20
+                script_code.extend(pubkey_hash.as_slice());
+                script_code.extend([0x88, 0xac]); // EQUALVERIFY, CHECKSIG
+                let args = WitnessSighashArgs {
+                    index,
+                    sighash_flag: Sighash::All,
+                    prevout_script: script_code.into(),
+                    prevout_value: prevout.value,
+                };
+                self.write_witness_sighash_preimage(&mut hasher, &args)?;
+            } else {
+                let args = LegacySighashArgs {
+                    index,
+                    sighash_flag: Sighash::All,
+                    prevout_script: (&prevout.script_pubkey).into(),
+                };
+                self.write_legacy_sighash_preimage(&mut hasher, &args)?;
+            }
+
+            let sig: Signature = key.try_sign_digest(hasher).map_err(TxError::SigningError)?;
+            let mut sig = sig
+                .normalize_s()
+                .unwrap_or(sig)
+                .to_der()
+                .as_bytes()
+                .to_vec();
+            sig.push(Sighash::All as u8);
+
+            if is_witness {
+                self.legacy_tx.vin[index].script_sig = script_sig;
+                self.witnesses[index] = vec![
+                    WitnessStackItem::new(sig),
+                    WitnessStackItem::new(pubkey.to_vec()),
+                ];
+            } else {
+                let mut script_sig = vec![];
+                push_minimal(&mut script_sig, &sig);
+                push_minimal(&mut script_sig, &pubkey);
+                self.legacy_tx.vin[index].script_sig = script_sig.into();
+                self.witnesses[index] = Witness::default();
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Transaction for WitnessTx {
@@ -340,6 +670,10 @@ impl WitnessTransaction for WitnessTx {
         self.legacy_tx.write_sighash_preimage(writer, args)
     }
 
+    fn legacy_sighash(&self, args: &LegacySighashArgs) -> TxResult<DigestOutput<Hash256>> {
+        self.legacy_tx.sighash(args)
+    }
+
     fn write_witness_sighash_preimage<W>(
         &self,
         writer: &mut W,
@@ -348,37 +682,84 @@ impl WitnessTransaction for WitnessTx {
     where
         W: Write,
     {
-        if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
-            return Err(TxError::NoneUnsupported);
-        }
+        self.write_bip143_preimage(writer, args, None)
+    }
+}
 
-        if (args.sighash_flag == Sighash::Single || args.sighash_flag == Sighash::SingleAcp)
-            && args.index >= self.outputs().len()
-        {
-            return Err(TxError::SighashSingleBug);
-        }
+/// Precomputed BIP143 `hash_prevouts`, `hash_sequence`, and `hash_outputs` for a transaction.
+/// Without the cache, each input's sighash rehashes every input and output. With it, these are
+/// hashed once, and shared by every input signed with a flag that commits to them.
+///
+/// The cache borrows the transaction immutably, so the transaction can't be mutated while the
+/// cache is alive. To sign after a mutation, drop the cache and build a new one.
+///
+/// ```compile_fail
+/// use bitcoins::types::{SighashCache, WitnessTx};
+///
+/// let mut tx = WitnessTx::default();
+/// let cache = SighashCache::new(&tx).unwrap();
+/// tx.set_input_outpoint(0, Default::default()).unwrap();
+/// cache.transaction();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SighashCache<'a> {
+    tx: &'a WitnessTx,
+    hash_prevouts: Hash256Digest,
+    hash_sequence: Hash256Digest,
+    hash_outputs: Hash256Digest,
+}
 
-        let input = &self.legacy_tx.vin[args.index];
+impl<'a> SighashCache<'a> {
+    /// Precompute the `SIGHASH_ALL` hashes of `tx`.
+    pub fn new(tx: &'a WitnessTx) -> TxResult<Self> {
+        Ok(Self {
+            tx,
+            hash_prevouts: tx.hash_prevouts(Sighash::All)?,
+            hash_sequence: tx.hash_sequence(Sighash::All)?,
+            hash_outputs: tx.hash_outputs(0, Sighash::All)?,
+        })
+    }
 
-        ser::write_u32_le(writer, self.legacy_tx.version)?;
-        self.hash_prevouts(args.sighash_flag)?.write_to(writer)?;
-        self.hash_sequence(args.sighash_flag)?.write_to(writer)?;
-        input.outpoint.write_to(writer)?;
-        args.prevout_script.write_to(writer)?;
-        ser::write_u64_le(writer, args.prevout_value)?;
-        ser::write_u32_le(writer, input.sequence)?;
-        self.hash_outputs(args.index, args.sighash_flag)?
-            .write_to(writer)?;
-        ser::write_u32_le(writer, self.legacy_tx.locktime)?;
-        ser::write_u32_le(writer, args.sighash_flag as u32)?;
-        Ok(())
+    /// The transaction the cache was built from.
+    pub fn transaction(&self) -> &'a WitnessTx {
+        self.tx
+    }
+
+    /// Writes the BIP143 sighash preimage to the provided `writer`. This is identical to
+    /// `WitnessTransaction::write_witness_sighash_preimage` on the cached transaction.
+    pub fn write_witness_sighash_preimage<W: Write>(
+        &self,
+        writer: &mut W,
+        args: &WitnessSighashArgs,
+    ) -> TxResult<()> {
+        self.tx.write_bip143_preimage(writer, args, Some(self))
+    }
+
+    /// Calculates the BIP143 sighash given the sighash args. This is identical to
+    /// `WitnessTransaction::witness_sighash` on the cached transaction.
+    pub fn witness_sighash(&self, args: &WitnessSighashArgs) -> TxResult<Hash256Digest> {
+        let mut w = Hash256::default();
+        self.write_witness_sighash_preimage(&mut w, args)?;
+        Ok(w.finalize_marked())
+    }
+}
+
+/// Error if a witness tx read from `cursor` left bytes unread. These can only be extra witness
+/// stacks, as the witness section has no count.
+pub(crate) fn check_consumed(cursor: &std::io::Cursor<&[u8]>, inputs: usize) -> TxResult<()> {
+    if (cursor.position() as usize) < cursor.get_ref().len() {
+        return Err(SerError::WitnessCountMismatch { inputs }.into());
     }
+    Ok(())
 }
 
 impl ByteFormat for WitnessTx {
     type Error = TxError;
 
     fn serialized_length(&self) -> usize {
+        if !self.has_witness() {
+            return self.legacy_tx.serialized_length();
+        }
         let mut len = 4; // version
         len += 2; // Segwit Flag
         len += coins_core::ser::prefix_byte_len(self.legacy_tx.vin.len() as u64) as usize;
@@ -395,10 +776,11 @@ impl ByteFormat for WitnessTx {
             .iter()
             .map(|o| o.serialized_length())
             .sum::<usize>();
-        for witness in self.witnesses.iter() {
-            len += coins_core::ser::prefix_byte_len(self.witnesses.len() as u64) as usize;
-            len += witness.iter().map(|w| w.serialized_length()).sum::<usize>();
-        }
+        len += self
+            .witnesses
+            .iter()
+            .map(|w| w.serialized_size())
+            .sum::<usize>();
         len += 4; // locktime
         len
     }
@@ -408,17 +790,35 @@ impl ByteFormat for WitnessTx {
         R: Read,
         Self: std::marker::Sized,
     {
+        // Read the version and the witness flag, then chain them back on the front of the
+        // reader. Transactions without witness data are serialized without the flag.
+        let mut tag = [0u8; 6];
+        reader.read_exact(&mut tag)?;
+        let mut chain = tag.chain(reader);
+        let reader = &mut chain;
+        if tag[4] != 0 {
+            return Ok(Self::from_legacy(LegacyTx::read_from(reader)?));
+        }
+        if tag[5] != 1 {
+            return Err(TxError::BadWitnessFlag([tag[4], tag[5]]));
+        }
+
         let version = ser::read_u32_le(reader)?;
-        let mut flag = [0u8; 2];
-        reader.read_exact(&mut flag)?;
-        if flag != [0u8, 1u8] {
-            return Err(TxError::BadWitnessFlag(flag));
-        };
-        let vin = ser::read_prefix_vec(reader)?;
+        reader.read_exact(&mut [0u8; 2])?;
+        let vin: Vin = ser::read_prefix_vec(reader)?;
         let vout = ser::read_prefix_vec(reader)?;
+
+        // There is no witness count on the wire. If the stream runs out before each input has a
+        // witness stack, it did not contain enough witnesses.
+        let mismatch = |e: SerError| match e {
+            SerError::IoError(ref io) if io.kind() == std::io::ErrorKind::UnexpectedEof => {
+                SerError::WitnessCountMismatch { inputs: vin.len() }
+            }
+            e => e,
+        };
         let mut witnesses = vec![];
         for _ in vin.iter() {
-            witnesses.push(ser::read_prefix_vec(reader)?);
+            witnesses.push(ser::read_prefix_vec(reader).map_err(mismatch)?);
         }
         let locktime = ser::read_u32_le(reader)?;
 
@@ -435,10 +835,25 @@ impl ByteFormat for WitnessTx {
         })
     }
 
+    /// Deserialize a witness tx from a slice, which must contain nothing else. As there is no
+    /// witness count on the wire, leftover bytes mean the slice has more witness stacks than the
+    /// tx has inputs.
+    fn deserialize_slice(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let tx = Self::read_from(&mut cursor)?;
+        if bytes.get(4) == Some(&0) {
+            check_consumed(&cursor, tx.inputs().len())?;
+        }
+        Ok(tx)
+    }
+
     fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
     where
         W: Write,
     {
+        if !self.has_witness() {
+            return self.legacy_tx.write_to(writer);
+        }
         let mut len = ser::write_u32_le(writer, self.version())?;
         len += writer.write(&[0u8, 1u8])?;
 
@@ -455,7 +870,12 @@ impl ByteFormat for WitnessTx {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::types::{BitcoinTxIn, TxOut, Witness, WitnessStackItem};
+    use crate::types::{script::parse_pushes, BitcoinTxIn, TxOut, Witness, WitnessStackItem};
+    use coins_bip32::{
+        k256::ecdsa::{signature::hazmat::PrehashVerifier, VerifyingKey},
+        primitives::{ChainCode, Hint, KeyFingerprint, XKeyInfo},
+        xkeys::XPriv,
+    };
 
     #[test]
     fn it_should_ensure_correct_amount_of_witnesses_addition() {
@@ -481,4 +901,561 @@ mod test {
         assert_eq!(tx.witnesses.len(), expected_size);
         assert_eq!(expected_witness, tx.witnesses[0]);
     }
+
+    #[test]
+    fn it_round_trips_witness_serialization() {
+        // from mainnet: 3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a
+        let tx_hex = "01000000000101b77bebb3ac480e99c0d95a4c812137b116e65e2f3b3a66a36d0e252928d460180100000000ffffffff03982457000000000017a91417b8e0f150215cc70bf2fb58070041d655b162dd8740e133000000000017a9142535e444f7d55f0500c1f86609d6cfc289576b698747abfb0100000000220020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d040047304402205c6a889efa26955bef7ce2b08792e63e25eac9859080f0d83912b0ea833d7eb402205f859f4640f1600db5012b467ec05bb4ae1779640c1b5fadc8908960740e52b30147304402201c239ea25cfeadfa9493a1b0d136d70f50f821385972b7188c4329c2bf2d23a302201ee790e4b6794af6567f85a226a387d5b0222c3dc90d2fc558d09e08062b8271016952210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae00000000";
+        let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
+        assert!(tx.has_witness());
+        assert_eq!(tx.witnesses()[0].len(), 4);
+        assert_eq!(tx.serialized_length(), tx_hex.len() / 2);
+        assert_eq!(tx.serialize_hex(), tx_hex);
+
+        let parsed = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+        assert!(parsed.is_witness());
+        assert_eq!(parsed.serialize_hex(), tx_hex);
+
+        // without witness data, the tx serializes in the legacy format, and parses back
+        let unsigned = WitnessTx::from_legacy(tx.as_legacy().clone());
+        assert!(!unsigned.has_witness());
+        let legacy_hex = unsigned.as_legacy().serialize_hex();
+        assert_eq!(unsigned.serialize_hex(), legacy_hex);
+        assert_eq!(unsigned.serialized_length(), legacy_hex.len() / 2);
+        assert_eq!(WitnessTx::deserialize_hex(&legacy_hex).unwrap(), unsigned);
+        assert!(!BitcoinTx::deserialize_hex(&legacy_hex)
+            .unwrap()
+            .is_witness());
+    }
+
+    fn xpriv_from_secret(secret: [u8; 32]) -> XPriv {
+        XPriv::new(
+            SigningKey::from_bytes(&secret.into()).unwrap(),
+            XKeyInfo {
+                depth: 0,
+                parent: KeyFingerprint([0u8; 4]),
+                index: 0,
+                chain_code: ChainCode([0u8; 32]),
+                hint: Hint::SegWit,
+            },
+        )
+    }
+
+    #[test]
+    fn it_signs_all_inputs_with_one_key() {
+        let key = xpriv_from_secret([1u8; 32]);
+        let script_pubkey: ScriptPubkey =
+            hex::decode("001479b000887626b294a914501a4cd226b58b235983")
+                .unwrap()
+                .into();
+        let prevouts = [
+            TxOut::new(60_000, script_pubkey.clone()),
+            TxOut::new(40_000, script_pubkey.clone()),
+        ];
+        let vin = vec![
+            BitcoinTxIn::new(
+                BitcoinOutpoint::new([1u8; 32].into(), 0),
+                vec![],
+                0xffff_fffd,
+            ),
+            BitcoinTxIn::new(
+                BitcoinOutpoint::new([2u8; 32].into(), 1),
+                vec![],
+                0xffff_fffd,
+            ),
+        ];
+        let vout = vec![TxOut::new(90_000, script_pubkey.clone())];
+        let mut tx = <WitnessTx as Transaction>::new(2, vin, vout, 0).unwrap();
+
+        // a key for another script leaves the inputs unsigned
+        let other = xpriv_from_secret([2u8; 32]);
+        tx.sign_all(&prevouts, |_| Some(&other)).unwrap();
+        assert!(tx.witnesses().iter().all(Vec::is_empty));
+
+        tx.sign_all(&prevouts, |spk| {
+            if spk == &script_pubkey {
+                Some(&key)
+            } else {
+                None
+            }
+        })
+        .unwrap();
+        assert!(tx.witnesses().iter().all(|w| w.len() == 2));
+        assert!(tx.is_fully_signed(&prevouts));
+
+        // each signature is valid for its input's BIP143 sighash, signed with the P2PKH
+        // script code of the pubkey hash
+        let pubkey = key.verify_key().to_sec1_bytes();
+        for (index, prevout) in prevouts.iter().enumerate() {
+            let args = WitnessSighashArgs {
+                index,
+                sighash_flag: Sighash::All,
+                prevout_script: hex::decode("76a91479b000887626b294a914501a4cd226b58b23598388ac")
+                    .unwrap()
+                    .into(),
+                prevout_value: prevout.value,
+            };
+            let sighash = tx.witness_sighash(&args).unwrap();
+            let witness = &tx.witnesses()[index];
+            assert_eq!(witness[0].items().last(), Some(&(Sighash::All as u8)));
+            assert_eq!(witness[1].items(), &pubkey[..]);
+            verify_signature(&sighash[..], witness[0].items(), &pubkey);
+        }
+        assert_eq!(
+            tx.serialize_hex(),
+            "0200000000010201010101010101010101010101010101010101010101010101010101010101010000000000fdffffff02020202020202020202020202020202020202020202020202020202020202020100000000fdffffff01905f01000000000016001479b000887626b294a914501a4cd226b58b23598302473044022055d4e6af7bb4c46f30402d76f7f29b68fc56ca6ac59d4fb9d667f5ef3312fd1b022039b7af7d837ea3390cbf03d2ac7c745c55fe638e5f83d0581db31ff25403e56a0121031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f0248304502210098d5be9496e0fa4dedfd5bf1f7c0b3b112354324ca37c9a3f27556840bfca9ae022016d43b5ec374a49ff73c7e03be1752bc9c7b061a903a3b15382d2920eef09c370121031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f00000000"
+        );
+
+        match tx.sign_all(&prevouts[..1], |_| Some(&key)) {
+            Err(TxError::PrevoutCountMismatch {
+                inputs: 2,
+                prevouts: 1,
+            }) => {}
+            e => panic!("expected prevout count mismatch, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_caches_witness_sighashes() {
+        let vin = (0..3u8)
+            .map(|i| {
+                BitcoinTxIn::new(
+                    BitcoinOutpoint::new([i; 32].into(), i as u32),
+                    vec![],
+                    0xffff_fffd - i as u32,
+                )
+            })
+            .collect::<Vec<_>>();
+        let vout = vec![
+            TxOut::new(90_000, vec![0x51]),
+            TxOut::new(10_000, vec![0x52]),
+        ];
+        let tx = <WitnessTx as Transaction>::new(2, vin, vout, 0).unwrap();
+        let cache = SighashCache::new(&tx).unwrap();
+
+        let flags = [
+            Sighash::All,
+            Sighash::AllAcp,
+            Sighash::Single,
+            Sighash::SingleAcp,
+        ];
+        for flag in flags.iter() {
+            for index in 0..2 {
+                let args = WitnessSighashArgs {
+                    index,
+                    sighash_flag: *flag,
+                    prevout_script: vec![0x51].into(),
+                    prevout_value: 50_000,
+                };
+                assert_eq!(
+                    cache.witness_sighash(&args).unwrap().as_slice(),
+                    &tx.witness_sighash(&args).unwrap()[..]
+                );
+            }
+        }
+
+        let args = WitnessSighashArgs {
+            index: 2,
+            sighash_flag: Sighash::Single,
+            prevout_script: vec![0x51].into(),
+            prevout_value: 50_000,
+        };
+        assert_eq!(
+            cache.witness_sighash(&args).unwrap().as_slice(),
+            &tx.witness_sighash(&args).unwrap()[..]
+        );
+        assert_eq!(cache.transaction(), &tx);
+    }
+
+    #[test]
+    fn it_repoints_only_unsigned_inputs() {
+        let vin = vec![
+            BitcoinTxIn::new(
+                BitcoinOutpoint::new([1u8; 32].into(), 0),
+                vec![],
+                0xffff_fffd,
+            ),
+            BitcoinTxIn::new(
+                BitcoinOutpoint::new([2u8; 32].into(), 1),
+                vec![],
+                0xffff_fffd,
+            ),
+        ];
+        let vout = vec![TxOut::new(90_000, vec![0x51])];
+        let mut tx = <WitnessTx as Transaction>::new(2, vin, vout, 0).unwrap();
+
+        let outpoint = BitcoinOutpoint::new([3u8; 32].into(), 2);
+        tx.set_input_outpoint(0, outpoint).unwrap();
+        assert_eq!(tx.inputs()[0].outpoint, outpoint);
+
+        tx.witnesses[1] = vec![WitnessStackItem::new(vec![0x01])];
+        match tx.set_input_outpoint(1, outpoint) {
+            Err(TxError::InputAlreadySigned(1)) => {}
+            e => panic!("expected already signed error, got {:?}", e),
+        }
+
+        tx.legacy_tx.vin[0].script_sig = vec![0x51].into();
+        match tx.set_input_outpoint(0, BitcoinOutpoint::null()) {
+            Err(TxError::InputAlreadySigned(0)) => {}
+            e => panic!("expected already signed error, got {:?}", e),
+        }
+        assert_eq!(tx.inputs()[0].outpoint, outpoint);
+
+        match tx.set_input_outpoint(2, outpoint) {
+            Err(TxError::NoSuchInput(2)) => {}
+            e => panic!("expected missing input error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_dedups_transactions_by_txid() {
+        let vin = vec![BitcoinTxIn::new(
+            BitcoinOutpoint::new([1u8; 32].into(), 0),
+            vec![],
+            0xffff_fffd,
+        )];
+        let vout = vec![TxOut::new(90_000, vec![0x51])];
+        let mut tx = <WitnessTx as Transaction>::new(2, vin, vout, 0).unwrap();
+        tx.witnesses[0] = vec![WitnessStackItem::new(vec![0x01])];
+
+        let mut malleated = tx.clone();
+        malleated.witnesses[0] = vec![WitnessStackItem::new(vec![0x02])];
+        assert_ne!(tx.wtxid(), malleated.wtxid());
+        assert_eq!(tx.dedup_key(), malleated.dedup_key());
+        assert_eq!(tx.dedup_key(), tx.txid());
+    }
+
+    #[test]
+    fn it_rejects_missing_witnesses() {
+        let input = format!("{}0000000000ffffffff", "00".repeat(32));
+        // version, flag, 2 inputs, 1 output, a single witness stack, and the locktime 5. The
+        // locktime is read as a second stack of 5 items, which runs out.
+        let hex = format!(
+            "02000000000102{}{}010000000000000000000101aa05000000",
+            input, input
+        );
+        match WitnessTx::deserialize_hex(&hex) {
+            Err(TxError::SerError(SerError::WitnessCountMismatch { inputs: 2 })) => {}
+            e => panic!("expected witness count mismatch, got {:?}", e),
+        }
+
+        // a truncated locktime is not a witness count mismatch
+        let hex = format!("02000000000101{}010000000000000000000101aa000000", input);
+        match WitnessTx::deserialize_hex(&hex) {
+            Err(TxError::SerError(SerError::IoError(_))) => {}
+            e => panic!("expected io error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_rejects_extra_witnesses() {
+        let input = format!("{}0000000000ffffffff", "00".repeat(32));
+        // version, flag, 1 input, 1 output, two witness stacks, and the locktime
+        let hex = format!(
+            "02000000000101{}010000000000000000000101aa0101bb00000000",
+            input
+        );
+        match WitnessTx::deserialize_hex(&hex) {
+            Err(TxError::SerError(SerError::WitnessCountMismatch { inputs: 1 })) => {}
+            e => panic!("expected witness count mismatch, got {:?}", e),
+        }
+        match BitcoinTx::deserialize_hex(&hex) {
+            Err(TxError::SerError(SerError::WitnessCountMismatch { inputs: 1 })) => {}
+            e => panic!("expected witness count mismatch, got {:?}", e),
+        }
+
+        // a single stack parses
+        let hex = format!("02000000000101{}010000000000000000000101aa00000000", input);
+        let tx = WitnessTx::deserialize_hex(&hex).unwrap();
+        assert_eq!(tx.serialize_hex(), hex);
+    }
+
+    /// Verify a DER signature, with its sighash flag byte, over `sighash`.
+    fn verify_signature(sighash: &[u8], signature: &[u8], pubkey: &[u8]) {
+        let key = VerifyingKey::from_sec1_bytes(pubkey).unwrap();
+        let signature = Signature::from_der(&signature[..signature.len() - 1]).unwrap();
+        key.verify_prehash(sighash, &signature).unwrap();
+    }
+
+    #[test]
+    fn it_computes_sighashes_for_each_spend_type() {
+        // The BIP174 test transaction. Input 0 spends a P2SH 2-of-2 multisig, and input 1 a
+        // P2SH-P2WSH 2-of-2 multisig worth 2 BTC.
+        let signed = BitcoinTx::deserialize_hex("0200000000010258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd7500000000da00473044022074018ad4180097b873323c0015720b3684cc8123891048e7dbcd9b55ad679c99022073d369b740e3eb53dcefa33823c8070514ca55a7dd9544f157c167913261118c01483045022100f61038b308dc1da865a34852746f015772934208c6d24454393cd99bdf2217770220056e675a675a6d0a02b85b14e5e29074d8a25a9b5760bea2816f661910a006ea01475221029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f2102dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d752aeffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d01000000232200208c2353173743b595dfb4a07b72ba8e42e3797da74e87fe7d9d7497e3b2028903ffffffff0270aaf00800000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df5546e8742d1d87008f000400473044022062eb7a556107a7c73f45ac4ab5a1dddf6f7075fb1275969a7f383efff784bcb202200c05dbb7470dbf2f08557dd356c7325c1ed30913e996cd3840945db12228da5f01473044022065f45ba5998b59a27ffe1a7bed016af1f1f90d54b3aa8f7450aa5f56a25103bd02207f724703ad1edb96680b284b56d4ffcb88f7fb759eabbe08aa30f29b851383d20147522103089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc21023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e7352ae00000000").unwrap();
+        let unsigned = LegacyTx::deserialize_hex("020000000258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd750000000000ffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d0100000000ffffffff0270aaf00800000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df5546e8742d1d87008f00000000").unwrap();
+        let tx = WitnessTx::from_legacy(unsigned.clone());
+        let redeem_script = Script::deserialize_hex("475221029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f2102dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d752ae").unwrap();
+        let witness_script = Script::deserialize_hex("47522103089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc21023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e7352ae").unwrap();
+
+        let legacy = Hash256Digest::deserialize_hex(
+            "ff089a1634a922b1dc623aca50b2b922c1487786bae4476ab1ade9897ea65d5f",
+        )
+        .unwrap();
+        let witness = Hash256Digest::deserialize_hex(
+            "c5a3684b155f6f441ae3fad632a3463a6d5a00c0f0e84075f5649847c17b2b3b",
+        )
+        .unwrap();
+
+        // the signatures follow a dummy item in the script sig of input 0 and the witness of
+        // input 1, in the order of the multisig pubkeys
+        let signed = match signed {
+            BitcoinTx::Witness(signed) => signed,
+            _ => panic!("expected a witness tx"),
+        };
+        let legacy_signatures = parse_pushes(signed.inputs()[0].script_sig.items()).unwrap();
+        let witness_signatures = signed.witnesses()[1]
+            .iter()
+            .map(|item| item.items().to_vec())
+            .collect::<Vec<_>>();
+        let pubkeys = |script: &Script| -> Vec<Vec<u8>> {
+            let items = script.items();
+            vec![items[2..35].to_vec(), items[36..69].to_vec()]
+        };
+
+        // (input index, spend, sighash, signatures, pubkeys)
+        let cases = [
+            (
+                0,
+                SpendInfo::Bare(redeem_script.clone()),
+                legacy,
+                &legacy_signatures,
+                pubkeys(&redeem_script),
+            ),
+            (
+                0,
+                SpendInfo::P2sh(redeem_script.clone()),
+                legacy,
+                &legacy_signatures,
+                pubkeys(&redeem_script),
+            ),
+            (
+                1,
+                SpendInfo::P2wsh(witness_script.clone()),
+                witness,
+                &witness_signatures,
+                pubkeys(&witness_script),
+            ),
+            (
+                1,
+                SpendInfo::P2shP2wsh(witness_script.clone()),
+                witness,
+                &witness_signatures,
+                pubkeys(&witness_script),
+            ),
+        ];
+        for (index, spend, expected, signatures, pubkeys) in cases.iter() {
+            let sighash = tx
+                .spend_sighash(*index, Sighash::All, 200_000_000, spend)
+                .unwrap();
+            assert_eq!(sighash, *expected);
+            assert_eq!(
+                BitcoinTx::Legacy(unsigned.clone())
+                    .spend_sighash(*index, Sighash::All, 200_000_000, spend)
+                    .unwrap(),
+                *expected
+            );
+            for (signature, pubkey) in signatures[1..3].iter().zip(pubkeys.iter()) {
+                verify_signature(sighash.as_slice(), signature, pubkey);
+            }
+        }
+
+        // The BIP143 P2SH-P2WSH 6-of-6 multisig example, which is signed with each flag. The
+        // witness sighash doesn't support `NONE`, so those signatures are skipped.
+        let tx = WitnessTx::from_legacy(LegacyTx::deserialize_hex("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap());
+        let witness_script = Script::deserialize_hex("cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae").unwrap();
+        let cases = [
+            (
+                Sighash::All,
+                "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c",
+            ),
+            (
+                Sighash::Single,
+                "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea",
+            ),
+            (
+                Sighash::AllAcp,
+                "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e",
+            ),
+            (
+                Sighash::SingleAcp,
+                "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b",
+            ),
+        ];
+        for (flag, expected) in cases.iter() {
+            let expected = Hash256Digest::deserialize_hex(expected).unwrap();
+            for spend in [
+                SpendInfo::P2wsh(witness_script.clone()),
+                SpendInfo::P2shP2wsh(witness_script.clone()),
+            ]
+            .iter()
+            {
+                assert_eq!(
+                    tx.spend_sighash(0, *flag, 987_654_321, spend).unwrap(),
+                    expected
+                );
+            }
+        }
+        match tx.spend_sighash(
+            0,
+            Sighash::None,
+            987_654_321,
+            &SpendInfo::P2wsh(witness_script),
+        ) {
+            Err(TxError::NoneUnsupported) => {}
+            e => panic!("expected none unsupported error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_computes_taproot_sighashes() {
+        // The BIP341 wallet vectors, `keyPathSpending`
+        let tx = LegacyTx::deserialize_hex("02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c010000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6dafdd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d957275419a418420000000000fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acbfe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc090464cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b000000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d").unwrap();
+        let tx = WitnessTx::from_legacy(tx);
+        // `utxosSpent`
+        let prevouts = [
+            (
+                "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+                420_000_000,
+            ),
+            (
+                "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+                462_000_000,
+            ),
+            (
+                "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+                294_000_000,
+            ),
+            (
+                "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+                504_000_000,
+            ),
+            (
+                "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605",
+                630_000_000,
+            ),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378_000_000),
+            (
+                "512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831",
+                672_000_000,
+            ),
+            (
+                "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+                546_000_000,
+            ),
+            (
+                "512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+                588_000_000,
+            ),
+        ]
+        .iter()
+        .map(|(script_pubkey, value)| TxOut::new(*value, hex::decode(script_pubkey).unwrap()))
+        .collect::<Vec<_>>();
+
+        // (input index, hash type, sighash). The vectors have no annexes.
+        let cases = [
+            (
+                0,
+                TapSighashType::Single,
+                "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555",
+            ),
+            (
+                1,
+                TapSighashType::SingleAcp,
+                "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d",
+            ),
+            (
+                3,
+                TapSighashType::All,
+                "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669",
+            ),
+            (
+                4,
+                TapSighashType::Default,
+                "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef",
+            ),
+            (
+                6,
+                TapSighashType::None,
+                "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85",
+            ),
+            (
+                7,
+                TapSighashType::NoneAcp,
+                "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10",
+            ),
+            (
+                8,
+                TapSighashType::AllAcp,
+                "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2",
+            ),
+        ];
+        for (index, sighash_type, expected) in cases.iter() {
+            let sighash = tx
+                .taproot_sighash(*index, &prevouts, *sighash_type, None, None)
+                .unwrap();
+            assert_eq!(hex::encode(sighash), *expected);
+        }
+
+        let spend = SpendInfo::Taproot {
+            prevouts: prevouts.clone(),
+            leaf_hash: None,
+        };
+        assert_eq!(
+            hex::encode(tx.spend_sighash(3, Sighash::All, 0, &spend).unwrap()),
+            "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"
+        );
+
+        // BIP341 has no sighash vectors for annexes or script paths. Each commits to the
+        // spend type, so changes the sighash.
+        let mut leaf_hash = [0u8; 32];
+        leaf_hash.copy_from_slice(
+            &hex::decode("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21")
+                .unwrap(),
+        );
+        let annex = [0x50u8, 0x01, 0x02];
+        let key_path = tx
+            .taproot_sighash(3, &prevouts, TapSighashType::All, None, None)
+            .unwrap();
+        let with_annex = tx
+            .taproot_sighash(3, &prevouts, TapSighashType::All, None, Some(&annex[..]))
+            .unwrap();
+        let script_path = tx
+            .taproot_sighash(3, &prevouts, TapSighashType::All, Some(leaf_hash), None)
+            .unwrap();
+        assert_ne!(with_annex, key_path);
+        assert_ne!(script_path, key_path);
+        assert_ne!(script_path, with_annex);
+
+        let one_input_tx = <WitnessTx as WitnessTransaction>::new(
+            2,
+            vec![tx.inputs()[0].clone()],
+            tx.outputs().to_vec(),
+            vec![],
+            0,
+        )
+        .unwrap();
+        let one_output_tx = <WitnessTx as WitnessTransaction>::new(
+            2,
+            tx.inputs().to_vec(),
+            tx.outputs()[..1].to_vec(),
+            vec![],
+            0,
+        )
+        .unwrap();
+        match one_input_tx.taproot_sighash(0, &prevouts, TapSighashType::Default, None, None) {
+            Err(TxError::PrevoutCountMismatch {
+                inputs: 1,
+                prevouts: 9,
+            }) => {}
+            e => panic!("expected prevout count mismatch, got {:?}", e),
+        }
+        match one_output_tx.taproot_sighash(1, &prevouts, TapSighashType::Single, None, None) {
+            Err(TxError::NoMatchingOutput(1)) => {}
+            e => panic!("expected no matching output, got {:?}", e),
+        }
+        match tx.taproot_sighash(9, &prevouts, TapSighashType::Default, None, None) {
+            Err(TxError::NoSuchInput(9)) => {}
+            e => panic!("expected no such input, got {:?}", e),
+        }
+    }
 }
@@ -90,6 +90,7 @@ where
 {
     type Encoder = T;
     type Transaction = HandshakeTx;
+    type Error = <HandshakeTx as Transaction>::TxError;
 
     fn new() -> Self {
         Self {
@@ -183,7 +184,7 @@ where
         self
     }
 
-    fn build(self) -> Result<Self::Transaction, <Self::Transaction as Transaction>::TxError> {
+    fn build(self) -> Result<Self::Transaction, Self::Error> {
         <HandshakeTx as HandshakeTransaction>::new(
             self.version,
             self.vin,
@@ -1,10 +1,8 @@
 use k256::ecdsa;
 
-use coins_core::prelude::{Hash160, Hash160Digest, MarkedDigest, MarkedDigestOutput};
-
 use crate::{
     path::{DerivationPath, KeyDerivation},
-    primitives::{Hint, KeyFingerprint, XKeyInfo},
+    primitives::{Hint, XKeyInfo},
     xkeys::{Parent, XPriv, XPub, SEED},
     Bip32Error,
 };
@@ -264,20 +262,6 @@ impl DerivedPubkey {
     pub const fn new(key: ecdsa::VerifyingKey, derivation: KeyDerivation) -> Self {
         Self { key, derivation }
     }
-
-    /// Return the hash of the compressed (Sec1) pubkey.
-    pub fn pubkey_hash160(&self) -> Hash160Digest {
-        Hash160::digest_marked(self.key.to_sec1_bytes().as_ref())
-    }
-
-    /// The fingerprint is the first 4 bytes of the HASH160 of the serialized
-    /// public key.
-    pub fn fingerprint(&self) -> KeyFingerprint {
-        let digest = self.pubkey_hash160();
-        let mut buf = [0u8; 4];
-        buf.copy_from_slice(&digest.as_slice()[..4]);
-        buf.into()
-    }
 }
 
 #[cfg(test)]
@@ -548,8 +532,8 @@ mod test {
         ];
         assert_eq!(sig.to_der().as_bytes(), der_sig);
         assert_eq!(&sig, &Signature::from_der(&der_sig).unwrap());
-        assert_eq!(sig.r().to_bytes().as_slice(), &rsv[..32]);
-        assert_eq!(sig.s().to_bytes().as_slice(), &rsv[32..64]);
+        assert_eq!(&sig.r().to_bytes()[..], &rsv[..32]);
+        assert_eq!(&sig.s().to_bytes()[..], &rsv[32..64]);
         assert_eq!(recovery_id.to_byte(), rsv[64]);
     }
 
@@ -570,6 +554,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_computes_key_identifiers() {
+        // BIP32 test vector 1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let m = DerivedXPriv::root_from_seed(&seed, None).unwrap();
+        let m_pub = m.verify_key();
+        let identifier = hex::decode("3442193e1bb70916e914552172cd4e2dbc9df811").unwrap();
+        let fingerprint: KeyFingerprint = [0x34, 0x42, 0x19, 0x3e].into();
+
+        assert_eq!(m_pub.identifier().as_slice(), &identifier[..]);
+        assert_eq!(m_pub.fingerprint(), fingerprint);
+        assert_eq!(m.xpriv.identifier().as_slice(), &identifier[..]);
+        assert_eq!(identifier_of(m_pub.as_ref()).as_slice(), &identifier[..]);
+        assert_eq!(fingerprint_of(m_pub.as_ref()), fingerprint);
+
+        let child = m_pub.derive_child(0).unwrap();
+        assert_eq!(child.xpub.xkey_info.parent, m_pub.fingerprint());
+    }
+
     #[test]
     fn it_checks_ancestry() {
         let m = DerivedXPriv::custom_root_from_seed(&[0u8; 32][..], None).unwrap();
@@ -11,6 +11,9 @@ use crate::{
 /// Decode a bytevector from a base58 check string
 pub fn decode_b58_check(s: &str) -> Result<Vec<u8>, Bip32Error> {
     let data: Vec<u8> = bs58::decode(s).into_vec()?;
+    if data.len() < 4 {
+        return Err(Bip32Error::BadB58Checksum);
+    }
     let idx = data.len() - 4;
     let payload = &data[..idx];
     let checksum = &data[idx..];
@@ -18,11 +21,14 @@ pub fn decode_b58_check(s: &str) -> Result<Vec<u8>, Bip32Error> {
     let digest = &Hash256::digest(payload);
 
     let mut expected = [0u8; 4];
-    expected.copy_from_slice(&digest.as_slice()[..4]);
+    expected.copy_from_slice(&digest[..4]);
     if expected != checksum {
         Err(Bip32Error::BadB58Checksum)
     } else {
-        Ok(payload.to_vec())
+        // truncate in place, rather than copying the payload, which may hold a secret
+        let mut data = data;
+        data.truncate(idx);
+        Ok(data)
     }
 }
 
@@ -31,12 +37,95 @@ pub fn encode_b58_check(v: &[u8]) -> String {
     let digest = &Hash256::digest(v);
 
     let mut checksum = [0u8; 4];
-    checksum.copy_from_slice(&digest.as_slice()[..4]);
+    checksum.copy_from_slice(&digest[..4]);
 
     let mut data = v.to_vec();
     data.extend(checksum);
 
-    bs58::encode(data).into_string()
+    let encoded = bs58::encode(&data).into_string();
+    wipe(&mut data);
+    encoded
+}
+
+/// Overwrite a buffer that may hold secret key material. A no-op without the `zeroize` feature.
+#[allow(unused_variables)]
+fn wipe(buf: &mut [u8]) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(buf);
+}
+
+/// A WIF-encoded private key. With the `zeroize` feature enabled, this is a
+/// `Zeroizing<String>`, which wipes the key from memory when dropped.
+#[cfg(feature = "zeroize")]
+pub type WifString = zeroize::Zeroizing<String>;
+
+/// A WIF-encoded private key. With the `zeroize` feature enabled, this is a
+/// `Zeroizing<String>`, which wipes the key from memory when dropped.
+#[cfg(not(feature = "zeroize"))]
+pub type WifString = String;
+
+/// The network a WIF private key is encoded for. WIF distinguishes only mainnet from the test
+/// networks, which share a version byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifNetwork {
+    /// Mainnet, version byte 0x80
+    Mainnet,
+    /// Testnet, signet, and regtest, version byte 0xef
+    Testnet,
+}
+
+impl WifNetwork {
+    /// The WIF version byte.
+    pub fn version(&self) -> u8 {
+        match self {
+            WifNetwork::Mainnet => 0x80,
+            WifNetwork::Testnet => 0xef,
+        }
+    }
+
+    /// The network using a WIF version byte, if any.
+    pub fn from_version(version: u8) -> Option<Self> {
+        match version {
+            0x80 => Some(WifNetwork::Mainnet),
+            0xef => Some(WifNetwork::Testnet),
+            _ => None,
+        }
+    }
+}
+
+/// Encode a private key in Wallet Import Format. If `compressed`, importers will use the
+/// compressed pubkey.
+// The final conversion is a no-op without the `zeroize` feature
+#[allow(clippy::useless_conversion)]
+pub fn encode_wif(key: &ecdsa::SigningKey, network: WifNetwork, compressed: bool) -> WifString {
+    let mut data = vec![network.version()];
+    data.extend(key.to_bytes());
+    if compressed {
+        data.push(0x01);
+    }
+    let wif = encode_b58_check(&data);
+    wipe(&mut data);
+    wif.into()
+}
+
+/// Decode a private key from Wallet Import Format. Returns the key, its network, and whether it
+/// uses the compressed pubkey. Errors with `BadB58Checksum` if the checksum fails.
+pub fn decode_wif(s: &str) -> Result<(ecdsa::SigningKey, WifNetwork, bool), Bip32Error> {
+    let mut data = decode_b58_check(s)?;
+    let result = parse_wif_payload(&data);
+    wipe(&mut data);
+    result
+}
+
+/// Parse a decoded WIF payload: the version byte, the secret, and the optional compression flag.
+fn parse_wif_payload(data: &[u8]) -> Result<(ecdsa::SigningKey, WifNetwork, bool), Bip32Error> {
+    let (secret, compressed) = match data.len() {
+        33 => (&data[1..], false),
+        34 if data[33] == 0x01 => (&data[1..33], true),
+        _ => return Err(Bip32Error::BadWif),
+    };
+    let network = WifNetwork::from_version(data[0]).ok_or(Bip32Error::BadWif)?;
+    Ok((ecdsa::SigningKey::from_slice(secret)?, network, compressed))
 }
 
 /// Contains network-specific serialization information
@@ -403,3 +492,81 @@ impl<P: NetworkParams> XKeyEncoder for BitcoinEncoder<P> {
         Self::read_xpub_body(reader, hint)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_encodes_and_decodes_wif() {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = ecdsa::SigningKey::from_slice(&secret).unwrap();
+
+        let cases = [
+            (
+                WifNetwork::Mainnet,
+                true,
+                "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn",
+            ),
+            (
+                WifNetwork::Mainnet,
+                false,
+                "5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf",
+            ),
+            (
+                WifNetwork::Testnet,
+                true,
+                "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA",
+            ),
+            (
+                WifNetwork::Testnet,
+                false,
+                "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjJoQFacbgwmaKkrx",
+            ),
+        ];
+        for (network, compressed, wif) in cases.iter() {
+            assert_eq!(encode_wif(&key, *network, *compressed).as_str(), *wif);
+            let (decoded, decoded_network, decoded_compressed) = decode_wif(wif).unwrap();
+            assert_eq!(decoded, key);
+            assert_eq!(decoded_network, *network);
+            assert_eq!(decoded_compressed, *compressed);
+        }
+
+        match decode_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWo") {
+            Err(Bip32Error::BadB58Checksum) => {}
+            e => panic!("expected bad checksum, got {:?}", e),
+        }
+        match decode_wif("1") {
+            Err(Bip32Error::BadB58Checksum) => {}
+            e => panic!("expected bad checksum, got {:?}", e),
+        }
+
+        // wrong version byte, and a bad compression flag
+        let mut data = vec![0x00];
+        data.extend(secret);
+        let mut bad_flag = vec![0x80];
+        bad_flag.extend(secret);
+        bad_flag.push(0x02);
+        for data in [data, bad_flag].iter() {
+            match decode_wif(&encode_b58_check(data)) {
+                Err(Bip32Error::BadWif) => {}
+                e => panic!("expected bad wif, got {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn it_returns_zeroizing_wifs() {
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let key = ecdsa::SigningKey::from_slice(&secret).unwrap();
+        let wif: zeroize::Zeroizing<String> = encode_wif(&key, WifNetwork::Mainnet, true);
+        assert_eq!(
+            wif.as_str(),
+            "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn"
+        );
+        assert_eq!(decode_wif(&wif).unwrap().0, key);
+    }
+}
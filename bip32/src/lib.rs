@@ -28,7 +28,7 @@
 //! let xpriv: XPriv = xpriv_str.parse().unwrap();
 //!
 //! let child_xpriv = xpriv.derive_child(33)?;
-//! let sig: RecoverableSignature = child_xpriv.sign_digest(digest.clone());
+//! let sig: Signature = child_xpriv.sign_digest(digest.clone());
 //!
 //! // Signing key types are associated with verifying key types. You can always derive a pubkey
 //! let child_xpub = child_xpriv.verify_key();
@@ -106,6 +106,14 @@ pub enum Bip32Error {
     #[error("Master key seed generation received <16 bytes")]
     SeedTooShort,
 
+    /// Master key seed import received >64 bytes
+    #[error("Master key seed import received >64 bytes")]
+    SeedTooLong,
+
+    /// Master key seed import received invalid hex
+    #[error(transparent)]
+    HexError(#[from] hex::FromHexError),
+
     /// HMAC I_l was invalid during key generations.
     #[error("HMAC left segment was 0 or greated than the curve order. How?")]
     InvalidKey,
@@ -118,6 +126,10 @@ pub enum Bip32Error {
     #[error("Attempted to tweak an xpriv or xpub directly")]
     BadTweak,
 
+    /// The BIP341 TapTweak was not less than the curve order, or produced the point at infinity
+    #[error("TapTweak was not less than the curve order, or produced the point at infinity")]
+    InvalidTapTweak,
+
     /// Unrecognized version when deserializing xpriv
     #[error("Version bytes 0x{0:x?} don't match any network xpriv version bytes")]
     BadXPrivVersionBytes([u8; 4]),
@@ -134,6 +146,10 @@ pub enum Bip32Error {
     #[error("Checksum mismatch on b58 deserialization")]
     BadB58Checksum,
 
+    /// WIF private key had an unknown version byte, or a malformed payload
+    #[error("Malformed WIF private key")]
+    BadWif,
+
     /// Bubbled up error from bs58 library
     #[error(transparent)]
     B58Error(#[from] bs58::decode::Error),
@@ -146,6 +162,14 @@ pub enum Bip32Error {
     #[error("Attempted to deserialize a DER signature to a recoverable signature. Use deserialize_vrs instead")]
     NoRecoveryId,
 
+    /// The message signature header byte is outside the range 27..=34
+    #[error("Invalid recoverable signature header byte: {0}")]
+    BadRecoveryHeader(u8),
+
+    /// Attempted to derive a child past the maximum depth of 255
+    #[error("Attempted to derive a child past the maximum depth of 255")]
+    MaxDepthExceeded,
+
     /// Attempted to deserialize a very long path
     #[error("Invalid Bip32 Path.")]
     InvalidBip32Path,
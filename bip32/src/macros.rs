@@ -55,6 +55,43 @@ macro_rules! inherit_signer {
             {
                 self.$attr.sign_digest_recoverable(digest)
             }
+
+            /// Produce a recoverable ECDSA signature over a 32-byte message digest, e.g. a
+            /// Bitcoin signed message hash. The signature is marked as made by a compressed key.
+            pub fn sign_recoverable(
+                &self,
+                digest: &[u8; 32],
+            ) -> Result<crate::primitives::RecoverableSignature, crate::Bip32Error> {
+                let key: &k256::ecdsa::SigningKey = self.as_ref();
+                let (sig, recovery_id) = key.sign_prehash_recoverable(digest)?;
+                Ok(crate::primitives::RecoverableSignature::new(
+                    sig,
+                    recovery_id,
+                    true,
+                ))
+            }
+
+            /// Produce a BIP340 Schnorr signature over a 32-byte message digest, e.g. a BIP341
+            /// sighash. The nonce is derived deterministically, with all-zero auxiliary
+            /// randomness.
+            pub fn sign_schnorr(
+                &self,
+                digest: &[u8; 32],
+            ) -> Result<crate::primitives::SchnorrSig, crate::Bip32Error> {
+                self.sign_schnorr_with_aux_rand(digest, &[0u8; 32])
+            }
+
+            /// Produce a BIP340 Schnorr signature over a 32-byte message digest, mixing
+            /// `aux_rand` into the nonce as BIP340 recommends.
+            pub fn sign_schnorr_with_aux_rand(
+                &self,
+                digest: &[u8; 32],
+                aux_rand: &[u8; 32],
+            ) -> Result<crate::primitives::SchnorrSig, crate::Bip32Error> {
+                let key: &k256::ecdsa::SigningKey = self.as_ref();
+                let key = k256::schnorr::SigningKey::from_bytes(&key.to_bytes())?;
+                Ok(key.sign_prehash_with_aux_rand(digest, aux_rand)?)
+            }
         }
     };
 }
@@ -69,6 +106,44 @@ macro_rules! inherit_verifier {
                 data.copy_from_slice(&generic_array);
                 data
             }
+
+            /// The BIP32 key identifier: the HASH160 of the compressed sec1 public key.
+            pub fn identifier(&self) -> coins_core::hashes::Hash160Digest {
+                self.pubkey_hash160()
+            }
+
+            /// Return the bitcoin HASH160 of the compressed sec1 public key.
+            pub fn pubkey_hash160(&self) -> coins_core::hashes::Hash160Digest {
+                use coins_core::hashes::MarkedDigest;
+                coins_core::hashes::Hash160::digest_marked(&self.to_sec1_bytes())
+            }
+
+            /// The fingerprint is the first 4 bytes of the key identifier. It populates the
+            /// parent fingerprint of child keys, and the key source of PSBT fields.
+            pub fn fingerprint(&self) -> crate::primitives::KeyFingerprint {
+                use coins_core::hashes::MarkedDigestOutput;
+                let mut buf = [0u8; 4];
+                buf.copy_from_slice(&self.identifier().as_slice()[..4]);
+                buf.into()
+            }
+
+            /// Get the BIP340 x-only representation of the public key.
+            pub fn to_x_only_bytes(&self) -> [u8; 32] {
+                let mut data = [0u8; 32];
+                data.copy_from_slice(&self.to_sec1_bytes()[1..]);
+                data
+            }
+
+            /// Verify a BIP340 Schnorr signature over a 32-byte message digest. The key is
+            /// used with an even y coordinate, as BIP340 requires.
+            pub fn verify_schnorr(
+                &self,
+                digest: &[u8; 32],
+                signature: &crate::primitives::SchnorrSig,
+            ) -> Result<(), crate::Bip32Error> {
+                let key = k256::schnorr::VerifyingKey::from_bytes(&self.to_x_only_bytes())?;
+                Ok(key.verify_raw(digest, signature)?)
+            }
         }
 
         impl<D> k256::ecdsa::signature::DigestVerifier<D, k256::ecdsa::Signature> for $struct_name
@@ -18,15 +18,20 @@ fn try_parse_index(s: &str) -> Result<u32, Bip32Error> {
         false
     };
 
-    index_str
-        .parse::<u32>()
-        .map(|v| if harden { v + BIP32_HARDEN } else { v })
-        .map_err(|_| Bip32Error::MalformattedDerivation(s.to_owned()))
+    let malformatted = || Bip32Error::MalformattedDerivation(s.to_owned());
+    let index = index_str.parse::<u32>().map_err(|_| malformatted())?;
+    if !harden {
+        return Ok(index);
+    }
+    // hardened indices must be below 2^31 before the harden flag is applied
+    if index >= BIP32_HARDEN {
+        return Err(malformatted());
+    }
+    Ok(index + BIP32_HARDEN)
 }
 
 fn try_parse_path(path: &str) -> Result<Vec<u32>, Bip32Error> {
-    path.to_owned()
-        .split('/')
+    path.split('/')
         .filter(|v| v != &"m")
         .map(try_parse_index)
         .collect::<Result<Vec<u32>, Bip32Error>>()
@@ -95,7 +100,7 @@ impl DerivationPath {
     }
 
     /// Make an iterator over the path indices
-    pub fn iter(&self) -> Iter<u32> {
+    pub fn iter(&self) -> Iter<'_, u32> {
         self.0.iter()
     }
 
@@ -279,12 +284,17 @@ impl ByteFormat for KeyDerivation {
 }
 
 #[cfg(test)]
-pub mod test {
+mod test {
     use super::*;
 
     #[test]
     fn it_parses_index_strings() {
-        let cases = [("32", 32), ("32h", 32 + BIP32_HARDEN), ("0h", BIP32_HARDEN)];
+        let cases = [
+            ("32", 32),
+            ("32h", 32 + BIP32_HARDEN),
+            ("0h", BIP32_HARDEN),
+            ("2147483647'", u32::MAX),
+        ];
         for case in cases.iter() {
             match try_parse_index(case.0) {
                 Ok(v) => assert_eq!(v, case.1),
@@ -295,7 +305,15 @@ pub mod test {
 
     #[test]
     fn it_handles_malformatted_indices() {
-        let cases = ["-", "h", "toast", "憂鬱"];
+        let cases = [
+            "-",
+            "h",
+            "toast",
+            "憂鬱",
+            "2147483648'",
+            "4294967295h",
+            "4294967296",
+        ];
         for case in cases.iter() {
             match try_parse_index(case) {
                 Ok(_) => panic!("expected an error"),
@@ -325,7 +343,7 @@ pub mod test {
 
     #[test]
     fn it_handles_malformatted_derivations() {
-        let cases = ["//", "m/", "-", "h", "toast", "憂鬱"];
+        let cases = ["//", "m/", "-", "h", "toast", "憂鬱", "m/84'/2147483648'"];
         for case in cases.iter() {
             match try_parse_path(case) {
                 Ok(_) => panic!("expected an error"),
@@ -366,7 +384,7 @@ pub mod test {
         let cases = ["//", "m/", "-", "h", "toast", "憂鬱"];
 
         for case in cases.iter() {
-            let path: Result<DerivationPath, _> = case.parse().map_err(Into::into);
+            let path: Result<DerivationPath, _> = case.parse();
             match path {
                 Ok(_) => panic!("expected an error"),
                 Err(Bip32Error::MalformattedDerivation(e)) => assert_eq!(&e, case),
@@ -18,6 +18,12 @@ pub use k256::{
     elliptic_curve::sec1::ToEncodedPoint as _,
 };
 
+/// The BIP32 key identifier of a public key: the HASH160 of its compressed sec1 serialization
+pub fn identifier_of(k: &VerifyingKey) -> coins_core::hashes::Hash160Digest {
+    use coins_core::hashes::MarkedDigest;
+    coins_core::hashes::Hash160::digest_marked(&k.to_sec1_bytes())
+}
+
 /// shortcut for easy usage
 pub fn fingerprint_of(k: &VerifyingKey) -> KeyFingerprint {
     use coins_core::hashes::Digest;
@@ -1,5 +1,6 @@
 use crate::Bip32Error;
 use coins_core::ser::ByteFormat;
+use k256::ecdsa;
 use std::io::{Read, Write};
 
 /// We treat the bip32 xpub bip49 ypub and bip84 zpub convention as a hint regarding address type.
@@ -97,3 +98,232 @@ impl PartialEq for XKeyInfo {
             && self.chain_code == other.chain_code
     }
 }
+
+/// A recoverable ECDSA signature, as used by Bitcoin signed messages. The signed message header
+/// byte encodes the recovery id, and whether the signer's address uses a compressed pubkey.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct RecoverableSignature {
+    sig: ecdsa::Signature,
+    recovery_id: ecdsa::RecoveryId,
+    compressed: bool,
+}
+
+impl RecoverableSignature {
+    /// Instantiate a new recoverable signature.
+    pub fn new(sig: ecdsa::Signature, recovery_id: ecdsa::RecoveryId, compressed: bool) -> Self {
+        Self {
+            sig,
+            recovery_id,
+            compressed,
+        }
+    }
+
+    /// Instantiate a recoverable signature from a signature and a signed message header byte,
+    /// `27 + recovery_id`, plus 4 if the address uses a compressed pubkey.
+    pub fn from_header_byte(sig: ecdsa::Signature, header: u8) -> Result<Self, Bip32Error> {
+        if !(27..=34).contains(&header) {
+            return Err(Bip32Error::BadRecoveryHeader(header));
+        }
+        let flags = header - 27;
+        let recovery_id =
+            ecdsa::RecoveryId::from_byte(flags & 3).ok_or(Bip32Error::BadRecoveryHeader(header))?;
+        Ok(Self::new(sig, recovery_id, flags & 4 == 4))
+    }
+
+    /// The signed message header byte.
+    pub fn header_byte(&self) -> u8 {
+        27 + self.recovery_id.to_byte() + if self.compressed { 4 } else { 0 }
+    }
+
+    /// Read a 65-byte compact signature: the signed message header byte, followed by the 32-byte
+    /// `r` and `s` values. This is the format that Bitcoin Core's `signmessage` base64-encodes.
+    pub fn from_compact(data: &[u8; 65]) -> Result<Self, Bip32Error> {
+        let sig = ecdsa::Signature::from_slice(&data[1..])?;
+        Self::from_header_byte(sig, data[0])
+    }
+
+    /// Serialize to the 65-byte compact format. See `from_compact`.
+    pub fn to_compact(&self) -> [u8; 65] {
+        let mut data = [0u8; 65];
+        data[0] = self.header_byte();
+        data[1..].copy_from_slice(&self.sig.to_bytes());
+        data
+    }
+
+    /// Return a copy of this signature, marked as made by a compressed or uncompressed key.
+    pub fn with_compression(&self, compressed: bool) -> Self {
+        Self {
+            compressed,
+            ..*self
+        }
+    }
+
+    /// The ECDSA signature.
+    pub fn signature(&self) -> &ecdsa::Signature {
+        &self.sig
+    }
+
+    /// The recovery id.
+    pub fn recovery_id(&self) -> ecdsa::RecoveryId {
+        self.recovery_id
+    }
+
+    /// True if the signer's address uses a compressed pubkey.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+/// Conversions between ECDSA signatures and the fixed 64-byte compact `r || s` form, as some
+/// protocols and storage formats prefer it to DER.
+pub trait CompactSignature: Sized {
+    /// Serialize to the compact form. `r` and `s` are each zero-padded to 32 bytes. `s` is
+    /// normalized to the low half of the curve order, as Bitcoin's standardness rules require.
+    fn to_compact(&self) -> [u8; 64];
+
+    /// Read a signature from the compact form. Errors if `r` or `s` is zero or exceeds the curve
+    /// order.
+    fn from_compact(data: &[u8; 64]) -> Result<Self, Bip32Error>;
+}
+
+impl CompactSignature for ecdsa::Signature {
+    fn to_compact(&self) -> [u8; 64] {
+        let mut data = [0u8; 64];
+        data.copy_from_slice(&self.normalize_s().unwrap_or(*self).to_bytes());
+        data
+    }
+
+    fn from_compact(data: &[u8; 64]) -> Result<Self, Bip32Error> {
+        Ok(ecdsa::Signature::from_slice(data)?)
+    }
+}
+
+/// Recover the public key that produced `sig` over the 32-byte message digest `digest`.
+pub fn recover_pubkey(
+    digest: &[u8; 32],
+    sig: &RecoverableSignature,
+) -> Result<ecdsa::VerifyingKey, Bip32Error> {
+    Ok(ecdsa::VerifyingKey::recover_from_prehash(
+        digest,
+        &sig.sig,
+        sig.recovery_id,
+    )?)
+}
+
+/// A BIP340 Schnorr signature.
+pub type SchnorrSig = k256::schnorr::Signature;
+
+/// The parity of a point's y coordinate. BIP341 control blocks commit to the parity of the
+/// tweaked output key.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Parity {
+    /// Even y coordinate
+    Even,
+    /// Odd y coordinate
+    Odd,
+}
+
+impl Parity {
+    /// The parity bit, 0 for even and 1 for odd.
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Parity::Even => 0,
+            Parity::Odd => 1,
+        }
+    }
+
+    /// The parity of the y coordinate of `key`.
+    pub fn of(key: &ecdsa::VerifyingKey) -> Self {
+        if key.to_sec1_bytes()[0] == 0x03 {
+            Parity::Odd
+        } else {
+            Parity::Even
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_core::hashes::{Digest, Hash256};
+    use std::convert::TryInto;
+
+    #[test]
+    fn it_converts_der_signatures_to_and_from_compact() {
+        // r = 1 and s = n - 1. r is zero-padded to 32 bytes, and s is normalized to 1
+        let der = hex::decode(
+            "3026020101022100fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140",
+        )
+        .unwrap();
+        let normalized = hex::decode("3006020101020101").unwrap();
+        let mut expected = [0u8; 64];
+        expected[31] = 1;
+        expected[63] = 1;
+
+        let sig = ecdsa::Signature::from_der(&der).unwrap();
+        let compact = sig.to_compact();
+        assert_eq!(compact[..], expected[..]);
+
+        let sig = ecdsa::Signature::from_compact(&compact).unwrap();
+        assert_eq!(sig.to_der().as_bytes(), &normalized[..]);
+        assert_eq!(sig.to_compact()[..], compact[..]);
+
+        assert!(ecdsa::Signature::from_compact(&[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn it_round_trips_compact_recoverable_signatures() {
+        // The `signmessage` digest of "Hello, world!", signed with secret 0x11..11
+        let mut message = b"\x18Bitcoin Signed Message:\n\x0d".to_vec();
+        message.extend(b"Hello, world!");
+        let digest: [u8; 32] = Hash256::digest(&message).into();
+        let compact: [u8; 65] = hex::decode("20802d8eb202a301f625fccddcef982064921cbbeff5ddd1c08828530f9f651ffb385e168e889a52e72d3cda9e0adbbe8fc5d546945339ff474c360b106dc97f47").unwrap().try_into().unwrap();
+        let pubkey =
+            hex::decode("034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa")
+                .unwrap();
+
+        let sig = RecoverableSignature::from_compact(&compact).unwrap();
+        assert!(sig.is_compressed());
+        assert_eq!(sig.recovery_id().to_byte(), 1);
+        assert_eq!(sig.to_compact()[..], compact[..]);
+        assert_eq!(
+            recover_pubkey(&digest, &sig).unwrap().to_sec1_bytes()[..],
+            pubkey[..]
+        );
+
+        let mut bad_header = compact;
+        bad_header[0] = 35;
+        assert!(RecoverableSignature::from_compact(&bad_header).is_err());
+    }
+
+    #[test]
+    fn it_round_trips_recovery_header_bytes() {
+        let sig = ecdsa::Signature::from_slice(&[1u8; 64]).unwrap();
+        for recid in 0..4u8 {
+            let recovery_id = ecdsa::RecoveryId::from_byte(recid).unwrap();
+            let uncompressed = RecoverableSignature::new(sig, recovery_id, false);
+            assert_eq!(uncompressed.header_byte(), 27 + recid);
+            assert_eq!(
+                RecoverableSignature::from_header_byte(sig, 27 + recid).unwrap(),
+                uncompressed
+            );
+
+            let compressed = uncompressed.with_compression(true);
+            assert!(compressed.is_compressed());
+            assert_eq!(compressed.recovery_id(), recovery_id);
+            assert_eq!(compressed.header_byte(), 31 + recid);
+            assert_eq!(
+                RecoverableSignature::from_header_byte(sig, 31 + recid).unwrap(),
+                compressed
+            );
+            assert_eq!(compressed.with_compression(false), uncompressed);
+        }
+
+        for header in [0u8, 26, 35, 255].iter() {
+            match RecoverableSignature::from_header_byte(sig, *header) {
+                Err(Bip32Error::BadRecoveryHeader(h)) => assert_eq!(h, *header),
+                e => panic!("expected bad header error, got {:?}", e),
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use coins_core::hashes::{Hash160, Hash160Digest, MarkedDigest, MarkedDigestOutput};
+use coins_core::hashes::{tagged_hash, Hash160Digest};
 use hmac::{Hmac, Mac};
-use k256::{ecdsa, elliptic_curve::sec1::FromEncodedPoint};
+use k256::{
+    ecdsa,
+    elliptic_curve::{sec1::FromEncodedPoint, PrimeField},
+};
 use sha2::Sha512;
 use std::{
     convert::{TryFrom, TryInto},
@@ -8,11 +11,70 @@ use std::{
 };
 
 use crate::{
+    enc::{decode_wif, encode_wif, WifNetwork, WifString, XKeyEncoder},
     path::DerivationPath,
-    primitives::{ChainCode, Hint, KeyFingerprint, XKeyInfo},
+    primitives::{ChainCode, Hint, KeyFingerprint, Parity, XKeyInfo},
     Bip32Error, BIP32_HARDEN,
 };
 
+/// Compute the BIP341 TapTweak scalar `int(tagged_hash("TapTweak", P || merkle_root))`, where
+/// `P` is the x-only internal key.
+fn tap_tweak_scalar(
+    internal_key: &ecdsa::VerifyingKey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<k256::Scalar, Bip32Error> {
+    let mut data = internal_key.to_sec1_bytes()[1..].to_vec();
+    if let Some(root) = merkle_root {
+        data.extend(root);
+    }
+    Option::from(k256::Scalar::from_repr(
+        tagged_hash("TapTweak", &data).into(),
+    ))
+    .ok_or(Bip32Error::InvalidTapTweak)
+}
+
+/// Tweak an internal key for use as a BIP341 output key,
+/// `Q = P + int(tagged_hash("TapTweak", P || merkle_root))·G`. `P` is the internal key with an
+/// even y coordinate. Without a `merkle_root`, this is the BIP86 key-path-only output key.
+/// Returns `Q` and the parity of its y coordinate.
+pub fn tap_tweak_pubkey(
+    internal_key: &ecdsa::VerifyingKey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<(ecdsa::VerifyingKey, Parity), Bip32Error> {
+    let tweak = tap_tweak_scalar(internal_key, merkle_root)?;
+
+    let mut point =
+        k256::ProjectivePoint::from_encoded_point(&internal_key.to_encoded_point(true)).unwrap();
+    if Parity::of(internal_key) == Parity::Odd {
+        point = -point;
+    }
+    point.add_assign(k256::ProjectivePoint::GENERATOR.mul(tweak));
+
+    let key = ecdsa::VerifyingKey::from_affine(point.to_affine())
+        .map_err(|_| Bip32Error::InvalidTapTweak)?;
+    Ok((key, Parity::of(&key)))
+}
+
+/// Tweak the secret of an internal key, so that it signs for the output key produced by
+/// `tap_tweak_pubkey`. Returns the tweaked secret, and the parity of the output key.
+pub fn tap_tweak_privkey(
+    internal_key: &ecdsa::SigningKey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<(ecdsa::SigningKey, Parity), Bip32Error> {
+    let tweak = tap_tweak_scalar(internal_key.verifying_key(), merkle_root)?;
+
+    let mut secret = **internal_key.as_nonzero_scalar();
+    if Parity::of(internal_key.verifying_key()) == Parity::Odd {
+        secret = -secret;
+    }
+    let tweaked = Option::<k256::NonZeroScalar>::from(k256::NonZeroScalar::new(secret + tweak))
+        .ok_or(Bip32Error::InvalidTapTweak)?;
+
+    let key = ecdsa::SigningKey::from(tweaked);
+    let parity = Parity::of(key.verifying_key());
+    Ok((key, parity))
+}
+
 /// The BIP32-defined seed used for derivation of the root node.
 pub const SEED: &[u8; 12] = b"Bitcoin seed";
 
@@ -124,11 +186,57 @@ impl XPriv {
         }
     }
 
+    /// The BIP32 key identifier: the HASH160 of the compressed public key
+    pub fn identifier(&self) -> Hash160Digest {
+        self.verify_key().identifier()
+    }
+
     /// The fingerprint is the first 4 bytes of the HASH160 of the public key
     pub fn fingerprint(&self) -> KeyFingerprint {
         self.verify_key().fingerprint()
     }
 
+    /// Encode the private key in Wallet Import Format. The extended key information is lost.
+    pub fn to_wif(&self, network: WifNetwork, compressed: bool) -> WifString {
+        encode_wif(&self.key, network, compressed)
+    }
+
+    /// Decode a private key in Wallet Import Format, returning the key, its network, and
+    /// whether it signals a compressed pubkey. WIF carries no extended key information, so the
+    /// key has depth 0, a zero chain code, and a `Legacy` hint. Don't derive children from it.
+    pub fn from_wif(s: &str) -> Result<(XPriv, WifNetwork, bool), Bip32Error> {
+        let (key, network, compressed) = decode_wif(s)?;
+        let xkey_info = XKeyInfo {
+            depth: 0,
+            parent: KeyFingerprint([0u8; 4]),
+            index: 0,
+            chain_code: ChainCode([0u8; 32]),
+            hint: Hint::Legacy,
+        };
+        Ok((XPriv::new(key, xkey_info), network, compressed))
+    }
+
+    /// Serialize the key to a base58check string, e.g. `xprv...`. The encoder `E` selects the
+    /// network version bytes, so `TestnetEncoder` produces `tprv...`.
+    pub fn to_base58check<E: XKeyEncoder>(&self) -> Result<String, Bip32Error> {
+        E::xpriv_to_base58(self)
+    }
+
+    /// Read a key from a base58check string, using the network version bytes of the encoder
+    /// `E`.
+    pub fn from_base58check<E: XKeyEncoder>(s: &str) -> Result<XPriv, Bip32Error> {
+        E::xpriv_from_base58(s)
+    }
+
+    /// Tweak the key for a BIP341 key-path spend. See `tap_tweak_privkey`. The tweaked key is
+    /// not part of this key's derivation tree, so it is returned without a chain code.
+    pub fn tap_tweak(
+        &self,
+        merkle_root: Option<[u8; 32]>,
+    ) -> Result<(ecdsa::SigningKey, Parity), Bip32Error> {
+        tap_tweak_privkey(&self.key, merkle_root)
+    }
+
     /// Generate a customized root node
     pub fn root_node(
         hmac_key: &[u8],
@@ -148,6 +256,17 @@ impl XPriv {
         Self::custom_root_from_seed(data, hint)
     }
 
+    /// Generate a root node from a hex-encoded seed, as some wallets export. Uses the
+    /// BIP32-standard hmac key. The seed must hold between 128 and 512 bits, as BIP32
+    /// recommends.
+    pub fn from_hex_seed(seed: &str, hint: Option<Hint>) -> Result<XPriv, Bip32Error> {
+        let seed = hex::decode(seed)?;
+        if seed.len() > 64 {
+            return Err(Bip32Error::SeedTooLong);
+        }
+        Self::root_from_seed(&seed, hint)
+    }
+
     /// Instantiate a root node using a custom HMAC key.
     pub fn custom_root_node(
         hmac_key: &[u8],
@@ -189,7 +308,8 @@ impl XPriv {
     }
 
     /// Derive a series of child indices. Allows traversing several levels of the tree at once.
-    /// Accepts an iterator producing u32, or a string.
+    /// Accepts an iterator producing u32, or a string. Errors with `MaxDepthExceeded` before
+    /// deriving if the path would pass depth 255.
     pub fn derive_path<E, P>(&self, p: P) -> Result<Self, Bip32Error>
     where
         E: Into<Bip32Error>,
@@ -200,6 +320,9 @@ impl XPriv {
         if path.is_empty() {
             return Ok(self.clone());
         }
+        if self.xkey_info.depth as usize + path.len() > u8::MAX as usize {
+            return Err(Bip32Error::MaxDepthExceeded);
+        }
 
         let mut current = self.to_owned();
         for index in path.iter() {
@@ -211,6 +334,11 @@ impl XPriv {
 
 impl Parent for XPriv {
     fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
+        let depth = self
+            .xkey_info
+            .depth
+            .checked_add(1)
+            .ok_or(Bip32Error::MaxDepthExceeded)?;
         let hardened = index >= BIP32_HARDEN;
 
         let key: &ecdsa::SigningKey = self.as_ref();
@@ -240,7 +368,7 @@ impl Parent for XPriv {
         Ok(Self {
             key: ecdsa::SigningKey::from(tweaked),
             xkey_info: XKeyInfo {
-                depth: self.xkey_info.depth + 1,
+                depth,
                 parent: self.fingerprint(),
                 index,
                 chain_code,
@@ -299,18 +427,25 @@ impl XPub {
         Self { key, xkey_info }
     }
 
-    /// The fingerprint is the first 4 bytes of the HASH160 of the serialized
-    /// public key.
-    pub fn fingerprint(&self) -> KeyFingerprint {
-        let digest = self.pubkey_hash160();
-        let mut buf = [0u8; 4];
-        buf.copy_from_slice(&digest.as_slice()[..4]);
-        buf.into()
+    /// Serialize the key to a base58check string, e.g. `xpub...`. The encoder `E` selects the
+    /// network version bytes, so `TestnetEncoder` produces `tpub...`.
+    pub fn to_base58check<E: XKeyEncoder>(&self) -> Result<String, Bip32Error> {
+        E::xpub_to_base58(self)
     }
 
-    /// Return the bitcoin HASH160 of the serialized public key
-    pub fn pubkey_hash160(&self) -> Hash160Digest {
-        Hash160::digest_marked(self.key.to_sec1_bytes().as_ref())
+    /// Read a key from a base58check string, using the network version bytes of the encoder
+    /// `E`.
+    pub fn from_base58check<E: XKeyEncoder>(s: &str) -> Result<XPub, Bip32Error> {
+        E::xpub_from_base58(s)
+    }
+
+    /// Tweak the key to produce a BIP341 output key. See `tap_tweak_pubkey`. The output key is
+    /// not part of this key's derivation tree, so it is returned without a chain code.
+    pub fn tap_tweak(
+        &self,
+        merkle_root: Option<[u8; 32]>,
+    ) -> Result<(ecdsa::VerifyingKey, Parity), Bip32Error> {
+        tap_tweak_pubkey(&self.key, merkle_root)
     }
 }
 
@@ -325,6 +460,11 @@ impl Parent for XPub {
         if index >= BIP32_HARDEN {
             return Err(Bip32Error::HardenedDerivationFailed);
         }
+        let depth = self
+            .xkey_info
+            .depth
+            .checked_add(1)
+            .ok_or(Bip32Error::MaxDepthExceeded)?;
         let mut data = vec![];
         // secp256k1 points are converted to compressed form
         // https://github.com/RustCrypto/elliptic-curves/blob/3ee0ba1aa5bb74777928c21bb198d2a696f0dd9d/k256/src/lib.rs#L98
@@ -351,7 +491,7 @@ impl Parent for XPub {
         Ok(Self {
             key,
             xkey_info: XKeyInfo {
-                depth: self.xkey_info.depth + 1,
+                depth,
                 parent: self.fingerprint(),
                 index,
                 chain_code,
@@ -365,10 +505,10 @@ impl Parent for XPub {
 mod test {
     use super::*;
     use crate::{
-        enc::{MainnetEncoder, XKeyEncoder},
+        enc::{MainnetEncoder, TestnetEncoder},
         primitives::*,
     };
-    use coins_core::hashes::Hash256;
+    use coins_core::hashes::{Digest, Hash256};
     use k256::ecdsa::signature::{DigestSigner, DigestVerifier};
 
     use hex;
@@ -581,6 +721,326 @@ mod test {
         assert_eq!(&recovered.to_sec1_bytes(), &child_xpub.key.to_sec1_bytes());
     }
 
+    #[test]
+    fn it_signs_recoverable_message_digests() {
+        // The `signmessage` digest of "Hello, world!", signed with secret 0x11..11
+        let mut message = b"\x18Bitcoin Signed Message:\n\x0d".to_vec();
+        message.extend(b"Hello, world!");
+        let digest: [u8; 32] = Hash256::digest(&message).into();
+        let compact = hex::decode("20802d8eb202a301f625fccddcef982064921cbbeff5ddd1c08828530f9f651ffb385e168e889a52e72d3cda9e0adbbe8fc5d546945339ff474c360b106dc97f47").unwrap();
+
+        let key = ecdsa::SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let xpriv = XPriv::new(
+            key,
+            XPriv::root_from_seed(&[0; 32], None).unwrap().xkey_info,
+        );
+        let sig = xpriv.sign_recoverable(&digest).unwrap();
+        assert_eq!(sig.to_compact()[..], compact[..]);
+
+        let recovered = recover_pubkey(&digest, &sig).unwrap();
+        assert_eq!(recovered, *xpriv.verify_key().as_ref());
+    }
+
+    #[test]
+    fn it_round_trips_wifs() {
+        let xpriv_str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".to_owned();
+        let xpriv: XPriv = MainnetEncoder::xpriv_from_base58(&xpriv_str).unwrap();
+
+        for network in [WifNetwork::Mainnet, WifNetwork::Testnet].iter() {
+            for compressed in [true, false].iter() {
+                let wif = xpriv.to_wif(*network, *compressed);
+                let (decoded, decoded_network, decoded_compressed) = XPriv::from_wif(&wif).unwrap();
+                assert_eq!(decoded.key, xpriv.key);
+                assert_eq!(decoded_network, *network);
+                assert_eq!(decoded_compressed, *compressed);
+                assert_eq!(decoded.xkey_info.depth, 0);
+                assert_eq!(decoded.xkey_info.chain_code, ChainCode([0u8; 32]));
+                assert_eq!(decoded.to_wif(*network, *compressed), wif);
+            }
+        }
+
+        match XPriv::from_wif(&xpriv_str) {
+            Err(Bip32Error::BadWif) => {}
+            e => panic!("expected bad wif, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_stops_deriving_at_the_max_depth() {
+        let xpriv_str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".to_owned();
+        let xpriv: XPriv = MainnetEncoder::xpriv_from_base58(&xpriv_str).unwrap();
+
+        let deepest = xpriv.derive_path(&[0u32; 255][..]).unwrap();
+        assert_eq!(deepest.xkey_info.depth, 255);
+        match deepest.derive_child(0) {
+            Err(Bip32Error::MaxDepthExceeded) => {}
+            e => panic!("expected max depth error, got {:?}", e),
+        }
+        match deepest.verify_key().derive_child(0) {
+            Err(Bip32Error::MaxDepthExceeded) => {}
+            e => panic!("expected max depth error, got {:?}", e),
+        }
+        match xpriv.derive_path(&[0u32; 256][..]) {
+            Err(Bip32Error::MaxDepthExceeded) => {}
+            e => panic!("expected max depth error, got {:?}", e),
+        }
+        match xpriv.derive_child(0).unwrap().derive_path(&[0u32; 255][..]) {
+            Err(Bip32Error::MaxDepthExceeded) => {}
+            e => panic!("expected max depth error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_signs_and_verifies_schnorr_bip340_vectors() {
+        let info = XKeyInfo {
+            depth: 0,
+            parent: KeyFingerprint([0u8; 4]),
+            index: 0,
+            chain_code: ChainCode([0u8; 32]),
+            hint: Hint::Legacy,
+        };
+        let to_32 = |s: &str| {
+            let mut buf = [0u8; 32];
+            buf.copy_from_slice(&hex::decode(s).unwrap());
+            buf
+        };
+
+        // BIP340 test vectors 0 through 2. (secret key, pubkey, aux_rand, message, signature)
+        let cases = [
+            (
+                "0000000000000000000000000000000000000000000000000000000000000003",
+                "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+            ),
+            (
+                "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+                "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+                "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+            ),
+            (
+                "c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9",
+                "dd308afec5777e13121fa72b9cc1b7cc0139715309b086c960e18fd969774eb8",
+                "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906",
+                "7e2d58d8b3bcdf1abadec7829054f90dda9805aab56c77333024b9d0a508b75c",
+                "5831aaeed7b44bb74e5eab94ba9d4294c49bcf2a60728d8b4c200f50dd313c1bab745879a5ad954a72c45a91c3a51d3c7adea98d82f8481e0e1e03674a6f3fb7",
+            ),
+        ];
+        for (secret, pubkey, aux_rand, message, signature) in cases.iter() {
+            let key = ecdsa::SigningKey::from_bytes(&to_32(secret).into()).unwrap();
+            let xpriv = XPriv::new(key, info);
+            let xpub = xpriv.verify_key();
+            assert_eq!(xpub.to_x_only_bytes(), to_32(pubkey));
+
+            let digest = to_32(message);
+            let sig = xpriv
+                .sign_schnorr_with_aux_rand(&digest, &to_32(aux_rand))
+                .unwrap();
+            assert_eq!(sig.to_bytes().to_vec(), hex::decode(signature).unwrap());
+            xpub.verify_schnorr(&digest, &sig).unwrap();
+
+            let sig = xpriv.sign_schnorr(&digest).unwrap();
+            xpub.verify_schnorr(&digest, &sig).unwrap();
+            assert!(xpub.verify_schnorr(&[1u8; 32], &sig).is_err());
+        }
+
+        // BIP340 test vector 6, where R has an odd y coordinate
+        let xpub = XPub::new(
+            ecdsa::VerifyingKey::from_sec1_bytes(
+                &hex::decode("02dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659")
+                    .unwrap(),
+            )
+            .unwrap(),
+            info,
+        );
+        let sig = SchnorrSig::try_from(
+            &hex::decode("fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a14602975563cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2")
+                .unwrap()[..],
+        )
+        .unwrap();
+        assert!(xpub
+            .verify_schnorr(
+                &to_32("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89"),
+                &sig
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn it_tap_tweaks_keys() {
+        let info = XKeyInfo {
+            depth: 0,
+            parent: KeyFingerprint([0u8; 4]),
+            index: 0,
+            chain_code: ChainCode([0u8; 32]),
+            hint: Hint::Legacy,
+        };
+
+        // BIP341 wallet test vectors. (internal key, merkle root, output key)
+        let cases = [
+            (
+                "02d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+                None,
+                "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+            ),
+            (
+                "02187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+                Some("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"),
+                "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+            ),
+        ];
+        for (internal, root, output) in cases.iter() {
+            let key =
+                ecdsa::VerifyingKey::from_sec1_bytes(&hex::decode(internal).unwrap()).unwrap();
+            let root = root.map(|r| hex::decode(r).unwrap().try_into().unwrap());
+            let (tweaked, parity) = XPub::new(key, info).tap_tweak(root).unwrap();
+            assert_eq!(
+                tweaked.to_sec1_bytes()[1..].to_vec(),
+                hex::decode(output).unwrap()
+            );
+            assert_eq!(parity, Parity::Odd);
+        }
+
+        // (secret, merkle root, tweaked secret, parity). Each tweaked secret must match the
+        // public tweak of its key, which the BIP341 vectors above check.
+        let cases = [
+            (
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                None,
+                "3cf5216d476a5e637bf0da674e50ddf55c403270dd36494dfcca438132fa30e8",
+                Parity::Odd,
+            ),
+            (
+                "0000000000000000000000000000000000000000000000000000000000000003",
+                None,
+                "965a70e32ca36371d64d9942813b6e96e42498e4483c319cd4316cbc53485c85",
+                Parity::Even,
+            ),
+            // odd internal key
+            (
+                "0000000000000000000000000000000000000000000000000000000000000006",
+                Some([7u8; 32]),
+                "d1e4bd44bec08edb2e94862a5ca57a4a30b57ce7c7dd9da7ff530af5f631c02d",
+                Parity::Odd,
+            ),
+        ];
+        for (secret, root, tweaked_secret, expected_parity) in cases.iter() {
+            let key = ecdsa::SigningKey::from_slice(&hex::decode(secret).unwrap()).unwrap();
+            let xpriv = XPriv::new(key, info);
+            let (tweaked, parity) = xpriv.tap_tweak(*root).unwrap();
+            assert_eq!(
+                tweaked.to_bytes().to_vec(),
+                hex::decode(tweaked_secret).unwrap()
+            );
+            assert_eq!(parity, *expected_parity);
+
+            let (tweaked_pub, pub_parity) = xpriv.verify_key().tap_tweak(*root).unwrap();
+            assert_eq!(tweaked_pub, *tweaked.verifying_key());
+            assert_eq!(pub_parity, parity);
+        }
+    }
+
+    #[test]
+    fn it_derives_parsed_paths() {
+        let xpriv_str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".to_owned();
+        let xpriv: XPriv = MainnetEncoder::xpriv_from_base58(&xpriv_str).unwrap();
+
+        // an empty path returns the key unchanged
+        let path: DerivationPath = "m".parse().unwrap();
+        assert!(path.is_empty());
+        assert_eq!(xpriv.derive_path(&path).unwrap(), xpriv);
+
+        // both harden markers produce the same key
+        let expected = xpriv
+            .derive_child(BIP32_HARDEN)
+            .unwrap()
+            .derive_child(1)
+            .unwrap();
+        for path in ["m/0'/1", "m/0h/1"].iter() {
+            let path: DerivationPath = path.parse().unwrap();
+            assert_eq!(xpriv.derive_path(&path).unwrap(), expected);
+        }
+
+        match "m/2147483648'/1".parse::<DerivationPath>() {
+            Err(Bip32Error::MalformattedDerivation(_)) => {}
+            e => panic!("expected malformatted derivation, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn it_round_trips_base58check_keys() {
+        // BIP32 test vector 1, m/0'/1, with testnet version bytes. The mainnet strings are the
+        // ones given in BIP32.
+        let xpriv_str = "tprv8e8VYgZxtHsSdGrtvdxYaSrryZGiYviWzGWtDDKTGh5NMXAEB8gYSCLHpFCywNs5uqV7ghRjimALQJkRFZnUrLHpzi2pGkwqLtbubgWuQ8q";
+        let xpub_str = "tpubDApXh6cD2fZ7WjtgpHd8yrWyYaneiFuRZa7fVjMkgxsmC1QzoXW8cgx9zQFJ81Jx4deRGfRE7yXA9A3STsxXj4CKEZJHYgpMYikkas9DBTP";
+
+        let root =
+            XPriv::from_hex_seed("000102030405060708090a0b0c0d0e0f", Some(Hint::Legacy)).unwrap();
+        let xpriv = root
+            .derive_path("m/0'/1".parse::<DerivationPath>().unwrap())
+            .unwrap();
+        let xpub = xpriv.verify_key();
+
+        assert_eq!(xpriv.to_base58check::<TestnetEncoder>().unwrap(), xpriv_str);
+        assert_eq!(xpub.to_base58check::<TestnetEncoder>().unwrap(), xpub_str);
+        assert_eq!(
+            XPriv::from_base58check::<TestnetEncoder>(xpriv_str).unwrap(),
+            xpriv
+        );
+        assert_eq!(
+            XPub::from_base58check::<TestnetEncoder>(xpub_str).unwrap(),
+            xpub
+        );
+
+        let mainnet_xpriv = "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs";
+        let mainnet_xpub = "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ";
+        assert_eq!(
+            xpriv.to_base58check::<MainnetEncoder>().unwrap(),
+            mainnet_xpriv
+        );
+        assert_eq!(
+            xpub.to_base58check::<MainnetEncoder>().unwrap(),
+            mainnet_xpub
+        );
+        assert_eq!(
+            XPub::from_base58check::<MainnetEncoder>(mainnet_xpub).unwrap(),
+            xpub
+        );
+        assert!(XPub::from_base58check::<MainnetEncoder>(xpub_str).is_err());
+    }
+
+    #[test]
+    fn it_imports_hex_seeds() {
+        // BIP32 test vectors 1, 2, and 4
+        let cases = [
+            ("000102030405060708090a0b0c0d0e0f", "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"),
+            ("fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542", "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U"),
+            ("3ddd5602285899a946114506157c7997e5444528f3003f6134712147db19b678", "xprv9s21ZrQH143K48vGoLGRPxgo2JNkJ3J3fqkirQC2zVdk5Dgd5w14S7fRDyHH4dWNHUgkvsvNDCkvAwcSHNAQwhwgNMgZhLtQC63zxwhQmRv"),
+        ];
+        for (seed, xpriv) in cases.iter() {
+            assert_eq!(
+                XPriv::from_hex_seed(seed, None).unwrap(),
+                MainnetEncoder::xpriv_from_base58(xpriv).unwrap()
+            );
+        }
+
+        match XPriv::from_hex_seed("000102030405060708090a0b0c0d0e", None) {
+            Err(Bip32Error::SeedTooShort) => {}
+            e => panic!("expected seed too short, got {:?}", e),
+        }
+        match XPriv::from_hex_seed(&"00".repeat(65), None) {
+            Err(Bip32Error::SeedTooLong) => {}
+            e => panic!("expected seed too long, got {:?}", e),
+        }
+        match XPriv::from_hex_seed("not hex", None) {
+            Err(Bip32Error::HexError(_)) => {}
+            e => panic!("expected hex error, got {:?}", e),
+        }
+    }
+
     #[test]
     fn it_can_read_keys() {
         let xpriv_str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".to_owned();
@@ -11,6 +11,16 @@ use thiserror::Error;
 const PBKDF2_ROUNDS: u32 = 2048;
 const PBKDF2_BYTES: usize = 64;
 
+/// A mnemonic phrase. With the `zeroize` feature enabled, this is a `Zeroizing<String>`, which
+/// wipes the phrase from memory when dropped.
+#[cfg(feature = "zeroize")]
+pub type Phrase = zeroize::Zeroizing<String>;
+
+/// A mnemonic phrase. With the `zeroize` feature enabled, this is a `Zeroizing<String>`, which
+/// wipes the phrase from memory when dropped.
+#[cfg(not(feature = "zeroize"))]
+pub type Phrase = String;
+
 /// Mnemonic represents entropy that can be represented as a phrase. A mnemonic can be used to
 /// deterministically generate an extended private key or derive its child keys.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -44,6 +54,13 @@ pub enum MnemonicError {
     Bip32Error(#[from] Bip32Error),
 }
 
+#[cfg(feature = "zeroize")]
+impl<W: Wordlist> Drop for Mnemonic<W> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.entropy);
+    }
+}
+
 impl<W: Wordlist> Mnemonic<W> {
     /// Returns a new mnemonic generated using the provided random number generator.
     pub fn new<R: Rng>(rng: &mut R) -> Self {
@@ -102,14 +119,16 @@ impl<W: Wordlist> Mnemonic<W> {
         };
 
         // Ensures the checksum word matches the checksum word in the given phrase.
-        match phrase == mnemonic.to_phrase() {
+        match phrase == mnemonic.to_phrase().as_str() {
             true => Ok(mnemonic),
             false => Err(MnemonicError::InvalidPhrase(phrase.into())),
         }
     }
 
     /// Converts the mnemonic into phrase.
-    pub fn to_phrase(&self) -> String {
+    // The final conversion is a no-op without the `zeroize` feature
+    #[allow(clippy::useless_conversion)]
+    pub fn to_phrase(&self) -> Phrase {
         let length = self.word_count().expect("always valid in memory");
 
         // Compute checksum. Checksum is the most significant (ENTROPY_BYTES/4) bits. That is also
@@ -140,7 +159,7 @@ impl<W: Wordlist> Mnemonic<W> {
             })
             .collect::<Vec<&str>>();
 
-        phrase.join(" ")
+        phrase.join(" ").into()
     }
 
     fn word_count(&self) -> Result<usize, MnemonicError> {
@@ -187,6 +206,22 @@ impl<W: Wordlist> Mnemonic<W> {
     }
 }
 
+/// Compute the 64-byte BIP39 seed of a mnemonic phrase and passphrase, using
+/// PBKDF2-HMAC-SHA512. The phrase must use words from `W`, and is rejected if its checksum is
+/// invalid. Use an empty passphrase if the backup has none.
+pub fn seed_from_mnemonic<W: Wordlist>(
+    phrase: &str,
+    passphrase: &str,
+) -> Result<[u8; PBKDF2_BYTES], MnemonicError> {
+    let seed = Mnemonic::<W>::new_from_phrase(phrase)?.to_seed(Some(passphrase))?;
+    Ok(seed.try_into().expect("seed is PBKDF2_BYTES long"))
+}
+
+/// Generate the BIP32 root key of a seed, e.g. one produced by `seed_from_mnemonic`.
+pub fn root_from_seed(seed: &[u8]) -> Result<XPriv, Bip32Error> {
+    XPriv::root_from_seed(seed, None)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::English;
@@ -385,7 +420,7 @@ mod tests {
             let expected_entropy: Vec<u8> = hex::decode(entropy_str).unwrap();
             let mnemonic = Mnemonic::<W>::new_from_phrase(phrase).unwrap();
             assert_eq!(mnemonic.entropy, expected_entropy);
-            assert_eq!(mnemonic.to_phrase(), phrase.to_string());
+            assert_eq!(mnemonic.to_phrase().as_str(), *phrase);
         })
     }
 
@@ -400,10 +435,20 @@ mod tests {
                     _wordlist: PhantomData,
                 };
                 assert_eq!(mnemonic.entropy, entropy);
-                assert_eq!(mnemonic.to_phrase(), expected_phrase.to_string())
+                assert_eq!(mnemonic.to_phrase().as_str(), *expected_phrase)
             })
     }
 
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn it_returns_zeroizing_phrases() {
+        let (_, expected_phrase, _, _) = TESTCASES[0];
+        let mnemonic = Mnemonic::<W>::new_from_phrase(expected_phrase).unwrap();
+        let phrase: zeroize::Zeroizing<String> = mnemonic.to_phrase();
+        assert_eq!(phrase.as_str(), expected_phrase);
+        assert_eq!(Mnemonic::<W>::new_from_phrase(&phrase).unwrap(), mnemonic);
+    }
+
     #[test]
     fn test_to_seed() {
         TESTCASES
@@ -434,4 +479,33 @@ mod tests {
                 );
             });
     }
+
+    #[test]
+    fn it_derives_root_keys_from_phrases() {
+        TESTCASES
+            .iter()
+            .for_each(|(_, phrase, expected_seed, expected_master_key)| {
+                let seed = seed_from_mnemonic::<W>(phrase, "TREZOR").unwrap();
+                assert_eq!(expected_seed, &hex::encode(seed));
+                assert_eq!(
+                    MainnetEncoder::xpriv_from_base58(expected_master_key).unwrap(),
+                    root_from_seed(&seed).unwrap(),
+                );
+            });
+
+        // the empty passphrase is the default
+        let (_, phrase, _, _) = TESTCASES[0];
+        let mnemonic = Mnemonic::<W>::new_from_phrase(phrase).unwrap();
+        assert_eq!(
+            root_from_seed(&seed_from_mnemonic::<W>(phrase, "").unwrap()).unwrap(),
+            mnemonic.master_key(None).unwrap(),
+        );
+
+        // bad checksum word
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+        match seed_from_mnemonic::<W>(phrase, "") {
+            Err(MnemonicError::InvalidPhrase(_)) => {}
+            e => panic!("expected invalid phrase, got {:?}", e),
+        }
+    }
 }
@@ -19,6 +19,9 @@ pub trait TxBuilder: std::marker::Sized {
     /// the `pay` function to decode addresses into associated `RecipientIdentifier`s.
     type Encoder: AddressEncoder;
 
+    /// The error returned by `build()`. Transaction errors convert into it.
+    type Error: From<<Self::Transaction as Transaction>::TxError>;
+
     /// Instantiate a new builder
     fn new() -> Self;
 
@@ -38,6 +41,7 @@ pub trait TxBuilder: std::marker::Sized {
     }
 
     /// Instantiate a new builder from transaction hex
+    #[cfg(feature = "hex")]
     fn from_hex_tx(hex_str: &str) -> Result<Self, <Self::Transaction as Transaction>::TxError> {
         let tx = Self::Transaction::deserialize_hex(hex_str)?;
         Ok(Self::from_tx(tx))
@@ -92,5 +96,5 @@ pub trait TxBuilder: std::marker::Sized {
     fn locktime(self, locktime: u32) -> Self;
 
     /// Consume the builder and produce a transaction from the builder's current state.
-    fn build(self) -> Result<Self::Transaction, <Self::Transaction as Transaction>::TxError>;
+    fn build(self) -> Result<Self::Transaction, Self::Error>;
 }
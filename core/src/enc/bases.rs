@@ -53,6 +53,36 @@ pub enum EncodingError {
     /// Invalid Address Size
     #[error("Invalid Address Size")]
     InvalidSizeError,
+
+    /// Bech32 checksum variant does not match the witness version, as required by BIP350
+    #[error("Witness v{version} programs may not use a {got:?} checksum")]
+    WrongBech32Variant {
+        /// The witness version of the program.
+        version: u8,
+        /// The checksum variant found.
+        got: Bech32Variant,
+    },
+}
+
+/// The checksum variant of a bech32 string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bech32Variant {
+    /// The original BIP173 checksum
+    Bech32,
+    /// The BIP350 checksum
+    Bech32m,
+}
+
+impl Bech32Variant {
+    /// The variant BIP350 requires for a witness version. v0 programs use bech32, while v1 and
+    /// later programs use bech32m.
+    pub fn for_witness_version(version: u8) -> Self {
+        if version == 0 {
+            Bech32Variant::Bech32
+        } else {
+            Bech32Variant::Bech32m
+        }
+    }
 }
 
 /// Impl explicitly because FromBase58CheckError doesn't implement the std error format
@@ -91,6 +121,116 @@ pub fn decode_bech32(expected_hrp: &str, s: &str) -> EncodingResult<(u8, Vec<u8>
     Ok((v[0].to_u8(), payload))
 }
 
+/// The BIP350 bech32m checksum constant.
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// The bech32 character set, indexed by 5-bit value.
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The BIP173 checksum function.
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATORS: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut chk = 1u32;
+    for value in values.iter() {
+        let top = chk >> 25;
+        chk = ((chk & 0x01ff_ffff) << 5) ^ *value as u32;
+        for (i, generator) in GENERATORS.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+/// Expand the HRP for use in the checksum, followed by the 5-bit data values.
+fn bech32_checksum_input(hrp: &str, data: &[u8]) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 0x1f));
+    values.extend(data);
+    values
+}
+
+/// Encode a witness version and program to bech32m, as specified in BIP350. Witness v1+
+/// programs use bech32m. v0 programs must use `encode_bech32`.
+pub fn encode_bech32m(hrp: &str, v: u8, h: &[u8]) -> EncodingResult<String> {
+    if hrp.is_empty()
+        || hrp
+            .bytes()
+            .any(|b| !(33..=126).contains(&b) || b.is_ascii_uppercase())
+    {
+        return Err(BechError::InvalidChar(hrp.chars().next().unwrap_or(' ')).into());
+    }
+    let mut data = vec![u5::try_from_u8(v)?.to_u8()];
+    data.extend(h.to_base32().iter().map(|c| c.to_u8()));
+
+    let mut values = bech32_checksum_input(hrp, &data);
+    values.extend([0u8; 6]);
+    let checksum = bech32_polymod(&values) ^ BECH32M_CONST;
+    data.extend((0..6).map(|i| ((checksum >> (5 * (5 - i))) & 0x1f) as u8));
+
+    let mut encoded = hrp.to_owned();
+    encoded.push('1');
+    encoded.extend(data.iter().map(|c| BECH32_CHARSET[*c as usize] as char));
+    if encoded.len() > 90 {
+        return Err(BechError::InvalidLength.into());
+    }
+    Ok(encoded)
+}
+
+/// Decode a witness version and program from a bech32m string, as specified in BIP350. Caller
+/// specifies an expected HRP. If a different HRP is found, returns `WrongHrp`. Strings with a
+/// bech32 checksum return `InvalidChecksum`.
+pub fn decode_bech32m(expected_hrp: &str, s: &str) -> EncodingResult<(u8, Vec<u8>)> {
+    if s.len() > 90 {
+        return Err(BechError::InvalidLength.into());
+    }
+    if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(BechError::MixedCase.into());
+    }
+    let s = s.to_ascii_lowercase();
+    let (hrp, data) = s.rsplit_once('1').ok_or(BechError::MissingSeparator)?;
+    if hrp.is_empty() || data.len() < 7 {
+        return Err(BechError::InvalidLength.into());
+    }
+    let data = data
+        .chars()
+        .map(|c| {
+            BECH32_CHARSET
+                .iter()
+                .position(|b| *b as char == c)
+                .map(|p| p as u8)
+                .ok_or(BechError::InvalidChar(c))
+        })
+        .collect::<Result<Vec<u8>, BechError>>()?;
+    if bech32_polymod(&bech32_checksum_input(hrp, &data)) != BECH32M_CONST {
+        return Err(BechError::InvalidChecksum.into());
+    }
+    if hrp != expected_hrp {
+        return Err(EncodingError::WrongHrp {
+            got: hrp.to_owned(),
+            expected: expected_hrp.to_owned(),
+        });
+    }
+
+    // Extract the witness version and payload, dropping the checksum
+    let data = data[..data.len() - 6]
+        .iter()
+        .map(|c| u5::try_from_u8(*c))
+        .collect::<Result<Vec<u5>, BechError>>()?;
+    let (v, p) = data.split_at(1);
+    let payload = Vec::from_base32(p)?;
+
+    Ok((v[0].to_u8(), payload))
+}
+
 /// Encodes a byte slice to base58check with the specified version byte.
 pub fn encode_base58(version: u8, v: &[u8]) -> String {
     encode(v).with_check_version(version).into_string()
@@ -169,6 +309,70 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_should_encode_and_decode_bech32m() {
+        let cases = [
+            (
+                "bc",
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                1,
+                "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+            (
+                "tb",
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                1,
+                "000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+            (
+                "bc",
+                "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+                1,
+                "751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+        ];
+        for case in cases.iter() {
+            let (version, data) = decode_bech32m(case.0, case.1).unwrap();
+            assert_eq!(version, case.2);
+            assert_eq!(hex::encode(&data), case.3);
+            assert_eq!(encode_bech32m(case.0, version, &data).unwrap(), case.1);
+            assert_eq!(
+                decode_bech32m(case.0, &case.1.to_uppercase()).unwrap(),
+                (version, data)
+            );
+        }
+
+        // bech32 checksums are not valid bech32m, and vice versa
+        match decode_bech32m(
+            "bc",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+        ) {
+            Err(EncodingError::BechError(BechError::InvalidChecksum)) => {}
+            e => panic!("expected invalid checksum, got {:?}", e),
+        }
+        match decode_bech32(
+            "bc",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ) {
+            Err(EncodingError::BechError(BechError::InvalidChecksum)) => {}
+            e => panic!("expected invalid checksum, got {:?}", e),
+        }
+        match decode_bech32m(
+            "tb",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ) {
+            Err(EncodingError::WrongHrp { .. }) => {}
+            e => panic!("expected wrong hrp, got {:?}", e),
+        }
+        match decode_bech32m(
+            "bc",
+            "bc1P0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ) {
+            Err(EncodingError::BechError(BechError::MixedCase)) => {}
+            e => panic!("expected mixed case, got {:?}", e),
+        }
+    }
+
     #[test]
     fn it_should_error_on_wrong_version_and_hrp_and_invalid_addrs() {
         match decode_bech32("tb", "bc1q233q49ve8ysdsztqh9ue57m6227627j8ztscl9") {
@@ -8,19 +8,20 @@ use digest::{
     core_api::{BlockSizeUser, OutputSizeUser},
     HashMarker, Output, VariableOutput,
 };
-use std::io::Write;
+use std::io::{Read, Write};
 
 use crate::ser::{ByteFormat, SerError, SerResult};
 
 // Useful re-exports
 pub use digest::Digest;
+#[allow(deprecated)]
 pub use generic_array::GenericArray;
 pub use ripemd::Ripemd160;
 pub use sha2::Sha256;
 pub use sha3::Sha3_256;
 
 /// Output of a Digest function
-pub type DigestOutput<D> = GenericArray<u8, <D as OutputSizeUser>::OutputSize>;
+pub type DigestOutput<D> = Output<D>;
 
 /// Convenience interface for hash function outputs, particularly marked digest outputs
 pub trait MarkedDigestOutput:
@@ -40,16 +41,50 @@ pub trait MarkedDigestOutput:
         reversed
     }
 
-    /// Deserialize to BE hex
+    /// Deserialize from BE hex. This is the order Bitcoin Core's RPC and most block explorers
+    /// display TXIDs and block hashes in, so a txid copied from `bitcoin-cli` should be parsed
+    /// with this.
+    #[cfg(feature = "hex")]
     fn from_be_hex(be: &str) -> SerResult<Self> {
         Ok(Self::deserialize_hex(be)?.reversed())
     }
 
-    /// Convert to BE hex
+    /// Convert to BE hex. This is the order Bitcoin Core's RPC displays TXIDs and block hashes
+    /// in.
+    #[cfg(feature = "hex")]
     fn to_be_hex(&self) -> String {
         self.reversed().serialize_hex()
     }
 
+    /// Deserialize from LE hex, without reversing. This is the order digests appear in when
+    /// serialized in transactions and blocks, e.g. in an outpoint. An alias of
+    /// `deserialize_hex`.
+    #[cfg(feature = "hex")]
+    fn from_le_hex(le: &str) -> SerResult<Self> {
+        Self::deserialize_hex(le)
+    }
+
+    /// Convert to LE hex, without reversing. This is the order digests appear in when
+    /// serialized in transactions and blocks. An alias of `serialize_hex`.
+    #[cfg(feature = "hex")]
+    fn to_le_hex(&self) -> String {
+        self.serialize_hex()
+    }
+
+    /// Instantiate from a slice, copying its contents. Errors if the slice length is not the
+    /// digest size.
+    fn from_slice(slice: &[u8]) -> SerResult<Self> {
+        let mut digest = Self::default();
+        if slice.len() != digest.size() {
+            return Err(SerError::WrongLength {
+                expected: digest.size(),
+                got: slice.len(),
+            });
+        }
+        digest.as_mut_slice().copy_from_slice(slice);
+        Ok(digest)
+    }
+
     /// Use as a mutable slice
     fn as_mut_slice(&mut self) -> &mut [u8] {
         self.as_mut()
@@ -59,6 +94,19 @@ pub trait MarkedDigestOutput:
     fn as_slice(&self) -> &[u8] {
         self.as_ref()
     }
+
+    /// Compare to `other` in constant time. Unlike `==`, this does not return early at the first
+    /// differing byte, so it is suitable for comparing authentication tags.
+    #[cfg(feature = "subtle")]
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        subtle::ConstantTimeEq::ct_eq(self.as_slice(), other.as_slice())
+    }
+
+    /// Compare to `other` in constant time, and return the result as a `bool`.
+    #[cfg(feature = "subtle")]
+    fn constant_time_eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
 }
 
 /// A marked digest
@@ -71,6 +119,23 @@ where
 
     /// Shortcut to produce a marked digest
     fn digest_marked(data: &[u8]) -> D;
+
+    /// Produce a marked digest of everything read from `reader`, until it returns EOF. The input
+    /// is hashed in fixed-size chunks, so it is never held in memory all at once. Errors from the
+    /// reader are returned unmodified, and no digest is produced.
+    fn digest_reader<R: Read>(reader: &mut R) -> std::io::Result<D> {
+        let mut hasher = Self::default();
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => Digest::update(&mut hasher, &buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(hasher.finalize_marked())
+    }
 }
 
 #[derive(Clone, Default)]
@@ -99,7 +164,7 @@ impl OutputSizeUser for Hash256 {
 }
 
 impl digest::FixedOutput for Hash256 {
-    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+    fn finalize_into(self, out: &mut Output<Self>) {
         let mut hasher = sha2::Sha256::default();
         hasher.update(self.0.finalize());
         Digest::finalize_into(hasher, out)
@@ -152,7 +217,7 @@ impl OutputSizeUser for Hash160 {
 }
 
 impl digest::FixedOutput for Hash160 {
-    fn finalize_into(self, out: &mut GenericArray<u8, Self::OutputSize>) {
+    fn finalize_into(self, out: &mut Output<Self>) {
         let mut hasher = ripemd::Ripemd160::default();
         hasher.update(self.0.finalize());
         Digest::finalize_into(hasher, out)
@@ -239,6 +304,247 @@ impl digest::Reset for Blake2b256 {
     }
 }
 
+/// The SHA256 initial hash values.
+const SHA256_IV: [u32; 8] = [
+    0x6a09_e667,
+    0xbb67_ae85,
+    0x3c6e_f372,
+    0xa54f_f53a,
+    0x510e_527f,
+    0x9b05_688c,
+    0x1f83_d9ab,
+    0x5be0_cd19,
+];
+
+#[derive(Clone)]
+/// A single-sha256 `Digest` implementation whose midstate can be exported and restored. This
+/// allows the state after a fixed prefix, such as a doubled BIP340 tag, to be computed once and
+/// reused.
+pub struct ResumableSha256 {
+    state: [u32; 8],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+impl Default for ResumableSha256 {
+    fn default() -> Self {
+        Self {
+            state: SHA256_IV,
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+}
+
+impl ResumableSha256 {
+    /// Export the midstate, as the big-endian encoding of the internal state words. Returns
+    /// `None` unless the bytes hashed so far fill a whole number of 64-byte blocks.
+    pub fn export_midstate(&self) -> Option<[u8; 32]> {
+        if !self.buffer.is_empty() {
+            return None;
+        }
+        let mut midstate = [0u8; 32];
+        for (chunk, word) in midstate.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        Some(midstate)
+    }
+
+    /// Restore a hasher from a midstate exported after hashing `bytes_so_far` bytes. Returns
+    /// `None` if `bytes_so_far` is not a multiple of 64.
+    pub fn from_midstate(midstate: &[u8; 32], bytes_so_far: u64) -> Option<Self> {
+        if !bytes_so_far.is_multiple_of(64) {
+            return None;
+        }
+        let mut state = [0u32; 8];
+        for (word, chunk) in state.iter_mut().zip(midstate.chunks_exact(4)) {
+            let mut buf = [0u8; 4];
+            buf.copy_from_slice(chunk);
+            *word = u32::from_be_bytes(buf);
+        }
+        Some(Self {
+            state,
+            buffer: Vec::with_capacity(64),
+            length: bytes_so_far,
+        })
+    }
+
+    /// The number of bytes hashed so far, including those hashed before the midstate was
+    /// exported.
+    pub fn bytes_so_far(&self) -> u64 {
+        self.length
+    }
+
+    fn compress(state: &mut [u32; 8], block: &[u8]) {
+        #[allow(deprecated)]
+        sha2::compress256(state, &[*GenericArray::from_slice(block)]);
+    }
+}
+
+impl std::io::Write for ResumableSha256 {
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        digest::Update::update(self, buf);
+        Ok(buf.len())
+    }
+}
+
+impl HashMarker for ResumableSha256 {}
+
+impl BlockSizeUser for ResumableSha256 {
+    type BlockSize = <Sha256 as BlockSizeUser>::BlockSize;
+}
+
+impl OutputSizeUser for ResumableSha256 {
+    type OutputSize = <Sha256 as OutputSizeUser>::OutputSize;
+}
+
+impl digest::Update for ResumableSha256 {
+    fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        let mut data = data;
+
+        if !self.buffer.is_empty() {
+            let fill = std::cmp::min(64 - self.buffer.len(), data.len());
+            self.buffer.extend_from_slice(&data[..fill]);
+            data = &data[fill..];
+            if self.buffer.len() < 64 {
+                return;
+            }
+            Self::compress(&mut self.state, &self.buffer);
+            self.buffer.clear();
+        }
+
+        let blocks = data.chunks_exact(64);
+        self.buffer.extend_from_slice(blocks.remainder());
+        for block in blocks {
+            Self::compress(&mut self.state, block);
+        }
+    }
+}
+
+impl digest::FixedOutput for ResumableSha256 {
+    fn finalize_into(mut self, out: &mut Output<Self>) {
+        // pad with 0x80, then zeros, then the 64-bit big-endian bit length
+        let mut tail = std::mem::take(&mut self.buffer);
+        tail.push(0x80);
+        while tail.len() % 64 != 56 {
+            tail.push(0);
+        }
+        tail.extend_from_slice(&(self.length * 8).to_be_bytes());
+        for block in tail.chunks_exact(64) {
+            Self::compress(&mut self.state, block);
+        }
+
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+    }
+}
+
+impl digest::FixedOutputReset for ResumableSha256 {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        let other = std::mem::take(self);
+        digest::FixedOutput::finalize_into(other, out);
+    }
+}
+
+impl digest::Reset for ResumableSha256 {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[derive(Clone)]
+/// A BIP340 tagged hash, `SHA256(SHA256(tag) || SHA256(tag) || msg)`. The midstate after the
+/// doubled tag is computed once in `new`, and reused by clones of the hasher and by `reset`.
+///
+/// Because the tag is chosen at runtime, this does not implement `Default`, and therefore not
+/// `Digest`. Use it via `std::io::Write` or `digest::Update`, and `digest::FixedOutput`.
+pub struct TaggedHash {
+    midstate: [u8; 32],
+    hasher: ResumableSha256,
+}
+
+impl TaggedHash {
+    /// Instantiate a hasher for `tag`, precomputing the tag midstate.
+    pub fn new(tag: &str) -> Self {
+        let tag = Sha256::digest(tag.as_bytes());
+        let mut hasher = ResumableSha256::default();
+        digest::Update::update(&mut hasher, &tag);
+        digest::Update::update(&mut hasher, &tag);
+        let midstate = hasher
+            .export_midstate()
+            .expect("the doubled tag is one block");
+        Self { midstate, hasher }
+    }
+
+    /// The midstate after hashing the doubled tag.
+    pub fn midstate(&self) -> [u8; 32] {
+        self.midstate
+    }
+
+    /// Consume the hasher, and return the 32-byte digest.
+    pub fn finish(self) -> [u8; 32] {
+        digest::FixedOutput::finalize_fixed(self).into()
+    }
+}
+
+/// Compute the BIP340 tagged hash of `data` with `tag`. When hashing many messages with the
+/// same tag, prefer cloning a single `TaggedHash`, which computes the tag midstate once.
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let mut hasher = TaggedHash::new(tag);
+    digest::Update::update(&mut hasher, data);
+    hasher.finish()
+}
+
+impl std::io::Write for TaggedHash {
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        digest::Update::update(self, buf);
+        Ok(buf.len())
+    }
+}
+
+impl HashMarker for TaggedHash {}
+
+impl OutputSizeUser for TaggedHash {
+    type OutputSize = <Sha256 as OutputSizeUser>::OutputSize;
+}
+
+impl digest::Update for TaggedHash {
+    fn update(&mut self, data: &[u8]) {
+        digest::Update::update(&mut self.hasher, data);
+    }
+}
+
+impl digest::FixedOutput for TaggedHash {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        digest::FixedOutput::finalize_into(self.hasher, out)
+    }
+}
+
+impl digest::FixedOutputReset for TaggedHash {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        let other = self.clone();
+        digest::FixedOutput::finalize_into(other, out);
+        digest::Reset::reset(self);
+    }
+}
+
+impl digest::Reset for TaggedHash {
+    fn reset(&mut self) {
+        self.hasher =
+            ResumableSha256::from_midstate(&self.midstate, 64).expect("64 is a block boundary");
+    }
+}
+
 marked_digest!(
     /// A bitcoin-style Hash160
     Hash160Digest,
@@ -250,3 +556,283 @@ marked_digest!(
     Hash256Digest,
     Hash256
 );
+
+marked_digest!(
+    /// A single SHA256. Distinct from `Hash256Digest`, so that a single hash can't be passed
+    /// where a double hash is expected
+    Sha256Digest,
+    sha2::Sha256
+);
+
+#[cfg(feature = "digest-serde")]
+impl_digest_serde!(Hash160Digest);
+#[cfg(feature = "digest-serde")]
+impl_digest_serde!(Hash256Digest);
+#[cfg(feature = "digest-serde")]
+impl_digest_serde!(Sha256Digest);
+
+#[cfg(feature = "digest-serde")]
+#[doc(hidden)]
+/// Serde implementations shared by `impl_digest_serde`.
+pub mod digest_serde {
+    use super::MarkedDigestOutput;
+    use serde::de::{Error, SeqAccess, Visitor};
+    use std::{fmt, marker::PhantomData};
+
+    /// Serialize as big-endian hex in human-readable formats, and as bytes otherwise.
+    pub fn serialize<T, S>(digest: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: MarkedDigestOutput,
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&digest.to_be_hex())
+        } else {
+            serializer.serialize_bytes(digest.as_slice())
+        }
+    }
+
+    /// Deserialize from big-endian hex in human-readable formats, and from bytes otherwise.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: MarkedDigestOutput,
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(DigestVisitor(PhantomData))
+        } else {
+            deserializer.deserialize_bytes(DigestVisitor(PhantomData))
+        }
+    }
+
+    struct DigestVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: MarkedDigestOutput> Visitor<'de> for DigestVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a big-endian hex digest, or the digest bytes")
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<T, E> {
+            T::from_be_hex(v).map_err(E::custom)
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<T, E> {
+            T::from_slice(v).map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+            let mut bytes = vec![];
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            T::from_slice(&bytes).map_err(A::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_builds_digests_from_slices() {
+        let bytes = (0u8..32).collect::<Vec<_>>();
+        let digest = Hash256Digest::from_slice(&bytes).unwrap();
+        assert_eq!(digest.as_slice(), &bytes[..]);
+
+        match Hash256Digest::from_slice(&bytes[..20]) {
+            Err(SerError::WrongLength {
+                expected: 32,
+                got: 20,
+            }) => {}
+            e => panic!("expected wrong length error, got {:?}", e),
+        }
+        assert!(Hash160Digest::from_slice(&bytes[..20]).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "hex")]
+    fn it_converts_digests_to_and_from_le_hex() {
+        // The genesis coinbase txid, as displayed by `bitcoin-cli`
+        let be = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+        let le = "3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a";
+
+        let digest = Hash256Digest::from_be_hex(be).unwrap();
+        assert_eq!(Hash256Digest::from_le_hex(le).unwrap(), digest);
+        assert_eq!(digest.to_le_hex(), le);
+        assert_eq!(digest.to_le_hex(), digest.serialize_hex());
+        assert_eq!(digest.to_be_hex(), be);
+    }
+
+    #[test]
+    fn it_marks_single_sha256_digests() {
+        let single: Sha256Digest = Sha256::digest_marked(b"abc");
+        let double: Hash256Digest = Hash256::digest_marked(b"abc");
+        assert_eq!(single.to_internal(), Sha256::digest(b"abc"));
+        assert_eq!(double.to_internal(), Sha256::digest(single.as_slice()));
+    }
+
+    #[test]
+    fn it_resumes_sha256_from_midstates() {
+        let data = (0..=255u8).cycle().take(300).collect::<Vec<_>>();
+        let expected = Sha256::digest(&data);
+
+        let mut straight = ResumableSha256::default();
+        straight.update(&data[..7]);
+        straight.update(&data[7..]);
+        assert_eq!(straight.finalize(), expected);
+
+        let mut prefix = ResumableSha256::default();
+        prefix.update(&data[..128]);
+        let midstate = prefix.export_midstate().unwrap();
+        let mut resumed = ResumableSha256::from_midstate(&midstate, 128).unwrap();
+        resumed.update(&data[128..]);
+        assert_eq!(resumed.bytes_so_far(), 300);
+        assert_eq!(resumed.finalize(), expected);
+
+        // midstates only exist at block boundaries
+        prefix.update(&data[128..129]);
+        assert!(prefix.export_midstate().is_none());
+        assert!(ResumableSha256::from_midstate(&midstate, 100).is_none());
+
+        // The BIP340 "TapLeaf" tag midstate, exported as big-endian state words. Resuming from
+        // it computes the tagged hash
+        let tag = Sha256::digest(b"TapLeaf");
+        let mut tagged = ResumableSha256::default();
+        tagged.update(tag);
+        tagged.update(tag);
+        let midstate = tagged.export_midstate().unwrap();
+        assert_eq!(
+            hex::encode(midstate),
+            "9ce0e4e67c116c3938b3caf2c30f5089d3f3936c47636e607db33eeaddc6f0c9"
+        );
+        let mut resumed = ResumableSha256::from_midstate(&midstate, 64).unwrap();
+        resumed.update(b"leaf");
+        assert_eq!(resumed.finalize()[..], tagged_hash("TapLeaf", b"leaf")[..]);
+        assert_eq!(ResumableSha256::default().finalize(), Sha256::digest(b""));
+    }
+
+    #[test]
+    fn it_computes_tagged_hashes() {
+        // The TapLeaf hash is from the BIP341 wallet vectors
+        let script = "20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac";
+        let mut leaf = vec![0xc0, 0x22]; // leaf version, script length
+        leaf.extend(hex::decode(script).unwrap());
+        let leaf_hash = tagged_hash("TapLeaf", &leaf);
+        assert_eq!(
+            hex::encode(leaf_hash),
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"
+        );
+
+        let other_leaf =
+            hex::decode("6b13becdaf0eee497e2f304adcfa1c0c9e84561c9989b7f2b5fc39f5f90a60f6")
+                .unwrap();
+        let mut hasher = TaggedHash::new("TapBranch");
+        hasher.write_all(&leaf_hash).unwrap();
+        hasher.write_all(&other_leaf).unwrap();
+
+        // BIP340 defines the tagged hash as sha256(sha256(tag) || sha256(tag) || data)
+        let tag = Sha256::digest(b"TapBranch");
+        let mut preimage = tag.to_vec();
+        preimage.extend(tag);
+        preimage.extend(leaf_hash);
+        preimage.extend(other_leaf);
+        assert_eq!(hasher.finish()[..], Sha256::digest(&preimage)[..]);
+
+        // R and P from BIP340 test vector 0, with a zero message
+        let mut challenge = hex::decode(
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+             f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        )
+        .unwrap();
+        challenge.extend([0u8; 32]);
+        assert_eq!(
+            hex::encode(tagged_hash("BIP0340/challenge", &challenge)),
+            "6bb6b93a91f2ecc0cd924f4f9baabb5e6eb21745bb00f2cebdaac908bb5d86ce"
+        );
+
+        // The midstate is reused across clones and resets
+        let tap_leaf = TaggedHash::new("TapLeaf");
+        assert_eq!(
+            hex::encode(tap_leaf.midstate()),
+            "9ce0e4e67c116c3938b3caf2c30f5089d3f3936c47636e607db33eeaddc6f0c9"
+        );
+        let mut reused = tap_leaf.clone();
+        digest::Update::update(&mut reused, b"garbage");
+        digest::Reset::reset(&mut reused);
+        digest::Update::update(&mut reused, &leaf);
+        assert_eq!(reused.finish(), leaf_hash);
+        assert_eq!(
+            hex::encode(tap_leaf.finish()),
+            "5212c288a377d1f8164962a5a13429f9ba6a7b84e59776a52c6637df2106facb"
+        );
+    }
+
+    #[cfg(feature = "digest-serde")]
+    #[test]
+    fn it_serdes_digests() {
+        let txid = Hash256Digest::from_be_hex(
+            "3c7fb4af9f4cdf19ebbd2fc7b4cf5d5a4d3853b14ba2b1f2d0e2ab2bb557b324",
+        )
+        .unwrap();
+        let json = serde_json::to_string(&txid).unwrap();
+        assert_eq!(
+            json,
+            "\"3c7fb4af9f4cdf19ebbd2fc7b4cf5d5a4d3853b14ba2b1f2d0e2ab2bb557b324\""
+        );
+        assert_eq!(serde_json::from_str::<Hash256Digest>(&json).unwrap(), txid);
+
+        let bytes = bincode::serialize(&txid).unwrap();
+        assert_eq!(&bytes[8..], txid.as_slice());
+        assert_eq!(bincode::deserialize::<Hash256Digest>(&bytes).unwrap(), txid);
+
+        let digest = Hash160Digest::from_slice(&[7u8; 20]).unwrap();
+        let json = serde_json::to_string(&digest).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Hash160Digest>(&json).unwrap(),
+            digest
+        );
+        assert!(serde_json::from_str::<Hash256Digest>(&json).is_err());
+    }
+
+    #[test]
+    fn it_digests_readers() {
+        let data = (0..=255u8).cycle().take(10_000).collect::<Vec<_>>();
+        let mut reader = std::io::Cursor::new(&data);
+        assert_eq!(
+            Hash256::digest_reader(&mut reader).unwrap(),
+            Hash256::digest_marked(&data)
+        );
+        assert_eq!(
+            Hash160::digest_reader(&mut &data[..]).unwrap(),
+            Hash160::digest_marked(&data)
+        );
+        assert_eq!(
+            Hash256::digest_reader(&mut std::io::empty()).unwrap(),
+            Hash256::digest_marked(&[])
+        );
+
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken pipe"))
+            }
+        }
+        let err = Hash256::digest_reader(&mut Failing).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    }
+
+    #[cfg(feature = "subtle")]
+    #[test]
+    fn it_compares_digests_in_constant_time() {
+        let a = Hash256::digest_marked(b"a");
+        let b = Hash256::digest_marked(b"b");
+        assert!(bool::from(a.ct_eq(&a)));
+        assert!(!bool::from(a.ct_eq(&b)));
+        assert!(a.constant_time_eq(&a));
+        assert!(!a.constant_time_eq(&b));
+        assert!(!a.constant_time_eq(&a.reversed()));
+    }
+}
@@ -1,5 +1,6 @@
 //! Useful macros for implementing new chains
 
+#[cfg(feature = "hex")]
 #[macro_export]
 /// Implement `serde::Serialize` and `serde::Deserialize` by passing through to the hex
 macro_rules! impl_hex_serde {
@@ -27,6 +28,40 @@ macro_rules! impl_hex_serde {
     };
 }
 
+#[cfg(not(feature = "hex"))]
+#[macro_export]
+/// Hex serde is unavailable without the `hex` feature. Types wrapped without it do not implement
+/// `serde::Serialize` or `serde::Deserialize`.
+macro_rules! impl_hex_serde {
+    ($item:ty) => {};
+}
+
+#[cfg(feature = "digest-serde")]
+#[macro_export]
+/// Implement `serde::Serialize` and `serde::Deserialize` for a marked digest output. Human-readable
+/// formats use big-endian hex, matching `to_be_hex`. Binary formats use the raw digest bytes.
+macro_rules! impl_digest_serde {
+    ($item:ty) => {
+        impl serde::Serialize for $item {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                $crate::hashes::digest_serde::serialize(self, serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $item {
+            fn deserialize<D>(deserializer: D) -> Result<$item, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                $crate::hashes::digest_serde::deserialize(deserializer)
+            }
+        }
+    };
+}
+
 #[macro_export]
 /// Wrap a prefixed vector of bytes (`u8`) in a newtype, and implement convenience functions for
 /// it.
@@ -82,11 +117,18 @@ macro_rules! wrap_prefixed_byte_vector {
                 Self(vec![])
             }
 
-            /// Return a reference to the underlying bytes
+            /// Return a reference to the underlying bytes. The slice borrows from `self`, so
+            /// `self` can't be modified or moved while it is held. Use `to_bytes` for an owned
+            /// copy.
             pub fn items(&self) -> &[u8] {
                 &self.0
             }
 
+            /// Return an owned copy of the underlying bytes.
+            pub fn to_bytes(&self) -> Vec<u8> {
+                self.0.clone()
+            }
+
             /// Set the underlying items vector.
             pub fn set_items(&mut self, v: Vec<u8>) {
                 self.0 = v
@@ -70,6 +70,7 @@ pub trait Network {
     }
 
     /// Instantiate a builder from a hex-serialized transaction
+    #[cfg(feature = "hex")]
     fn builder_from_hex(hex_tx: &str) -> Result<Self::Builder, <Self::Tx as Transaction>::TxError> {
         Self::Builder::from_hex_tx(hex_tx)
     }
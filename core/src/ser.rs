@@ -1,6 +1,7 @@
 //! A simple trait for binary (de)Serialization using std `Read` and `Write` traits.
 
 use base64::{prelude::*, DecodeError};
+#[cfg(feature = "hex")]
 use hex::FromHexError;
 use std::{
     convert::TryInto,
@@ -21,6 +22,7 @@ pub enum SerError {
     IoError(#[from] IOError),
 
     /// `deserialize_hex` encountered an error on its input.
+    #[cfg(feature = "hex")]
     #[error(transparent)]
     FromHexError(#[from] FromHexError),
 
@@ -40,6 +42,24 @@ pub enum SerError {
         /// The number of items succesfully deserialized
         got: usize,
     },
+
+    /// Thrown when a fixed-length type is built from a slice of the wrong length.
+    #[error("Expected {expected} bytes. Got {got} bytes")]
+    WrongLength {
+        /// The expected length
+        expected: usize,
+        /// The length of the slice
+        got: usize,
+    },
+
+    /// Thrown when a witness transaction's witness section ends before providing one witness
+    /// stack per input, or when bytes are left over after the transaction, which can only be
+    /// extra witness stacks.
+    #[error("Expected one witness stack for each of {inputs} inputs")]
+    WitnessCountMismatch {
+        /// The number of inputs in the transaction
+        inputs: usize,
+    },
 }
 
 /// Operation mode for `read_seq_from`.
@@ -173,8 +193,8 @@ where
     E: From<SerError> + From<IOError> + std::error::Error,
     I: ByteFormat<Error = E>,
 {
-    let items = read_compact_int(reader)?;
-    I::read_seq_from(reader, ReadSeqMode::Exactly(items.try_into().unwrap())).map_err(Into::into)
+    let items = CompactSize::read_from(reader)?;
+    I::read_seq_from(reader, ReadSeqMode::Exactly(items.0.try_into().unwrap()))
 }
 
 /// Convenience function to write a Bitcoin-style length-prefixed vector.
@@ -184,7 +204,7 @@ where
     E: From<SerError> + From<IOError> + std::error::Error,
     I: ByteFormat<Error = E>,
 {
-    let mut written = write_compact_int(writer, vector.len() as u64)?;
+    let mut written = CompactSize(vector.len() as u64).write_to(writer)?;
     written += I::write_seq_to(writer, vector.iter())?;
     Ok(written)
 }
@@ -317,14 +337,38 @@ pub trait ByteFormat {
         Ok(written)
     }
 
+    /// Compare `self` and `other` by their serializations. This is useful for types without a
+    /// `PartialEq` implementation, or for which structural comparison is expensive.
+    fn serialized_eq(&self, other: &Self) -> bool {
+        if self.serialized_length() != other.serialized_length() {
+            return false;
+        }
+        let mut left: Vec<u8> = vec![];
+        let mut right: Vec<u8> = vec![];
+        self.write_to(&mut left).expect("No error on heap write");
+        other.write_to(&mut right).expect("No error on heap write");
+        left == right
+    }
+
+    /// Deserializes an instance of `Self` from a byte slice. `deserialize_hex` and
+    /// `deserialize_base64` decode to bytes, then call this. Types whose encoding can't be
+    /// validated from a stream may override it to check the whole slice.
+    fn deserialize_slice(bytes: &[u8]) -> Result<Self, Self::Error>
+    where
+        Self: std::marker::Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+        Self::read_from(&mut cursor)
+    }
+
     /// Decodes a hex string to a `Vec<u8>`, deserializes an instance of `Self` from that vector.
+    #[cfg(feature = "hex")]
     fn deserialize_hex(s: &str) -> Result<Self, Self::Error>
     where
         Self: std::marker::Sized,
     {
-        let v: Vec<u8> = BASE64_STANDARD.decode(s).map_err(SerError::from)?;
-        let mut cursor = Cursor::new(v);
-        Self::read_from(&mut cursor)
+        let v: Vec<u8> = hex::decode(s).map_err(SerError::from)?;
+        Self::deserialize_slice(&v)
     }
 
     /// Serialize `self` to a base64 string, using standard RFC4648 non-url safe characters
@@ -333,11 +377,11 @@ pub trait ByteFormat {
         Self: std::marker::Sized,
     {
         let v: Vec<u8> = BASE64_STANDARD.decode(s).map_err(SerError::from)?;
-        let mut cursor = Cursor::new(v);
-        Self::read_from(&mut cursor)
+        Self::deserialize_slice(&v)
     }
 
     /// Serializes `self` to a vector, returns the hex-encoded vector
+    #[cfg(feature = "hex")]
     fn serialize_hex(&self) -> String {
         let mut v: Vec<u8> = vec![];
         self.write_to(&mut v).expect("No error on heap write");
@@ -376,7 +420,11 @@ impl ByteFormat for u8 {
                 v.truncate(n);
                 Ok(v)
             }
-            ReadSeqMode::UntilEnd => Ok(reader.bytes().collect::<Result<Vec<u8>, _>>()?),
+            ReadSeqMode::UntilEnd => {
+                let mut v = vec![];
+                reader.read_to_end(&mut v)?;
+                Ok(v)
+            }
         }
     }
 
@@ -398,10 +446,114 @@ impl ByteFormat for u8 {
     }
 }
 
+/// A Bitcoin-style compact-size integer, as used for element counts and script lengths.
+/// Serialization always uses the canonical (minimal) encoding, and deserialization rejects
+/// non-minimal encodings.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactSize(pub u64);
+
+impl From<u64> for CompactSize {
+    fn from(number: u64) -> Self {
+        Self(number)
+    }
+}
+
+impl From<CompactSize> for u64 {
+    fn from(size: CompactSize) -> Self {
+        size.0
+    }
+}
+
+impl std::fmt::Display for CompactSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ByteFormat for CompactSize {
+    type Error = SerError;
+
+    fn serialized_length(&self) -> usize {
+        prefix_byte_len(self.0) as usize
+    }
+
+    fn read_from<R>(reader: &mut R) -> SerResult<Self>
+    where
+        R: Read,
+        Self: std::marker::Sized,
+    {
+        read_compact_int(reader).map(Self)
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> SerResult<usize>
+    where
+        W: Write,
+    {
+        write_compact_int(writer, self.0)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    #[cfg(feature = "hex")]
+    fn it_round_trips_compact_sizes() {
+        let cases = [
+            (0, "00"),
+            (0xfc, "fc"),
+            (0xfd, "fdfd00"),
+            (0xffff, "fdffff"),
+            (0x1_0000, "fe00000100"),
+            (0xffff_ffff, "feffffffff"),
+            (0x1_0000_0000, "ff0000000001000000"),
+            (u64::MAX, "ffffffffffffffffff"),
+        ];
+        for case in cases.iter() {
+            let size = CompactSize(case.0);
+            assert_eq!(size.serialized_length(), case.1.len() / 2);
+            assert_eq!(size.serialize_hex(), case.1);
+            assert_eq!(CompactSize::deserialize_hex(case.1).unwrap(), size);
+            assert_eq!(size.to_string(), case.0.to_string());
+        }
+    }
+
+    #[test]
+    fn it_round_trips_compact_sizes_as_bytes() {
+        // byte serialization must not depend on the `hex` feature
+        let cases = [
+            (0xfc, vec![0xfc]),
+            (0xfd, vec![0xfd, 0xfd, 0x00]),
+            (0x1_0000, vec![0xfe, 0x00, 0x00, 0x01, 0x00]),
+        ];
+        for case in cases.iter() {
+            let size = CompactSize(case.0);
+            let mut v = vec![];
+            assert_eq!(size.write_to(&mut v).unwrap(), case.1.len());
+            assert_eq!(v, case.1);
+            assert_eq!(CompactSize::read_from(&mut v.as_slice()).unwrap(), size);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "hex")]
+    fn it_rejects_non_minimal_compact_sizes() {
+        let cases = [
+            "fd0100",
+            "fdfc00",
+            "fe01000000",
+            "feffff0000",
+            "ff0100000000000000",
+        ];
+        for case in cases.iter() {
+            match CompactSize::deserialize_hex(case) {
+                Err(SerError::NonMinimalVarInt) => {}
+                _ => panic!("expected non-minimal varint error"),
+            }
+        }
+    }
+
     #[test]
     fn it_matches_byte_len_and_prefix() {
         let cases = [
@@ -432,6 +584,13 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_compares_serializations() {
+        assert!(CompactSize(0xfd).serialized_eq(&CompactSize(0xfd)));
+        assert!(!CompactSize(0xfd).serialized_eq(&CompactSize(0xfc)));
+        assert!(!CompactSize(0xfd).serialized_eq(&CompactSize(0xfe)));
+    }
+
     #[test]
     fn it_implements_seq_ops_for_u8() {
         let input = vec![0, 1, 2, 3, 4];
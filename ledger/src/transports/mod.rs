@@ -5,9 +5,9 @@
 pub mod hid;
 
 /// APDU Transport wrapper for JS/WASM transports
-#[cfg(all(target_arch = "wasm32"))]
+#[cfg(target_arch = "wasm32")]
 pub mod wasm;
-#[cfg(all(target_arch = "wasm32"))]
+#[cfg(target_arch = "wasm32")]
 pub use wasm::LedgerTransport as DefaultTransport;
 
 /// APDU Transport for native HID